@@ -1,5 +1,6 @@
 //! The context for a task processed by a `Worker`.
 use crate::atomic::{Atomic, AtomicBool};
+use crate::hive::ProgressTracker;
 use std::fmt::Debug;
 use std::sync::Arc;
 
@@ -8,6 +9,8 @@ use std::sync::Arc;
 pub struct Context {
     index: usize,
     cancelled: Arc<AtomicBool>,
+    group: Option<Arc<str>>,
+    progress: Option<Arc<ProgressTracker>>,
     #[cfg(feature = "retry")]
     attempt: u32,
 }
@@ -18,6 +21,8 @@ impl Context {
         Self {
             index,
             cancelled,
+            group: None,
+            progress: None,
             #[cfg(feature = "retry")]
             attempt: 0,
         }
@@ -33,6 +38,36 @@ impl Context {
         self.index
     }
 
+    /// The name of the task group to which this task belongs, if any.
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    /// Sets the task group to which this task belongs.
+    pub(crate) fn set_group(&mut self, group: Arc<str>) {
+        self.group = Some(group);
+    }
+
+    /// Returns the shared name of the task group to which this task belongs, if any.
+    pub(crate) fn group_name(&self) -> Option<&Arc<str>> {
+        self.group.as_ref()
+    }
+
+    /// Sets the tracker to which this task reports its progress.
+    pub(crate) fn set_progress(&mut self, tracker: Arc<ProgressTracker>) {
+        self.progress = Some(tracker);
+    }
+
+    /// Reports the progress of this task, where `fraction` is the fraction of the task that is
+    /// complete (between `0.0` and `1.0`), along with a descriptive `message`. The most recently
+    /// reported progress of each active task is available from `Hive::progress`. Does nothing if
+    /// the task is not being executed by a `Hive`.
+    pub fn report_progress<S: Into<String>>(&self, fraction: f64, message: S) {
+        if let Some(tracker) = self.progress.as_ref() {
+            tracker.report(self.index, fraction, message.into());
+        }
+    }
+
     /// Returns `true` if the task has been cancelled. A long-running `Worker` should check this
     /// periodically and, if it returns `true`, exit early with an `ApplyError::Cancelled` result.
     pub fn is_cancelled(&self) -> bool {
@@ -55,6 +55,15 @@
 //!   `RefWorker` calls `apply_ref` within a `Panic::try_call` closure and automatically handles the
 //!   result.
 //!
+//! Some operations (such as inserting rows into a database) are much cheaper when performed on
+//! many inputs at once. A `Hive` may be configured to execute tasks in batches using
+//! [`Builder::batch`](crate::hive::builder::Builder#batch), in which case each worker thread
+//! collects several queued tasks and passes their inputs to
+//! [`Worker::apply_batch`](crate::bee::worker::Worker#apply_batch). The easiest way to take
+//! advantage of this is to implement [`BatchWorker`](crate::bee::worker::BatchWorker) and wrap it
+//! in a [`Batched`](crate::bee::stock::Batched) worker. There is still one `Outcome` per task, and
+//! each input that fails with a retryable error is retried individually.
+//!
 //! ## Stock Workers
 //!
 //! The [`stock`](crate::bee::stock) Submodule provides some commonly used worker implementations:
@@ -73,7 +82,19 @@
 //!       which also have a common error type.
 //!     * `PunkWorker` is like `ThunkWorker` except that it processes thunks that may panic
 //!       (`Punk`s).
+//! * `ProcessWorker`: a worker that sends each input to a long-lived child process over its
+//!   `stdin` and reads the reply from its `stdout`. `ProcessWorker`s are created by a
+//!   `ProcessQueen`, which spawns one child process per worker thread and respawns it if it
+//!   crashes.
+//! * `Batched`: adapts a `BatchWorker` to the `Worker` trait.
 //! * `EchoWorker`: simply returns its input. This is primarily useful for testing.
+//! * `CachingWorker`: wraps another worker and memoizes its outputs in a `Cache` that may be shared
+//!   by workers on all threads. The values are kept in a pluggable `CacheStore`, which by default
+//!   is an `LruStore`. A `CachingQueen` wraps another queen and wraps each worker it creates in a
+//!   `CachingWorker`.
+//! * `ChaosWorker`: wraps another worker and randomly injects errors, panics, and latency, as
+//!   specified by a seeded `Chaos`. A `ChaosQueen` wraps another queen and wraps each worker it
+//!   creates in a `ChaosWorker`. These are useful for testing how code handles worker failures.
 //!
 //! # Queen
 //!
@@ -114,11 +135,13 @@ mod worker;
 pub use context::Context;
 pub use error::{ApplyError, ApplyRefError};
 pub use queen::{CloneQueen, DefaultQueen, Queen};
-pub use worker::{RefWorker, RefWorkerResult, Worker, WorkerError, WorkerResult};
+pub use worker::{
+    BatchWorker, BatchWorkerResult, RefWorker, RefWorkerResult, Worker, WorkerError, WorkerResult,
+};
 
 pub mod prelude {
     pub use super::{
-        ApplyError, ApplyRefError, Context, Queen, RefWorker, RefWorkerResult, Worker, WorkerError,
-        WorkerResult,
+        ApplyError, ApplyRefError, BatchWorker, BatchWorkerResult, Context, Queen, RefWorker,
+        RefWorkerResult, Worker, WorkerError, WorkerResult,
     };
 }
@@ -0,0 +1,290 @@
+use crate::bee::{BatchWorker, Context, Worker, WorkerResult};
+
+/// A `Worker` that wraps a `BatchWorker`. When the `Hive` is configured to execute tasks in
+/// batches (see `Builder::batch`), all of the inputs in each batch are passed to the wrapped
+/// worker's `apply_batch` method at once; otherwise, `apply_batch` is called with a single input.
+#[derive(Clone, Debug, Default)]
+pub struct Batched<B>(B);
+
+impl<B: BatchWorker> Batched<B> {
+    /// Returns a new `Batched` that wraps `inner`.
+    pub fn new(inner: B) -> Self {
+        Self(inner)
+    }
+
+    /// Returns a reference to the wrapped `BatchWorker`.
+    pub fn inner(&self) -> &B {
+        &self.0
+    }
+}
+
+impl<B: BatchWorker> From<B> for Batched<B> {
+    fn from(inner: B) -> Self {
+        Self(inner)
+    }
+}
+
+impl<B: BatchWorker> Worker for Batched<B> {
+    type Input = B::Input;
+    type Output = B::Output;
+    type Error = B::Error;
+
+    /// Calls the wrapped worker's `apply_batch` method with a batch of one input.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the wrapped worker does not return a result.
+    fn apply(&mut self, input: Self::Input, _: &Context) -> WorkerResult<Self> {
+        self.0
+            .apply_batch(vec![input])
+            .into_iter()
+            .next()
+            .expect("BatchWorker did not return a result")
+    }
+
+    fn apply_batch(&mut self, inputs: Vec<Self::Input>, _: &[Context]) -> Vec<WorkerResult<Self>> {
+        self.0.apply_batch(inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Batched;
+    use crate::bee::{ApplyError, BatchWorker, BatchWorkerResult, Context, Worker};
+    use crate::hive::{outcome_channel, Builder, Outcome, PanicPolicy};
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Doubles its inputs and records the size of each batch. Inputs greater than `max_input` are
+    /// left without a result.
+    #[derive(Clone, Debug)]
+    struct Doubler {
+        batch_sizes: Arc<Mutex<Vec<usize>>>,
+        max_input: usize,
+    }
+
+    impl Doubler {
+        fn new(max_input: usize) -> Self {
+            Self {
+                batch_sizes: Default::default(),
+                max_input,
+            }
+        }
+    }
+
+    impl BatchWorker for Doubler {
+        type Input = usize;
+        type Output = usize;
+        type Error = ();
+
+        fn apply_batch(&mut self, inputs: Vec<usize>) -> Vec<BatchWorkerResult<Self>> {
+            self.batch_sizes.lock().push(inputs.len());
+            inputs
+                .into_iter()
+                .take_while(|i| *i <= self.max_input)
+                .map(|i| Ok(i * 2))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut worker = Batched::new(Doubler::new(10));
+        assert_eq!(worker.apply(2, &Context::empty()).unwrap(), 4);
+        assert_eq!(*worker.inner().batch_sizes.lock(), vec![1]);
+    }
+
+    #[test]
+    fn test_batches() {
+        let doubler = Doubler::new(usize::MAX);
+        let batch_sizes = Arc::clone(&doubler.batch_sizes);
+        let hive = Builder::new()
+            .batch(4, Duration::from_millis(100))
+            .build_with(Batched::new(doubler))
+            .unwrap();
+        // queue all the tasks before starting a thread so that the batches are full
+        let indices = hive.map_store(0..10);
+        hive.grow(1);
+        hive.join();
+        let mut outcomes = hive.take_stored();
+        let outputs: Vec<_> = indices
+            .into_iter()
+            .map(|index| outcomes.remove(&index).unwrap().unwrap())
+            .collect();
+        assert_eq!(outputs, (0..10).map(|i| i * 2).collect::<Vec<_>>());
+        assert_eq!(*batch_sizes.lock(), vec![4, 4, 2]);
+    }
+
+    #[test]
+    fn test_batch_timeout() {
+        let doubler = Doubler::new(usize::MAX);
+        let batch_sizes = Arc::clone(&doubler.batch_sizes);
+        let hive = Builder::new()
+            .num_threads(1)
+            .batch(4, Duration::from_millis(10))
+            .build_with(Batched::new(doubler))
+            .unwrap();
+        // the batch is executed once the timeout elapses, even though it is not full
+        assert_eq!(hive.apply(3).unwrap(), 6);
+        assert_eq!(*batch_sizes.lock(), vec![1]);
+    }
+
+    #[test]
+    fn test_batch_rate_limit() {
+        let doubler = Doubler::new(usize::MAX);
+        let batch_sizes = Arc::clone(&doubler.batch_sizes);
+        let hive = Builder::new()
+            .num_threads(1)
+            .batch(4, Duration::from_millis(10))
+            .rate_limit(1, Duration::from_secs(60))
+            .rate_limit_burst(1)
+            .build_with(Batched::new(doubler))
+            .unwrap();
+        let (tx, rx) = outcome_channel();
+        hive.swarm_send(0..2, tx);
+        // the batch does not wait for the rate-limited task beyond the batch timeout
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap().unwrap(), 0);
+        assert_eq!(*batch_sizes.lock(), vec![1]);
+    }
+
+    #[test]
+    fn test_missing_results() {
+        let hive = Builder::new()
+            .batch(10, Duration::from_millis(100))
+            .build_with(Batched::new(Doubler::new(4)))
+            .unwrap();
+        let indices = hive.map_store(0..10);
+        hive.grow(1);
+        hive.join();
+        let outcomes = hive.take_stored();
+        assert_eq!(outcomes.len(), 10);
+        for (i, index) in indices.into_iter().enumerate() {
+            match &outcomes[&index] {
+                Outcome::Success { value, .. } => assert_eq!(*value, i * 2),
+                Outcome::Missing { .. } => assert!(i > 4),
+                outcome => panic!("unexpected outcome {:?}", outcome),
+            }
+        }
+    }
+
+    /// Panics if the batch contains a `0`.
+    #[derive(Clone, Debug, Default)]
+    struct PanicOnZero;
+
+    impl BatchWorker for PanicOnZero {
+        type Input = usize;
+        type Output = usize;
+        type Error = ();
+
+        fn apply_batch(&mut self, inputs: Vec<usize>) -> Vec<BatchWorkerResult<Self>> {
+            assert!(!inputs.contains(&0), "oh no!");
+            inputs.into_iter().map(Ok).collect()
+        }
+    }
+
+    #[test]
+    fn test_panic() {
+        let hive = Builder::new()
+            .batch(5, Duration::from_millis(100))
+            .build_with_default::<Batched<PanicOnZero>>()
+            .unwrap();
+        let indices = hive.map_store(0..10);
+        hive.grow(1);
+        // all the tasks in the panicking batch are finished, so `join` does not block forever
+        hive.join();
+        assert_eq!(hive.num_panics(), 1);
+        assert_eq!(hive.num_tasks(), (0, 0));
+        let outcomes = hive.take_stored();
+        // the outcomes of the tasks in the panicking batch are lost
+        assert_eq!(outcomes.len(), 5);
+        assert!(indices[5..]
+            .iter()
+            .all(|index| outcomes[index].is_success()));
+    }
+
+    #[test]
+    fn test_panic_propagate() {
+        let hive = Builder::new()
+            .batch(5, Duration::from_millis(100))
+            .panic_policy(PanicPolicy::Propagate)
+            .build_with_default::<Batched<PanicOnZero>>()
+            .unwrap();
+        let indices = hive.map_store(0..10);
+        hive.grow(1);
+        hive.join();
+        let outcomes = hive.take_stored();
+        // every task in the panicking batch has the panic
+        assert_eq!(outcomes.len(), 10);
+        for index in &indices[..5] {
+            match &outcomes[index] {
+                Outcome::Panic { payload, .. } => {
+                    let payload = payload.payload();
+                    let message = payload
+                        .downcast_ref::<&str>()
+                        .copied()
+                        .or_else(|| payload.downcast_ref::<String>().map(String::as_str));
+                    assert_eq!(message, Some("oh no!"));
+                }
+                outcome => panic!("unexpected outcome {:?}", outcome),
+            }
+        }
+        assert!(indices[5..]
+            .iter()
+            .all(|index| outcomes[index].is_success()));
+    }
+
+    /// Fails with a retryable error the first time it sees an odd input.
+    #[derive(Clone, Debug, Default)]
+    struct FailOdd {
+        seen: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl BatchWorker for FailOdd {
+        type Input = usize;
+        type Output = usize;
+        type Error = ();
+
+        fn apply_batch(&mut self, inputs: Vec<usize>) -> Vec<BatchWorkerResult<Self>> {
+            let mut seen = self.seen.lock();
+            inputs
+                .into_iter()
+                .map(|input| {
+                    if input % 2 == 1 && !seen.contains(&input) {
+                        seen.push(input);
+                        Err(ApplyError::Retryable { input, error: () })
+                    } else {
+                        Ok(input)
+                    }
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_failures() {
+        let hive = Builder::new()
+            .num_threads(2)
+            .batch(5, Duration::from_millis(10))
+            .build_with_default::<Batched<FailOdd>>()
+            .unwrap();
+        for outcome in hive.map(0..10) {
+            assert_eq!(outcome.is_failure(), outcome.index() % 2 == 1);
+        }
+    }
+
+    #[cfg(feature = "retry")]
+    #[test]
+    fn test_retries() {
+        use crate::hive::OutcomeIteratorExt;
+        let hive = Builder::new()
+            .num_threads(2)
+            .batch(5, Duration::from_millis(10))
+            .max_retries(1)
+            .build_with_default::<Batched<FailOdd>>()
+            .unwrap();
+        // each odd input is retried individually and then succeeds
+        let outputs: Vec<_> = hive.map(0..10).into_outputs().collect();
+        assert_eq!(outputs, (0..10).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,656 @@
+//! A `Worker` wrapper that memoizes the outputs of another `Worker`.
+use crate::bee::{Context, Queen, Worker, WorkerResult};
+use crate::hive::{Clock, ClockRef};
+use parking_lot::{Condvar, Mutex};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{self, Debug};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A thread-safe store of the values memoized by a `Cache`. The store decides which values to
+/// keep and for how long; the `Cache` takes care of deduplicating in-flight computations and of
+/// counting hits and misses.
+///
+/// `LruStore` is the default implementation. Implement this trait to use a different eviction
+/// strategy or an external store.
+pub trait CacheStore<K, V>: Debug + Send + Sync + 'static {
+    /// Returns the value for `key` if it is in the store (and has not expired).
+    fn get(&self, key: &K) -> Option<V>;
+
+    /// Inserts `value` for `key`, replacing any existing value.
+    fn insert(&self, key: K, value: V);
+
+    /// Returns the number of values in the store.
+    fn len(&self) -> usize;
+
+    /// Returns `true` if there are no values in the store.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all values from the store.
+    fn clear(&self);
+}
+
+struct Entry<V> {
+    value: V,
+    inserted: Instant,
+    // the "time" at which the entry was inserted, which is its key in `LruState::insertions`
+    inserted_tick: u64,
+    // the "time" at which the entry was last used, which is its key in `LruState::lru`
+    tick: u64,
+}
+
+struct LruState<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    // keys ordered from least- to most-recently used
+    lru: BTreeMap<u64, K>,
+    // keys ordered from least- to most-recently inserted, i.e., in the order they expire
+    insertions: BTreeMap<u64, K>,
+    next_tick: u64,
+}
+
+impl<K: Hash + Eq + Clone, V> LruState<K, V> {
+    fn tick(&mut self) -> u64 {
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        tick
+    }
+
+    fn touch(&mut self, key: &K) {
+        let tick = self.tick();
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.lru.remove(&entry.tick);
+            entry.tick = tick;
+            self.lru.insert(tick, key.clone());
+        }
+    }
+
+    fn remove(&mut self, key: &K) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.lru.remove(&entry.tick);
+            self.insertions.remove(&entry.inserted_tick);
+        }
+    }
+
+    /// Removes all entries that were inserted more than `ttl` before `now`.
+    fn remove_expired(&mut self, ttl: Duration, now: Instant) {
+        while let Some((_, key)) = self.insertions.first_key_value() {
+            let expired = self
+                .entries
+                .get(key)
+                .is_some_and(|entry| now.saturating_duration_since(entry.inserted) > ttl);
+            if !expired {
+                break;
+            }
+            let key = key.clone();
+            self.remove(&key);
+        }
+    }
+}
+
+/// A `CacheStore` that holds at most `capacity` values, evicting the least-recently used value
+/// when it is full. If a time-to-live is specified, values are removed once they are older than
+/// the TTL. Expired values are removed whenever the store is accessed, not only when they are
+/// looked up.
+///
+/// # Examples
+///
+/// ```
+/// use beekeeper::bee::stock::{CacheStore, LruStore};
+/// use beekeeper::hive::ManualClock;
+/// use std::time::Duration;
+///
+/// let clock = ManualClock::new();
+/// let store = LruStore::new(10)
+///     .ttl(Duration::from_secs(60))
+///     .clock(clock.clone());
+/// store.insert(1, "one");
+/// assert_eq!(store.get(&1), Some("one"));
+/// clock.advance(Duration::from_secs(61));
+/// assert_eq!(store.get(&1), None);
+/// assert!(store.is_empty());
+/// ```
+pub struct LruStore<K, V> {
+    state: Mutex<LruState<K, V>>,
+    capacity: usize,
+    ttl: Option<Duration>,
+    clock: ClockRef,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> LruStore<K, V> {
+    /// Returns a new `LruStore` that holds at most `capacity` values.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(LruState {
+                entries: HashMap::new(),
+                lru: BTreeMap::new(),
+                insertions: BTreeMap::new(),
+                next_tick: 0,
+            }),
+            capacity,
+            ttl: None,
+            clock: ClockRef::default(),
+        }
+    }
+
+    /// Sets the maximum time for which a value is kept in the store.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Sets the `Clock` used to determine when values expire. Defaults to the `SystemClock`.
+    pub fn clock<C: Clock>(mut self, clock: C) -> Self {
+        self.clock = ClockRef::new(clock);
+        self
+    }
+
+    /// Locks the state and removes any expired entries.
+    fn lock(&self) -> parking_lot::MutexGuard<'_, LruState<K, V>> {
+        let mut state = self.state.lock();
+        if let Some(ttl) = self.ttl {
+            state.remove_expired(ttl, self.clock.now());
+        }
+        state
+    }
+}
+
+impl<K, V> CacheStore<K, V> for LruStore<K, V>
+where
+    K: Hash + Eq + Clone + Send + 'static,
+    V: Clone + Send + 'static,
+{
+    fn get(&self, key: &K) -> Option<V> {
+        let mut state = self.lock();
+        let value = state.entries.get(key)?.value.clone();
+        state.touch(key);
+        Some(value)
+    }
+
+    fn insert(&self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut state = self.lock();
+        state.remove(&key);
+        let tick = state.tick();
+        state.lru.insert(tick, key.clone());
+        state.insertions.insert(tick, key.clone());
+        let entry = Entry {
+            value,
+            inserted: self.clock.now(),
+            inserted_tick: tick,
+            tick,
+        };
+        state.entries.insert(key, entry);
+        while state.entries.len() > self.capacity {
+            if let Some((_, oldest)) = state.lru.pop_first() {
+                state.remove(&oldest);
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    fn clear(&self) {
+        let mut state = self.state.lock();
+        state.entries.clear();
+        state.lru.clear();
+        state.insertions.clear();
+    }
+}
+
+impl<K, V> Debug for LruStore<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LruStore")
+            .field("capacity", &self.capacity)
+            .field("ttl", &self.ttl)
+            .field("clock", &self.clock)
+            .finish()
+    }
+}
+
+/// Result of looking up a key in a `Cache`.
+enum Lookup<'a, K: Hash + Eq + Clone, V: Clone, S: CacheStore<K, V>> {
+    /// The value was found in the cache.
+    Hit(V),
+    /// The value was not found, and the caller is responsible for computing it.
+    Miss(Claim<'a, K, V, S>),
+}
+
+/// A claim on computing the value for a key. Other threads looking up the same key wait until the
+/// claim is dropped. If the claim is dropped without a value having been inserted (e.g., because
+/// the `Worker` returned an error or panicked), then one of the waiting threads claims the key.
+struct Claim<'a, K: Hash + Eq + Clone, V: Clone, S: CacheStore<K, V>> {
+    cache: &'a Cache<K, V, S>,
+    key: Option<K>,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, S: CacheStore<K, V>> Claim<'_, K, V, S> {
+    /// Inserts the computed `value` into the cache and releases the claim.
+    fn complete(mut self, value: V) {
+        if let Some(key) = self.key.take() {
+            self.cache.insert(key, value);
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, S: CacheStore<K, V>> Drop for Claim<'_, K, V, S> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.cache.release(&key);
+        }
+    }
+}
+
+/// A thread-safe cache of `Worker` outputs, which may be shared by `CachingWorker`s on multiple
+/// threads. The values are kept in a `CacheStore`, which by default is an `LruStore`.
+///
+/// A thread that looks up a key whose value is currently being computed by another thread waits
+/// for that computation to finish rather than duplicating it.
+pub struct Cache<K, V, S = LruStore<K, V>> {
+    store: S,
+    // keys whose values are currently being computed
+    in_flight: Mutex<HashSet<K>>,
+    released: Condvar,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    _value: PhantomData<fn() -> V>,
+}
+
+impl<K: Hash + Eq + Clone + Send + 'static, V: Clone + Send + 'static> Cache<K, V> {
+    /// Returns a new `Cache` that holds at most `capacity` values in an `LruStore`.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_store(LruStore::new(capacity))
+    }
+
+    /// Returns a new `Cache` that holds at most `capacity` values, each for at most `ttl`, in an
+    /// `LruStore`.
+    pub fn with_ttl(capacity: usize, ttl: Duration) -> Self {
+        Self::with_store(LruStore::new(capacity).ttl(ttl))
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, S: CacheStore<K, V>> Cache<K, V, S> {
+    /// Returns a new `Cache` that holds its values in `store`.
+    pub fn with_store(store: S) -> Self {
+        Self {
+            store,
+            in_flight: Mutex::new(HashSet::new()),
+            released: Condvar::new(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            _value: PhantomData,
+        }
+    }
+
+    /// Returns the store that holds the values of this cache.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Returns the number of lookups that found a value in the cache (including those that waited
+    /// for the value to be computed by another thread).
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of lookups that did not find a value in the cache.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of values in the cache.
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    /// Returns `true` if there are no values in the cache.
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+
+    /// Removes all values from the cache.
+    pub fn clear(&self) {
+        self.store.clear();
+    }
+
+    /// Returns the value for `key` if it is in the cache, otherwise claims the key for the current
+    /// thread. Blocks while the key is claimed by another thread.
+    fn lookup(&self, key: &K) -> Lookup<'_, K, V, S> {
+        let mut in_flight = self.in_flight.lock();
+        loop {
+            if let Some(value) = self.store.get(key) {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Lookup::Hit(value);
+            }
+            if !in_flight.contains(key) {
+                break;
+            }
+            self.released.wait(&mut in_flight);
+        }
+        in_flight.insert(key.clone());
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        Lookup::Miss(Claim {
+            cache: self,
+            key: Some(key.clone()),
+        })
+    }
+
+    /// Inserts a value into the store and releases the claim on the key.
+    fn insert(&self, key: K, value: V) {
+        let mut in_flight = self.in_flight.lock();
+        in_flight.remove(&key);
+        self.store.insert(key, value);
+        drop(in_flight);
+        self.released.notify_all();
+    }
+
+    /// Releases the claim on a key without inserting a value.
+    fn release(&self, key: &K) {
+        self.in_flight.lock().remove(key);
+        self.released.notify_all();
+    }
+}
+
+impl<K, V, S: Debug> Debug for Cache<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cache")
+            .field("store", &self.store)
+            .field("hits", &self.hits.load(Ordering::Relaxed))
+            .field("misses", &self.misses.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// A `Cache` of the outputs of `Worker` type `W`, keyed by input.
+pub type WorkerCache<W, S = LruStore<<W as Worker>::Input, <W as Worker>::Output>> =
+    Cache<<W as Worker>::Input, <W as Worker>::Output, S>;
+
+/// A `Worker` that wraps another `Worker` and memoizes its successful outputs in a `Cache`, which
+/// may be shared with other `CachingWorker`s. Errors are not cached.
+///
+/// A `CachingWorker` can be cloned (if the wrapped `Worker` can be cloned), and all clones share
+/// the same cache. Alternatively, use a `CachingQueen` to wrap the `Worker`s created by another
+/// `Queen`.
+///
+/// # Examples
+///
+/// ```
+/// use beekeeper::bee::stock::{Cache, CachingWorker, Caller};
+/// use beekeeper::hive::{Builder, OutcomeIteratorExt};
+/// use std::sync::Arc;
+///
+/// # fn main() {
+/// let cache = Arc::new(Cache::new(100));
+/// let worker = CachingWorker::new(Caller::of(|i: u64| i * i), Arc::clone(&cache));
+/// let hive = Builder::new().num_threads(4).build_with(worker).unwrap();
+/// // only 10 distinct inputs, so there are only 10 misses
+/// let squares = hive.map((0..100).map(|i| i % 10)).into_outputs().collect::<Vec<_>>();
+/// assert_eq!(squares[15], 25);
+/// assert_eq!(cache.misses(), 10);
+/// assert_eq!(cache.hits(), 90);
+/// # }
+/// ```
+pub struct CachingWorker<W: Worker, S = LruStore<<W as Worker>::Input, <W as Worker>::Output>> {
+    inner: W,
+    cache: Arc<WorkerCache<W, S>>,
+}
+
+impl<W, S> CachingWorker<W, S>
+where
+    W: Worker,
+    W::Input: Hash + Eq + Clone,
+    W::Output: Clone,
+    S: CacheStore<W::Input, W::Output>,
+{
+    /// Returns a new `CachingWorker` that wraps `inner` and stores its outputs in `cache`.
+    pub fn new(inner: W, cache: Arc<WorkerCache<W, S>>) -> Self {
+        Self { inner, cache }
+    }
+
+    /// Returns the cache used by this `CachingWorker`.
+    pub fn cache(&self) -> &Arc<WorkerCache<W, S>> {
+        &self.cache
+    }
+}
+
+impl<W: Worker + Clone, S> Clone for CachingWorker<W, S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            cache: Arc::clone(&self.cache),
+        }
+    }
+}
+
+impl<W: Worker, S: Debug> Debug for CachingWorker<W, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CachingWorker")
+            .field("inner", &self.inner)
+            .field("cache", &self.cache)
+            .finish()
+    }
+}
+
+impl<W, S> Worker for CachingWorker<W, S>
+where
+    W: Worker,
+    W::Input: Hash + Eq + Clone,
+    W::Output: Clone,
+    S: CacheStore<W::Input, W::Output>,
+{
+    type Input = W::Input;
+    type Output = W::Output;
+    type Error = W::Error;
+
+    fn apply(&mut self, input: Self::Input, ctx: &Context) -> WorkerResult<Self> {
+        match self.cache.lookup(&input) {
+            Lookup::Hit(value) => Ok(value),
+            Lookup::Miss(claim) => {
+                let result = self.inner.apply(input, ctx);
+                if let Ok(value) = &result {
+                    claim.complete(value.clone());
+                }
+                result
+            }
+        }
+    }
+}
+
+/// A `Queen` that wraps another `Queen` and wraps each `Worker` it creates in a `CachingWorker`.
+/// All of the `CachingWorker`s share the same `Cache`.
+#[derive(Debug)]
+pub struct CachingQueen<
+    Q: Queen,
+    S = LruStore<<<Q as Queen>::Kind as Worker>::Input, <<Q as Queen>::Kind as Worker>::Output>,
+> {
+    inner: Q,
+    cache: Arc<WorkerCache<Q::Kind, S>>,
+}
+
+impl<Q: Queen, S> CachingQueen<Q, S>
+where
+    <Q::Kind as Worker>::Input: Hash + Eq + Clone,
+    <Q::Kind as Worker>::Output: Clone,
+    S: CacheStore<<Q::Kind as Worker>::Input, <Q::Kind as Worker>::Output>,
+{
+    /// Returns a new `CachingQueen` that wraps `inner` and creates `CachingWorker`s that share
+    /// `cache`.
+    pub fn new(inner: Q, cache: Arc<WorkerCache<Q::Kind, S>>) -> Self {
+        Self { inner, cache }
+    }
+}
+
+impl<Q: Queen, S> Queen for CachingQueen<Q, S>
+where
+    <Q::Kind as Worker>::Input: Hash + Eq + Clone,
+    <Q::Kind as Worker>::Output: Clone,
+    S: CacheStore<<Q::Kind as Worker>::Input, <Q::Kind as Worker>::Output>,
+{
+    type Kind = CachingWorker<Q::Kind, S>;
+
+    fn create(&mut self) -> Self::Kind {
+        CachingWorker::new(self.inner.create(), Arc::clone(&self.cache))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cache, CacheStore, CachingQueen, CachingWorker, LruStore};
+    use crate::bee::stock::{Caller, EchoWorker, OnceCaller};
+    use crate::bee::{Context, DefaultQueen, Worker};
+    use crate::hive::{Builder, ManualClock, OutcomeIteratorExt};
+    use parking_lot::Mutex;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_lru() {
+        let cache = Arc::new(Cache::new(2));
+        let mut worker = CachingWorker::new(EchoWorker::<u8>::default(), Arc::clone(&cache));
+        let ctx = Context::empty();
+        for i in [1, 2, 1, 3, 1, 2] {
+            assert_eq!(worker.apply(i, &ctx).unwrap(), i);
+        }
+        // 2 was evicted when 3 was inserted because 1 was used more recently
+        assert_eq!(cache.hits(), 2);
+        assert_eq!(cache.misses(), 4);
+        assert_eq!(cache.len(), 2);
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_ttl() {
+        let clock = ManualClock::new();
+        let store = LruStore::new(10)
+            .ttl(Duration::from_millis(100))
+            .clock(clock.clone());
+        let cache = Arc::new(Cache::with_store(store));
+        let mut worker = CachingWorker::new(EchoWorker::<u8>::default(), Arc::clone(&cache));
+        let ctx = Context::empty();
+        worker.apply(1, &ctx).unwrap();
+        worker.apply(1, &ctx).unwrap();
+        clock.advance(Duration::from_millis(200));
+        worker.apply(1, &ctx).unwrap();
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(cache.misses(), 2);
+    }
+
+    #[test]
+    fn test_expired_removed() {
+        let clock = ManualClock::new();
+        let store = LruStore::new(10)
+            .ttl(Duration::from_secs(10))
+            .clock(clock.clone());
+        store.insert(1, 1);
+        clock.advance(Duration::from_secs(5));
+        store.insert(2, 2);
+        // using 1 does not extend its lifetime
+        assert_eq!(store.get(&1), Some(1));
+        clock.advance(Duration::from_secs(6));
+        // 1 is removed even though it is not looked up
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.get(&2), Some(2));
+        clock.advance(Duration::from_secs(5));
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_errors_not_cached() {
+        let cache = Arc::new(Cache::new(10));
+        let worker = OnceCaller::of(|i: u8| if i == 0 { Err("zero") } else { Ok(i) });
+        let mut worker = CachingWorker::new(worker, Arc::clone(&cache));
+        let ctx = Context::empty();
+        assert!(worker.apply(0, &ctx).is_err());
+        assert!(worker.apply(0, &ctx).is_err());
+        assert_eq!(cache.misses(), 2);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_in_flight() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let worker = {
+            let calls = Arc::clone(&calls);
+            Caller::of(move |i: u8| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(500));
+                i
+            })
+        };
+        let cache = Arc::new(Cache::new(10));
+        let hive = Builder::new()
+            .num_threads(4)
+            .build_with(CachingWorker::new(worker, Arc::clone(&cache)))
+            .unwrap();
+        let outputs = hive.swarm([7; 4]).into_outputs().collect::<Vec<_>>();
+        assert_eq!(outputs, vec![7; 4]);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.hits(), 3);
+        assert_eq!(cache.misses(), 1);
+    }
+
+    #[test]
+    fn test_queen() {
+        let cache = Arc::new(Cache::new(10));
+        let queen = CachingQueen::new(
+            DefaultQueen::<EchoWorker<u8>>::default(),
+            Arc::clone(&cache),
+        );
+        let hive = Builder::new().num_threads(2).build(queen).unwrap();
+        let outputs = hive.map([1, 2, 1, 2]).into_outputs().collect::<Vec<_>>();
+        assert_eq!(outputs, vec![1, 2, 1, 2]);
+        assert_eq!(cache.hits() + cache.misses(), 4);
+        assert_eq!(cache.len(), 2);
+    }
+
+    /// A store that never evicts anything.
+    #[derive(Debug, Default)]
+    struct MapStore(Mutex<HashMap<u8, u8>>);
+
+    impl CacheStore<u8, u8> for MapStore {
+        fn get(&self, key: &u8) -> Option<u8> {
+            self.0.lock().get(key).copied()
+        }
+
+        fn insert(&self, key: u8, value: u8) {
+            self.0.lock().insert(key, value);
+        }
+
+        fn len(&self) -> usize {
+            self.0.lock().len()
+        }
+
+        fn clear(&self) {
+            self.0.lock().clear();
+        }
+    }
+
+    #[test]
+    fn test_custom_store() {
+        let cache = Arc::new(Cache::with_store(MapStore::default()));
+        let hive = Builder::new()
+            .num_threads(2)
+            .build_with(CachingWorker::new(
+                Caller::of(|i: u8| i),
+                Arc::clone(&cache),
+            ))
+            .unwrap();
+        let outputs = hive.map((0..100).map(|i| i % 20)).into_outputs();
+        assert_eq!(outputs.count(), 100);
+        assert_eq!(cache.misses(), 20);
+        assert_eq!(cache.hits(), 80);
+        assert_eq!(cache.store().len(), 20);
+    }
+}
@@ -0,0 +1,355 @@
+//! `Worker` and `Queen` wrappers that inject faults, for testing how code handles failures.
+use crate::bee::{ApplyError, Context, Queen, Worker, WorkerResult};
+use std::fmt::Debug;
+use std::thread;
+use std::time::Duration;
+
+/// Specifies the faults injected by a `ChaosWorker`.
+///
+/// Each call to `apply` is turned into a `Retryable` error, a `Fatal` error, or a panic with the
+/// configured probabilities (at most one of these faults is injected per call). Independently, a
+/// random delay of up to `max_latency` is added before a call with the configured `latency`
+/// probability. Probabilities are clamped to `0.0..=1.0`.
+///
+/// The random number generator is seeded with `seed`, so a `ChaosWorker` always injects the same
+/// sequence of faults.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Chaos {
+    seed: u64,
+    retryable: f64,
+    fatal: f64,
+    panic: f64,
+    latency: f64,
+    max_latency: Duration,
+}
+
+impl Chaos {
+    /// Returns a new `Chaos` that uses the given `seed` and does not inject any faults.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the probability that a call results in an `ApplyError::Retryable` error.
+    pub fn retryable(mut self, probability: f64) -> Self {
+        self.retryable = probability.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the probability that a call results in an `ApplyError::Fatal` error.
+    pub fn fatal(mut self, probability: f64) -> Self {
+        self.fatal = probability.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the probability that a call panics.
+    pub fn panic(mut self, probability: f64) -> Self {
+        self.panic = probability.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the probability that a call is delayed by a random duration of up to `max_latency`.
+    pub fn latency(mut self, probability: f64, max_latency: Duration) -> Self {
+        self.latency = probability.clamp(0.0, 1.0);
+        self.max_latency = max_latency;
+        self
+    }
+
+    /// Returns a copy of this `Chaos` with a different seed.
+    fn with_seed(&self, seed: u64) -> Self {
+        Self {
+            seed,
+            ..self.clone()
+        }
+    }
+}
+
+/// A fault injected by a `ChaosWorker`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Fault {
+    Retryable,
+    Fatal,
+    Panic,
+}
+
+/// Error type of a `ChaosWorker`.
+#[derive(thiserror::Error, Debug)]
+pub enum ChaosError<E: Debug> {
+    /// The error was injected by the `ChaosWorker`.
+    #[error("Fault injected by ChaosWorker")]
+    Injected,
+    /// The error was returned by the wrapped `Worker`.
+    #[error("{0:?}")]
+    Inner(E),
+}
+
+/// A small, fast pseudo-random number generator (SplitMix64). This is not suitable for anything
+/// other than injecting faults.
+#[derive(Debug)]
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a random number in the range `0.0..1.0`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// A `Worker` that wraps another `Worker` and randomly injects the faults specified by a `Chaos`
+/// before delegating to the wrapped `Worker`.
+///
+/// # Examples
+///
+/// ```
+/// use beekeeper::bee::stock::{Caller, Chaos, ChaosWorker};
+/// use beekeeper::hive::{Builder, OutcomeIteratorExt};
+///
+/// # fn main() {
+/// let chaos = Chaos::new(42).fatal(0.5);
+/// let hive = Builder::new()
+///     .num_threads(4)
+///     .build_with(ChaosWorker::new(Caller::of(|i: u32| i * 2), chaos))
+///     .unwrap();
+/// let (successes, failures): (Vec<_>, Vec<_>) = hive
+///     .map(0..100)
+///     .into_results()
+///     .partition(Result::is_ok);
+/// assert!(!successes.is_empty());
+/// assert!(!failures.is_empty());
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ChaosWorker<W> {
+    inner: W,
+    chaos: Chaos,
+    rng: Rng,
+}
+
+impl<W: Worker> ChaosWorker<W> {
+    /// Returns a new `ChaosWorker` that wraps `inner` and injects faults according to `chaos`.
+    pub fn new(inner: W, chaos: Chaos) -> Self {
+        let rng = Rng(chaos.seed);
+        Self { inner, chaos, rng }
+    }
+
+    /// Returns a reference to the wrapped `Worker`.
+    pub fn inner(&self) -> &W {
+        &self.inner
+    }
+
+    /// Randomly chooses the fault (if any) to inject into the next call.
+    fn next_fault(&mut self) -> Option<Fault> {
+        let n = self.rng.next_f64();
+        let mut threshold = 0.0;
+        [
+            (Fault::Retryable, self.chaos.retryable),
+            (Fault::Fatal, self.chaos.fatal),
+            (Fault::Panic, self.chaos.panic),
+        ]
+        .into_iter()
+        .find_map(|(fault, probability)| {
+            threshold += probability;
+            (n < threshold).then_some(fault)
+        })
+    }
+
+    /// Randomly chooses the latency (if any) to add to the next call.
+    fn next_latency(&mut self) -> Option<Duration> {
+        (self.rng.next_f64() < self.chaos.latency)
+            .then(|| self.chaos.max_latency.mul_f64(self.rng.next_f64()))
+    }
+}
+
+/// Cloning a `ChaosWorker` also clones the state of its random number generator.
+impl<W: Clone> Clone for ChaosWorker<W> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            chaos: self.chaos.clone(),
+            rng: Rng(self.rng.0),
+        }
+    }
+}
+
+impl<W: Worker> Worker for ChaosWorker<W> {
+    type Input = W::Input;
+    type Output = W::Output;
+    type Error = ChaosError<W::Error>;
+
+    fn apply(&mut self, input: Self::Input, ctx: &Context) -> WorkerResult<Self> {
+        if let Some(latency) = self.next_latency() {
+            thread::sleep(latency);
+        }
+        match self.next_fault() {
+            Some(Fault::Retryable) => Err(ApplyError::Retryable {
+                input,
+                error: ChaosError::Injected,
+            }),
+            Some(Fault::Fatal) => Err(ApplyError::Fatal {
+                input: Some(input),
+                error: ChaosError::Injected,
+            }),
+            Some(Fault::Panic) => panic!("panic injected by ChaosWorker"),
+            None => self.inner.apply(input, ctx).map_err(|error| match error {
+                ApplyError::Fatal { input, error } => ApplyError::Fatal {
+                    input,
+                    error: ChaosError::Inner(error),
+                },
+                ApplyError::Retryable { input, error } => ApplyError::Retryable {
+                    input,
+                    error: ChaosError::Inner(error),
+                },
+                ApplyError::Cancelled { input } => ApplyError::Cancelled { input },
+                ApplyError::Panic { input, payload } => ApplyError::Panic { input, payload },
+            }),
+        }
+    }
+}
+
+/// A `Queen` that wraps another `Queen` and wraps each `Worker` it creates in a `ChaosWorker`.
+///
+/// Each `ChaosWorker` is seeded with a different value derived from the seed of the `Chaos`, so
+/// workers inject different (but reproducible) sequences of faults.
+#[derive(Debug)]
+pub struct ChaosQueen<Q> {
+    inner: Q,
+    chaos: Chaos,
+    rng: Rng,
+}
+
+impl<Q: Queen> ChaosQueen<Q> {
+    /// Returns a new `ChaosQueen` that wraps `inner` and creates `ChaosWorker`s that inject
+    /// faults according to `chaos`.
+    pub fn new(inner: Q, chaos: Chaos) -> Self {
+        let rng = Rng(chaos.seed);
+        Self { inner, chaos, rng }
+    }
+}
+
+impl<Q: Queen> Queen for ChaosQueen<Q> {
+    type Kind = ChaosWorker<Q::Kind>;
+
+    fn create(&mut self) -> Self::Kind {
+        let chaos = self.chaos.with_seed(self.rng.next_u64());
+        ChaosWorker::new(self.inner.create(), chaos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Chaos, ChaosError, ChaosQueen, ChaosWorker, Rng};
+    use crate::bee::stock::{Caller, EchoWorker};
+    use crate::bee::{ApplyError, Context, DefaultQueen, Queen, Worker};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_rng() {
+        let mut rng = Rng(0);
+        assert!((0..1000)
+            .map(|_| rng.next_f64())
+            .all(|n| (0.0..1.0).contains(&n)));
+        let mut a = Rng(7);
+        let mut b = Rng(7);
+        assert!((0..100).all(|_| a.next_u64() == b.next_u64()));
+    }
+
+    #[test]
+    fn test_no_chaos() {
+        let mut worker = ChaosWorker::new(Caller::of(|i: u8| i + 1), Chaos::new(1));
+        let ctx = Context::empty();
+        assert!((0..100).all(|i| worker.apply(i, &ctx).unwrap() == i + 1));
+    }
+
+    #[test]
+    fn test_errors() {
+        let chaos = Chaos::new(1).retryable(0.25).fatal(0.25);
+        let mut worker = ChaosWorker::new(Caller::of(|i: u32| i), chaos);
+        let ctx = Context::empty();
+        let (mut ok, mut retryable, mut fatal) = (0, 0, 0);
+        for i in 0..1000 {
+            match worker.apply(i, &ctx) {
+                Ok(output) => {
+                    assert_eq!(output, i);
+                    ok += 1
+                }
+                Err(ApplyError::Retryable {
+                    input,
+                    error: ChaosError::Injected,
+                }) => {
+                    assert_eq!(input, i);
+                    retryable += 1
+                }
+                Err(ApplyError::Fatal {
+                    input: Some(input),
+                    error: ChaosError::Injected,
+                }) => {
+                    assert_eq!(input, i);
+                    fatal += 1
+                }
+                Err(_) => panic!("unexpected error"),
+            }
+        }
+        assert!((400..600).contains(&ok));
+        assert!((150..350).contains(&retryable));
+        assert!((150..350).contains(&fatal));
+    }
+
+    #[test]
+    fn test_reproducible() {
+        let chaos = Chaos::new(5).retryable(0.2).fatal(0.2);
+        let mut worker1 = ChaosWorker::new(Caller::of(|i: u32| i), chaos.clone());
+        let mut worker2 = ChaosWorker::new(Caller::of(|i: u32| i), chaos);
+        let ctx = Context::empty();
+        assert!((0..100).all(|i| worker1.apply(i, &ctx).is_ok() == worker2.apply(i, &ctx).is_ok()));
+    }
+
+    #[test]
+    fn test_panic() {
+        let mut worker = ChaosWorker::new(Caller::of(|i: u8| i), Chaos::new(1).panic(1.0));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            worker.apply(0, &Context::empty())
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_latency() {
+        let chaos = Chaos::new(1).latency(1.0, Duration::from_millis(50));
+        let mut worker = ChaosWorker::new(Caller::of(|i: u8| i), chaos);
+        let start = Instant::now();
+        for i in 0..10 {
+            assert_eq!(worker.apply(i, &Context::empty()).unwrap(), i);
+        }
+        assert!(start.elapsed() > Duration::from_millis(50));
+        assert!(start.elapsed() < Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_queen() {
+        let mut queen = ChaosQueen::new(
+            DefaultQueen::<EchoWorker<u8>>::default(),
+            Chaos::new(3).fatal(0.5),
+        );
+        let mut worker1 = queen.create();
+        let mut worker2 = queen.create();
+        assert_ne!(worker1.chaos.seed, worker2.chaos.seed);
+        let ctx = Context::empty();
+        let results1 = (0..64)
+            .map(|i| worker1.apply(i, &ctx).is_ok())
+            .collect::<Vec<_>>();
+        let results2 = (0..64)
+            .map(|i| worker2.apply(i, &ctx).is_ok())
+            .collect::<Vec<_>>();
+        assert_ne!(results1, results2);
+    }
+}
@@ -1,7 +1,15 @@
+mod batch;
+mod cache;
 mod call;
+mod chaos;
 mod echo;
+mod process;
 mod thunk;
 
+pub use batch::Batched;
+pub use cache::{Cache, CacheStore, CachingQueen, CachingWorker, LruStore, WorkerCache};
 pub use call::{Caller, OnceCaller, RefCaller, RetryCaller};
+pub use chaos::{Chaos, ChaosError, ChaosQueen, ChaosWorker};
 pub use echo::EchoWorker;
+pub use process::{Framing, ProcessQueen, ProcessWorker};
 pub use thunk::{FunkWorker, PunkWorker, Thunk, ThunkWorker};
@@ -0,0 +1,463 @@
+//! A `Worker` that delegates each task to a long-lived child process.
+use crate::bee::{ApplyError, Context, Queen, Worker, WorkerResult};
+use std::ffi::OsString;
+use std::fmt::Debug;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Inputs larger than this are written to the child from a separate thread, so that the child can
+/// write its output while it is still reading the input without filling up the pipe buffers.
+/// Smaller inputs always fit in the pipe buffer and are written directly.
+const DIRECT_WRITE_MAX: usize = 4096;
+/// The default time to wait for a child process to exit after its `stdin` is closed.
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often to check whether a child process has exited while waiting for it to shut down.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How messages are delimited when they are exchanged with a child process.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Framing {
+    /// Each message is terminated by a newline (`\n`). The newline is appended to each input and
+    /// stripped from each output. Inputs must not contain a newline.
+    #[default]
+    Line,
+    /// Each message is preceded by its length in bytes, encoded as a big-endian `u32`.
+    LengthPrefixed,
+}
+
+/// The command used to spawn a child process, and the framing of the messages exchanged with it.
+#[derive(Clone, Debug)]
+struct ProcessSpec {
+    program: OsString,
+    args: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    current_dir: Option<PathBuf>,
+    framing: Framing,
+    shutdown_timeout: Duration,
+}
+
+impl ProcessSpec {
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .envs(self.envs.iter().map(|(key, val)| (key, val)))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit());
+        if let Some(ref dir) = self.current_dir {
+            command.current_dir(dir);
+        }
+        command
+    }
+
+    fn spawn(&self) -> io::Result<ChildProcess> {
+        let mut child = self.command().spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Ok(ChildProcess {
+            child,
+            stdin: Some(stdin),
+            stdout,
+        })
+    }
+}
+
+/// A running child process and its piped `stdin`/`stdout`.
+#[derive(Debug)]
+struct ChildProcess {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl ChildProcess {
+    /// Writes `input` to the child's `stdin` and reads back one message from its `stdout`.
+    ///
+    /// A large input is written from a separate thread while the output is being read, since a
+    /// child that streams its output (e.g., `cat`) would otherwise block writing to its full
+    /// `stdout` pipe while this thread blocks writing to the child's full `stdin` pipe.
+    fn exchange(&mut self, input: &[u8], framing: Framing) -> io::Result<Vec<u8>> {
+        if framing == Framing::LengthPrefixed && u32::try_from(input.len()).is_err() {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        let stdin = self.stdin.as_mut().unwrap();
+        if input.len() <= DIRECT_WRITE_MAX {
+            write_message(stdin, input, framing)?;
+            return read_message(&mut self.stdout, framing);
+        }
+        let stdout = &mut self.stdout;
+        thread::scope(|scope| {
+            let writer = scope.spawn(|| write_message(stdin, input, framing));
+            let output = read_message(stdout, framing);
+            let written = writer
+                .join()
+                .unwrap_or_else(|_| Err(io::Error::other("writer thread panicked")));
+            // a failed read is the more informative error, e.g., if the child exited early
+            let output = output?;
+            written?;
+            Ok(output)
+        })
+    }
+
+    /// Closes the child's `stdin` and waits up to `timeout` for it to exit, then kills it if it
+    /// is still running.
+    fn shutdown(mut self, timeout: Duration) {
+        drop(self.stdin.take());
+        let deadline = Instant::now() + timeout;
+        while let Ok(None) = self.child.try_wait() {
+            if Instant::now() >= deadline {
+                let _ = self.child.kill();
+                break;
+            }
+            thread::sleep(SHUTDOWN_POLL_INTERVAL);
+        }
+        let _ = self.child.wait();
+    }
+
+    /// Kills the child and waits for it to exit.
+    fn kill(mut self) {
+        let _ = self.child.kill();
+        self.shutdown(Duration::ZERO);
+    }
+}
+
+/// Writes one message containing `input` to a child's `stdin`.
+fn write_message(stdin: &mut ChildStdin, input: &[u8], framing: Framing) -> io::Result<()> {
+    match framing {
+        Framing::Line => {
+            stdin.write_all(input)?;
+            stdin.write_all(b"\n")?;
+        }
+        Framing::LengthPrefixed => {
+            let len = u32::try_from(input.len())
+                .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+            stdin.write_all(&len.to_be_bytes())?;
+            stdin.write_all(input)?;
+        }
+    }
+    stdin.flush()
+}
+
+/// Reads one message from a child's `stdout`.
+fn read_message(stdout: &mut BufReader<ChildStdout>, framing: Framing) -> io::Result<Vec<u8>> {
+    match framing {
+        Framing::Line => {
+            let mut output = Vec::new();
+            if stdout.read_until(b'\n', &mut output)? == 0 || output.pop() != Some(b'\n') {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            Ok(output)
+        }
+        Framing::LengthPrefixed => {
+            let mut len = [0u8; 4];
+            stdout.read_exact(&mut len)?;
+            let mut output = vec![0u8; u32::from_be_bytes(len) as usize];
+            stdout.read_exact(&mut output)?;
+            Ok(output)
+        }
+    }
+}
+
+/// A `Worker` that sends each input to a long-lived child process over its `stdin` and returns
+/// the message the child writes back to its `stdout`. `ProcessWorker`s are created by a
+/// [`ProcessQueen`], which spawns one child process per worker thread.
+///
+/// If the child process crashes (or any other I/O error occurs while communicating with it), the
+/// child is killed and a new one is spawned, and the task fails with `ApplyError::Retryable`. If a
+/// child process cannot be spawned, the task fails with `ApplyError::Fatal`.
+///
+/// When the `ProcessWorker` is dropped, the child's `stdin` is closed and the worker waits for the
+/// child to exit. If the child does not exit within the shutdown timeout (see
+/// [`ProcessQueen::shutdown_timeout`]), it is killed.
+#[derive(Debug)]
+pub struct ProcessWorker {
+    spec: Arc<ProcessSpec>,
+    num_spawned: Arc<AtomicUsize>,
+    child: Option<ChildProcess>,
+}
+
+impl ProcessWorker {
+    fn new(spec: Arc<ProcessSpec>, num_spawned: Arc<AtomicUsize>) -> Self {
+        let mut worker = Self {
+            spec,
+            num_spawned,
+            child: None,
+        };
+        // if the child cannot be spawned now, spawning is attempted again by `apply`
+        let _ = worker.spawn();
+        worker
+    }
+
+    /// Spawns a new child process if there is not already one running.
+    fn spawn(&mut self) -> io::Result<&mut ChildProcess> {
+        if self.child.is_none() {
+            self.child = Some(self.spec.spawn()?);
+            self.num_spawned.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(self.child.as_mut().unwrap())
+    }
+
+    /// Kills the current child process (if any) and spawns a new one.
+    fn respawn(&mut self) {
+        if let Some(child) = self.child.take() {
+            child.kill();
+        }
+        let _ = self.spawn();
+    }
+}
+
+impl Worker for ProcessWorker {
+    type Input = Vec<u8>;
+    type Output = Vec<u8>;
+    type Error = io::Error;
+
+    fn apply(&mut self, input: Self::Input, _: &Context) -> WorkerResult<Self> {
+        if self.spec.framing == Framing::Line && input.contains(&b'\n') {
+            return Err(ApplyError::Fatal {
+                input: Some(input),
+                error: io::Error::new(io::ErrorKind::InvalidInput, "input contains a newline"),
+            });
+        }
+        let framing = self.spec.framing;
+        let child = match self.spawn() {
+            Ok(child) => child,
+            Err(error) => {
+                return Err(ApplyError::Fatal {
+                    input: Some(input),
+                    error,
+                })
+            }
+        };
+        match child.exchange(&input, framing) {
+            Ok(output) => Ok(output),
+            Err(error) if error.kind() == io::ErrorKind::InvalidInput => Err(ApplyError::Fatal {
+                input: Some(input),
+                error,
+            }),
+            Err(error) => {
+                self.respawn();
+                Err(ApplyError::Retryable { input, error })
+            }
+        }
+    }
+}
+
+impl Drop for ProcessWorker {
+    fn drop(&mut self) {
+        if let Some(child) = self.child.take() {
+            child.shutdown(self.spec.shutdown_timeout);
+        }
+    }
+}
+
+/// A `Queen` that creates [`ProcessWorker`]s, each of which communicates with its own child
+/// process spawned from the same command.
+///
+/// # Examples
+///
+/// ```
+/// use beekeeper::bee::stock::{Framing, ProcessQueen};
+/// use beekeeper::hive::prelude::*;
+///
+/// # fn main() {
+/// let hive = Builder::new()
+///     .num_threads(2)
+///     .build(ProcessQueen::new("cat").framing(Framing::Line))
+///     .unwrap();
+/// let outputs: Vec<_> = hive
+///     .swarm([b"hello".to_vec(), b"world".to_vec()])
+///     .into_outputs()
+///     .collect();
+/// assert_eq!(outputs, vec![b"hello".to_vec(), b"world".to_vec()]);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ProcessQueen {
+    spec: Arc<ProcessSpec>,
+    num_spawned: Arc<AtomicUsize>,
+}
+
+impl ProcessQueen {
+    /// Creates a new `ProcessQueen` that spawns child processes by executing `program`.
+    pub fn new<S: Into<OsString>>(program: S) -> Self {
+        Self {
+            spec: Arc::new(ProcessSpec {
+                program: program.into(),
+                args: Vec::new(),
+                envs: Vec::new(),
+                current_dir: None,
+                framing: Framing::default(),
+                shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            }),
+            num_spawned: Default::default(),
+        }
+    }
+
+    /// Adds an argument to pass to the program.
+    pub fn arg<S: Into<OsString>>(mut self, arg: S) -> Self {
+        Arc::make_mut(&mut self.spec).args.push(arg.into());
+        self
+    }
+
+    /// Adds multiple arguments to pass to the program.
+    pub fn args<I: IntoIterator<Item = S>, S: Into<OsString>>(mut self, args: I) -> Self {
+        Arc::make_mut(&mut self.spec)
+            .args
+            .extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets an environment variable for the child processes.
+    pub fn env<K: Into<OsString>, V: Into<OsString>>(mut self, key: K, val: V) -> Self {
+        Arc::make_mut(&mut self.spec)
+            .envs
+            .push((key.into(), val.into()));
+        self
+    }
+
+    /// Sets the working directory for the child processes.
+    pub fn current_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        Arc::make_mut(&mut self.spec).current_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets how messages are delimited when exchanged with the child processes. Defaults to
+    /// `Framing::Line`.
+    pub fn framing(mut self, framing: Framing) -> Self {
+        Arc::make_mut(&mut self.spec).framing = framing;
+        self
+    }
+
+    /// Sets how long a `Worker` waits for its child process to exit after closing its `stdin`
+    /// when the `Worker` is dropped. A child that is still running after this time is killed.
+    /// Defaults to 5 seconds.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        Arc::make_mut(&mut self.spec).shutdown_timeout = timeout;
+        self
+    }
+
+    /// Returns the total number of child processes that have been spawned by the `Worker`s
+    /// created by this `Queen`, including those spawned to replace crashed processes.
+    pub fn num_spawned(&self) -> usize {
+        self.num_spawned.load(Ordering::Relaxed)
+    }
+}
+
+impl Queen for ProcessQueen {
+    type Kind = ProcessWorker;
+
+    fn create(&mut self) -> Self::Kind {
+        ProcessWorker::new(Arc::clone(&self.spec), Arc::clone(&self.num_spawned))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Framing, ProcessQueen};
+    use crate::bee::{ApplyError, Context, Queen, Worker};
+    use crate::hive::{Builder, OutcomeIteratorExt};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_line() {
+        let mut queen = ProcessQueen::new("cat");
+        let mut worker = queen.create();
+        let ctx = Context::empty();
+        assert_eq!(worker.apply(b"abc".to_vec(), &ctx).unwrap(), b"abc");
+        assert_eq!(worker.apply(b"".to_vec(), &ctx).unwrap(), b"");
+        assert!(matches!(
+            worker.apply(b"a\nb".to_vec(), &ctx),
+            Err(ApplyError::Fatal { input: Some(_), .. })
+        ));
+        assert_eq!(queen.num_spawned(), 1);
+    }
+
+    #[test]
+    fn test_length_prefixed() {
+        let mut queen = ProcessQueen::new("cat").framing(Framing::LengthPrefixed);
+        let mut worker = queen.create();
+        let ctx = Context::empty();
+        assert_eq!(worker.apply(b"a\nb".to_vec(), &ctx).unwrap(), b"a\nb");
+        assert_eq!(
+            worker.apply(vec![0; 100_000], &ctx).unwrap(),
+            vec![0; 100_000]
+        );
+    }
+
+    #[test]
+    fn test_large_messages() {
+        // `cat` starts writing its output before it has read all of the input, so this would
+        // deadlock if the input were written before reading the output
+        let mut queen = ProcessQueen::new("cat").framing(Framing::LengthPrefixed);
+        let mut worker = queen.create();
+        let ctx = Context::empty();
+        let input: Vec<u8> = (0..8_000_000u32).map(|i| i as u8).collect();
+        assert_eq!(worker.apply(input.clone(), &ctx).unwrap(), input);
+        let line = vec![b'x'; 4_000_000];
+        let mut queen = ProcessQueen::new("cat");
+        let mut worker = queen.create();
+        assert_eq!(worker.apply(line.clone(), &ctx).unwrap(), line);
+    }
+
+    #[test]
+    fn test_shutdown_timeout() {
+        // `sleep` never reads its `stdin`, so it does not exit when `stdin` is closed
+        let mut queen = ProcessQueen::new("sleep")
+            .arg("30")
+            .shutdown_timeout(Duration::from_millis(200));
+        let worker = queen.create();
+        let start = Instant::now();
+        drop(worker);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_respawn() {
+        // `head -n 1` echoes the first line and then exits
+        let mut queen = ProcessQueen::new("head").args(["-n", "1"]);
+        let mut worker = queen.create();
+        let ctx = Context::empty();
+        assert_eq!(worker.apply(b"a".to_vec(), &ctx).unwrap(), b"a");
+        assert!(matches!(
+            worker.apply(b"b".to_vec(), &ctx),
+            Err(ApplyError::Retryable { input, .. }) if input == b"b"
+        ));
+        assert_eq!(worker.apply(b"c".to_vec(), &ctx).unwrap(), b"c");
+        assert_eq!(queen.num_spawned(), 2);
+    }
+
+    #[test]
+    fn test_spawn_fail() {
+        let mut queen = ProcessQueen::new("/this/program/does/not/exist");
+        let mut worker = queen.create();
+        assert!(matches!(
+            worker.apply(b"a".to_vec(), &Context::empty()),
+            Err(ApplyError::Fatal { input: Some(_), .. })
+        ));
+        assert_eq!(queen.num_spawned(), 0);
+    }
+
+    #[test]
+    fn test_hive() {
+        let hive = Builder::new()
+            .num_threads(4)
+            .build(ProcessQueen::new("cat"))
+            .unwrap();
+        let outputs: Vec<_> = hive
+            .swarm((0..10u8).map(|i| vec![b'a' + i]))
+            .into_outputs()
+            .collect();
+        assert_eq!(
+            outputs,
+            (0..10u8).map(|i| vec![b'a' + i]).collect::<Vec<_>>()
+        );
+        let husk = hive.try_into_husk().unwrap();
+        assert_eq!(husk.queen().num_spawned(), 4);
+    }
+}
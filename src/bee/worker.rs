@@ -32,6 +32,26 @@ pub trait Worker: Debug + Sized + 'static {
     /// catch the panic and turn it into an `ApplyError::Panic` error.
     fn apply(&mut self, _: Self::Input, _: &Context) -> WorkerResult<Self>;
 
+    /// Applies this `Worker`'s function to a batch of inputs, where `ctxs[i]` is the `Context`
+    /// of `inputs[i]`, and returns one result per input, in the same order as the inputs.
+    ///
+    /// This method is only called by a `Hive` that is configured to execute tasks in batches
+    /// (see `Builder::batch`). The default implementation calls `apply` on each input in turn. It
+    /// should be overridden by workers that can process many inputs more efficiently than one at
+    /// a time - a simpler alternative is to implement [`BatchWorker`] and use the
+    /// [`Batched`](crate::bee::stock::Batched) adapter.
+    fn apply_batch(
+        &mut self,
+        inputs: Vec<Self::Input>,
+        ctxs: &[Context],
+    ) -> Vec<WorkerResult<Self>> {
+        inputs
+            .into_iter()
+            .zip(ctxs)
+            .map(|(input, ctx)| self.apply(input, ctx))
+            .collect()
+    }
+
     /// Applies this `Worker`'s function sequentially to an iterator of inputs and returns a
     /// iterator over the outputs.
     fn map(
@@ -91,6 +111,36 @@ where
     }
 }
 
+/// Alias for the result of applying a `BatchWorker` to a single input.
+pub type BatchWorkerResult<W> = Result<
+    <W as BatchWorker>::Output,
+    ApplyError<<W as BatchWorker>::Input, <W as BatchWorker>::Error>,
+>;
+
+/// A trait for stateful, fallible, idempotent functions that are applied to many inputs at once,
+/// such as batched database inserts or vectorized computations.
+///
+/// A `BatchWorker` is used in a `Hive` by wrapping it in a [`Batched`](crate::bee::stock::Batched)
+/// worker. The `Hive` must be configured to execute tasks in batches (see `Builder::batch`),
+/// otherwise each batch will only contain a single input.
+pub trait BatchWorker: Debug + Sized + 'static {
+    /// The type of the input to this function.
+    type Input: Send;
+    /// The type of the output from this function.
+    type Output: Send;
+    /// The type of error produced by this function.
+    type Error: Send + Debug;
+
+    /// Applies this worker's function to a batch of inputs and returns one result per input, in
+    /// the same order as the inputs. As with `Worker::apply`, an input that fails with a
+    /// retryable error must be returned in its `ApplyError::Retryable` so that it can be retried
+    /// individually.
+    ///
+    /// If fewer results than inputs are returned, the `Hive` produces an `Outcome::Missing` for
+    /// each input without a result.
+    fn apply_batch(&mut self, inputs: Vec<Self::Input>) -> Vec<BatchWorkerResult<Self>>;
+}
+
 #[cfg(test)]
 mod tests {
     use super::{ApplyRefError, RefWorker, RefWorkerResult, Worker, WorkerResult};
@@ -123,6 +173,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_apply_batch() {
+        let mut worker = MyWorker;
+        let ctxs: Vec<_> = (0..3).map(|_| Context::empty()).collect();
+        let outputs: Vec<_> = worker
+            .apply_batch(vec![1, 2, 3], &ctxs)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(outputs, vec![2, 3, 4]);
+    }
+
     #[derive(Debug)]
     struct MyRefWorker;
 
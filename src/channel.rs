@@ -4,6 +4,8 @@
 pub use prelude::channel;
 pub(crate) use prelude::*;
 
+use std::time::Duration;
+
 /// Possible results of calling `ReceiverExt::try_recv_msg()` on a `Receiver`.
 pub enum Message<T> {
     /// A message was successfully received from the channel.
@@ -25,6 +27,10 @@ pub trait ReceiverExt<T> {
     /// Attempts to receive a message from the channel. Returns `Message::Received` if a message
     /// was successfully received, otherwise one of `Message`'s error variants.
     fn try_recv_msg(&self) -> Message<T>;
+
+    /// Attempts to receive a message from the channel, blocking for up to `timeout`. Returns
+    /// `Message::ChannelEmpty` if no message was received before the timeout elapsed.
+    fn recv_timeout_msg(&self, timeout: Duration) -> Message<T>;
 }
 
 /// Trait implemented for channel `Receiver` types that do not already provide an `iter()` method.
@@ -39,7 +45,8 @@ pub mod prelude {
     pub use std::sync::mpsc::{channel, Receiver, SendError, Sender};
 
     use super::{Message, ReceiverExt, SenderExt};
-    use std::sync::mpsc::TryRecvError;
+    use std::sync::mpsc::{RecvTimeoutError, TryRecvError};
+    use std::time::Duration;
 
     impl<T> SenderExt<T> for Sender<T> {
         fn try_send_msg(&self, t: T) -> Option<T> {
@@ -58,6 +65,14 @@ pub mod prelude {
                 Err(TryRecvError::Disconnected) => Message::ChannelDisconnected,
             }
         }
+
+        fn recv_timeout_msg(&self, timeout: Duration) -> super::Message<T> {
+            match self.recv_timeout(timeout) {
+                Ok(t) => Message::Received(t),
+                Err(RecvTimeoutError::Timeout) => Message::ChannelEmpty,
+                Err(RecvTimeoutError::Disconnected) => Message::ChannelDisconnected,
+            }
+        }
     }
 }
 
@@ -66,7 +81,8 @@ pub mod prelude {
     pub use crossbeam_channel::{unbounded as channel, Receiver, SendError, Sender};
 
     use super::{Message, ReceiverExt, SenderExt};
-    use crossbeam_channel::TryRecvError;
+    use crossbeam_channel::{RecvTimeoutError, TryRecvError};
+    use std::time::Duration;
 
     impl<T> SenderExt<T> for Sender<T> {
         fn try_send_msg(&self, t: T) -> Option<T> {
@@ -85,6 +101,14 @@ pub mod prelude {
                 Err(TryRecvError::Disconnected) => Message::ChannelDisconnected,
             }
         }
+
+        fn recv_timeout_msg(&self, timeout: Duration) -> super::Message<T> {
+            match self.recv_timeout(timeout) {
+                Ok(t) => Message::Received(t),
+                Err(RecvTimeoutError::Timeout) => Message::ChannelEmpty,
+                Err(RecvTimeoutError::Disconnected) => Message::ChannelDisconnected,
+            }
+        }
     }
 }
 
@@ -93,7 +117,8 @@ pub mod prelude {
     pub use flume::{unbounded as channel, Receiver, SendError, Sender};
 
     use super::{Message, ReceiverExt, SenderExt};
-    use flume::TryRecvError;
+    use flume::{RecvTimeoutError, TryRecvError};
+    use std::time::Duration;
 
     impl<T> SenderExt<T> for Sender<T> {
         fn try_send_msg(&self, t: T) -> Option<T> {
@@ -112,6 +137,14 @@ pub mod prelude {
                 Err(TryRecvError::Disconnected) => Message::ChannelDisconnected,
             }
         }
+
+        fn recv_timeout_msg(&self, timeout: Duration) -> super::Message<T> {
+            match self.recv_timeout(timeout) {
+                Ok(t) => Message::Received(t),
+                Err(RecvTimeoutError::Timeout) => Message::ChannelEmpty,
+                Err(RecvTimeoutError::Disconnected) => Message::ChannelDisconnected,
+            }
+        }
     }
 }
 
@@ -120,7 +153,8 @@ pub mod prelude {
     pub use loole::{unbounded as channel, Receiver, SendError, Sender};
 
     use super::{Message, ReceiverExt, SenderExt};
-    use loole::TryRecvError;
+    use loole::{RecvTimeoutError, TryRecvError};
+    use std::time::Duration;
 
     impl<T> SenderExt<T> for Sender<T> {
         fn try_send_msg(&self, t: T) -> Option<T> {
@@ -139,5 +173,13 @@ pub mod prelude {
                 Err(TryRecvError::Disconnected) => Message::ChannelDisconnected,
             }
         }
+
+        fn recv_timeout_msg(&self, timeout: Duration) -> super::Message<T> {
+            match self.recv_timeout(timeout) {
+                Ok(t) => Message::Received(t),
+                Err(RecvTimeoutError::Timeout) => Message::ChannelEmpty,
+                Err(RecvTimeoutError::Disconnected) => Message::ChannelDisconnected,
+            }
+        }
     }
 }
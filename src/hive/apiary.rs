@@ -0,0 +1,780 @@
+//! A pool of threads that is shared by multiple `Hive`s.
+use super::{Gate, Hive, PanicPolicy, Shared, SpawnError};
+use crate::bee::{Queen, Worker};
+use crossbeam_utils::Backoff;
+use parking_lot::Mutex;
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The longest time an idle thread waits to be notified of new tasks before checking the member
+/// `Hive`s again. Tasks may become available without a notification, e.g., when a retry delay
+/// elapses; a thread waits for less time if a member's clock indicates that one of its deferred
+/// tasks may become available sooner.
+const IDLE_TIMEOUT: Duration = Duration::from_millis(20);
+/// The amount by which a member's pass is advanced each time one of its tasks is executed is
+/// `STRIDE / weight`.
+const STRIDE: u64 = 1 << 20;
+
+/// Error returned by [`Apiary::register`] when a `Hive` cannot be registered.
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ApiaryError {
+    /// The `Hive` is deterministic, so its tasks are always executed on the submitting thread.
+    #[error("The hive is deterministic")]
+    Deterministic,
+    /// The `Hive` is already registered with an `Apiary` (this one or another one).
+    #[error("The hive is already registered with an apiary")]
+    AlreadyRegistered,
+    /// The `Hive` has been poisoned, so it cannot process tasks.
+    #[error("The hive has been poisoned")]
+    Poisoned,
+}
+
+/// A pool of threads that is shared by any number of [`Hive`]s, which may have different `Worker`
+/// types.
+///
+/// A `Hive` is registered with an `Apiary` using [`Apiary::register`], after which the threads
+/// of the `Apiary` execute the tasks submitted to the `Hive` (in addition to any worker threads
+/// the `Hive` has of its own, so a `Hive` that is registered with an `Apiary` is usually built
+/// without worker threads, e.g., using `Builder::new()`). Each thread creates a `Worker` for each
+/// member `Hive` the first time it executes one of that `Hive`'s tasks.
+///
+/// Each member `Hive` keeps its own outcome store, suspended state, task groups, rate limits,
+/// panic policy, and statistics, and is used in exactly the same way as a `Hive` with its own
+/// worker threads. Batching (`Builder::batch`) and thread affinity have no effect on the tasks
+/// executed by an `Apiary`.
+///
+/// A panic in a member's task is handled according to the member's `PanicPolicy`, except that
+/// the `Apiary` thread on which it occurs is never terminated. Under `PanicPolicy::Stop`, that
+/// thread no longer executes the member's tasks (but continues to execute the tasks of the other
+/// members), just as a worker thread of the member would have been stopped.
+///
+/// Threads are shared between the members using weighted fair scheduling: when more than one
+/// member has tasks available, each member is given a share of the threads' time that is
+/// proportional to its weight. In addition, the maximum number of threads that may execute a
+/// member's tasks at the same time can be limited using [`Apiary::set_max_threads`].
+///
+/// A member is unregistered automatically when it is dropped, poisoned, or converted into a
+/// `Husk`. When the last clone of an `Apiary` is dropped, its threads terminate after completing
+/// their current tasks; any tasks that are still queued in member `Hive`s are not processed
+/// unless they have worker threads of their own.
+///
+/// # Examples
+///
+/// ```
+/// use beekeeper::bee::stock::{Caller, EchoWorker};
+/// use beekeeper::hive::{Apiary, Builder};
+///
+/// # fn main() {
+/// let apiary = Apiary::new(4).unwrap();
+/// let doubler = Builder::new()
+///     .build_with(Caller::of(|i: usize| i * 2))
+///     .unwrap();
+/// let echo = Builder::new()
+///     .build_with_default::<EchoWorker<String>>()
+///     .unwrap();
+/// // `doubler` gets twice as many turns as `echo` when both have tasks available
+/// apiary.register(&doubler, 2).unwrap();
+/// apiary.register(&echo, 1).unwrap();
+/// let outputs: Vec<_> = doubler.map(0..10).map(|outcome| outcome.unwrap()).collect();
+/// assert_eq!(outputs, vec![0, 2, 4, 6, 8, 10, 12, 14, 16, 18]);
+/// assert_eq!(echo.apply("hello".into()).unwrap(), "hello");
+/// assert_eq!(apiary.num_members(), 2);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Apiary(Arc<ApiaryInner>);
+
+/// Owned by the `Apiary` handles; the threads are stopped when it is dropped.
+struct ApiaryInner {
+    nest: Arc<Nest>,
+}
+
+/// Data shared by the threads of an `Apiary`.
+struct Nest {
+    num_threads: usize,
+    members: Mutex<Members>,
+    // gate used by idle threads to wait for tasks to be submitted to a member hive
+    gate: Gate,
+    // incremented each time tasks are submitted to a member hive
+    epoch: AtomicUsize,
+    // set when the last `Apiary` handle is dropped
+    closed: AtomicBool,
+}
+
+#[derive(Default)]
+struct Members {
+    list: Vec<Member>,
+    next_id: usize,
+    // the pass of the most recently scheduled member; a member that has been idle is not allowed
+    // to fall behind this value, so it can't monopolize the threads when it becomes busy again
+    vtime: u64,
+}
+
+struct Member {
+    id: usize,
+    // the address of the member hive's shared data, which identifies the hive
+    key: usize,
+    weight: u32,
+    max_threads: Option<usize>,
+    // the virtual time at which the member is next entitled to have a task executed
+    pass: u64,
+    // the number of threads currently executing one of the member's tasks
+    active: usize,
+    hive: Arc<dyn Tenant>,
+}
+
+/// Type-erased interface to the shared data of a member `Hive`.
+trait Tenant: Send + Sync {
+    /// Returns `true` if the hive still exists and is able to process tasks.
+    fn is_alive(&self) -> bool;
+
+    /// Returns an empty slot for a `Worker` of the hive's type.
+    fn new_slot(&self) -> Box<dyn Any>;
+
+    /// Executes the hive's next available task on the current thread using the `Worker` in
+    /// `slot`. Returns `None` if there is no task available, otherwise `Some(stop)`, where `stop`
+    /// is `true` if the `Worker` panicked and the hive's panic policy is `PanicPolicy::Stop`, in
+    /// which case the current thread should no longer execute the hive's tasks.
+    fn execute_next(&self, slot: &mut Box<dyn Any>, thread_index: usize) -> Option<bool>;
+
+    /// Returns the (real) time an idle thread should wait before checking the hive for tasks
+    /// again: at most `timeout`, but no longer than until one of the hive's deferred tasks (e.g.,
+    /// a task waiting to be retried) may become available.
+    fn idle_timeout(&self, timeout: Duration) -> Duration;
+
+    /// Removes the hive's link to the `Apiary`.
+    fn unlink(&self);
+}
+
+impl<W: Worker, Q: Queen<Kind = W>> Tenant for Weak<Shared<W, Q>> {
+    fn is_alive(&self) -> bool {
+        self.upgrade().is_some_and(|shared| !shared.is_poisoned())
+    }
+
+    fn new_slot(&self) -> Box<dyn Any> {
+        Box::new(None::<W>)
+    }
+
+    fn execute_next(&self, slot: &mut Box<dyn Any>, thread_index: usize) -> Option<bool> {
+        let shared = self.upgrade()?;
+        let worker = slot
+            .downcast_mut::<Option<W>>()
+            .expect("worker slot has the wrong type");
+        let panicked = Hive::execute_next(&shared, worker, thread_index)?;
+        Some(panicked && shared.panic_policy() == PanicPolicy::Stop)
+    }
+
+    fn idle_timeout(&self, timeout: Duration) -> Duration {
+        self.upgrade()
+            .map_or(timeout, |shared| shared.deferred_timeout(timeout))
+    }
+
+    fn unlink(&self) {
+        if let Some(shared) = self.upgrade() {
+            shared.set_apiary(None);
+        }
+    }
+}
+
+/// A link from a member `Hive` to its `Apiary`, which is used to notify the `Apiary`'s threads
+/// when tasks are submitted to the `Hive`.
+pub struct ApiaryLink(Weak<Nest>);
+
+impl ApiaryLink {
+    /// Wakes up the idle threads of the `Apiary` (if it still exists).
+    pub fn wake(&self) {
+        if let Some(nest) = self.0.upgrade() {
+            nest.wake();
+        }
+    }
+
+    /// Unregisters the hive with the given shared data from the `Apiary` (if it still exists),
+    /// then waits until none of the `Apiary`'s threads holds a reference to the shared data.
+    pub fn unregister<W: Worker, Q: Queen<Kind = W>>(&self, shared: &Arc<Shared<W, Q>>) {
+        let Some(nest) = self.0.upgrade() else {
+            return;
+        };
+        let removed = nest.members.lock().remove(key(shared));
+        if let Some(member) = removed {
+            // a thread only upgrades its reference to the shared data while it holds a clone of
+            // the member's tenant
+            let mut backoff = None::<Backoff>;
+            while Arc::strong_count(&member.hive) > 1 {
+                backoff.get_or_insert_with(Backoff::new).snooze();
+            }
+        }
+    }
+}
+
+impl Apiary {
+    /// Creates a new `Apiary` and spawns `num_threads` threads.
+    pub fn new(num_threads: usize) -> Result<Self, SpawnError> {
+        let nest = Arc::new(Nest {
+            num_threads,
+            members: Default::default(),
+            gate: Default::default(),
+            epoch: Default::default(),
+            closed: Default::default(),
+        });
+        // the `Apiary` is created first so that the threads are stopped if spawning fails
+        let apiary = Self(Arc::new(ApiaryInner {
+            nest: Arc::clone(&nest),
+        }));
+        for thread_index in 0..num_threads {
+            let nest = Arc::clone(&nest);
+            thread::Builder::new()
+                .name(format!("apiary-{thread_index}"))
+                .spawn(move || nest.run(thread_index))
+                .map_err(SpawnError::Spawn)?;
+        }
+        Ok(apiary)
+    }
+
+    fn nest(&self) -> &Nest {
+        &self.0.nest
+    }
+
+    /// Returns the number of threads in this `Apiary`.
+    pub fn num_threads(&self) -> usize {
+        self.nest().num_threads
+    }
+
+    /// Returns the number of `Hive`s that are registered with this `Apiary`.
+    pub fn num_members(&self) -> usize {
+        let mut members = self.nest().members.lock();
+        members.prune();
+        members.list.len()
+    }
+
+    /// Registers `hive` with this `Apiary` so that its tasks are executed by this `Apiary`'s
+    /// threads. When more than one member has tasks available, the threads are shared between
+    /// them in proportion to their `weight`s.
+    ///
+    /// Returns an error if the `hive` is deterministic, has been poisoned, or is already
+    /// registered with an `Apiary`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weight` is `0`.
+    pub fn register<W, Q>(&self, hive: &Hive<W, Q>, weight: u32) -> Result<(), ApiaryError>
+    where
+        W: Worker,
+        Q: Queen<Kind = W>,
+    {
+        assert!(weight > 0, "weight must be greater than 0");
+        let shared = hive.shared();
+        if shared.is_deterministic() {
+            return Err(ApiaryError::Deterministic);
+        }
+        if shared.is_poisoned() {
+            return Err(ApiaryError::Poisoned);
+        }
+        if !shared.set_apiary(Some(ApiaryLink(Arc::downgrade(&self.0.nest)))) {
+            return Err(ApiaryError::AlreadyRegistered);
+        }
+        let mut members = self.nest().members.lock();
+        let id = members.next_id;
+        members.next_id += 1;
+        let pass = members.vtime;
+        members.list.push(Member {
+            id,
+            key: key(shared),
+            weight,
+            max_threads: None,
+            pass,
+            active: 0,
+            hive: Arc::new(Arc::downgrade(shared)),
+        });
+        drop(members);
+        // the hive may already have queued tasks
+        self.nest().wake();
+        Ok(())
+    }
+
+    /// Unregisters `hive` from this `Apiary`. Tasks that are currently being executed by this
+    /// `Apiary`'s threads are completed, but no more of the `hive`'s tasks are started. Returns
+    /// `false` if the `hive` is not registered with this `Apiary`.
+    pub fn unregister<W, Q>(&self, hive: &Hive<W, Q>) -> bool
+    where
+        W: Worker,
+        Q: Queen<Kind = W>,
+    {
+        let removed = self.nest().members.lock().remove(key(hive.shared()));
+        match removed {
+            Some(member) => {
+                member.hive.unlink();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sets the `weight` of `hive`, which must be registered with this `Apiary`. Returns `false`
+    /// if the `hive` is not registered with this `Apiary`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weight` is `0`.
+    pub fn set_weight<W, Q>(&self, hive: &Hive<W, Q>, weight: u32) -> bool
+    where
+        W: Worker,
+        Q: Queen<Kind = W>,
+    {
+        assert!(weight > 0, "weight must be greater than 0");
+        self.nest()
+            .members
+            .lock()
+            .update(key(hive.shared()), |member| member.weight = weight)
+    }
+
+    /// Sets the maximum number of this `Apiary`'s threads that may execute tasks of `hive` at the
+    /// same time. If `max_threads` is `None`, the number of threads is unlimited. Returns `false`
+    /// if the `hive` is not registered with this `Apiary`.
+    pub fn set_max_threads<W, Q>(&self, hive: &Hive<W, Q>, max_threads: Option<usize>) -> bool
+    where
+        W: Worker,
+        Q: Queen<Kind = W>,
+    {
+        let updated = self
+            .nest()
+            .members
+            .lock()
+            .update(key(hive.shared()), |member| {
+                member.max_threads = max_threads
+            });
+        // if the limit was raised, idle threads may now be able to execute the hive's tasks
+        self.nest().wake();
+        updated
+    }
+}
+
+impl fmt::Debug for Apiary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Apiary")
+            .field("num_threads", &self.num_threads())
+            .field("num_members", &self.num_members())
+            .finish()
+    }
+}
+
+impl Drop for ApiaryInner {
+    fn drop(&mut self) {
+        self.nest.closed.store(true, Ordering::Release);
+        let mut members = self.nest.members.lock();
+        members
+            .list
+            .drain(..)
+            .for_each(|member| member.hive.unlink());
+        drop(members);
+        self.nest.gate.notify_all();
+    }
+}
+
+/// Returns the key that identifies the hive with the given shared data.
+fn key<W: Worker, Q: Queen<Kind = W>>(shared: &Arc<Shared<W, Q>>) -> usize {
+    Arc::as_ptr(shared) as *const () as usize
+}
+
+impl Nest {
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    fn epoch(&self) -> usize {
+        self.epoch.load(Ordering::Acquire)
+    }
+
+    fn wake(&self) {
+        self.epoch.fetch_add(1, Ordering::AcqRel);
+        self.gate.notify_all();
+    }
+
+    /// The main loop of an `Apiary` thread. Each thread has its own `Worker` for each member.
+    fn run(&self, thread_index: usize) {
+        crate::panic::set_thread_index(thread_index);
+        let mut workers: HashMap<usize, Box<dyn Any>> = HashMap::new();
+        // members whose tasks this thread no longer executes due to `PanicPolicy::Stop`
+        let mut stopped: HashSet<usize> = HashSet::new();
+        while !self.is_closed() {
+            let epoch = self.epoch();
+            if !self.execute_next(&mut workers, &mut stopped, thread_index) {
+                // drop the workers of hives that are no longer members
+                let members = self.members.lock();
+                let ids = members.ids();
+                let timeout = members.idle_timeout(IDLE_TIMEOUT);
+                drop(members);
+                workers.retain(|id, _| ids.contains(id));
+                stopped.retain(|id| ids.contains(id));
+                self.gate.wait_while_until(
+                    || self.epoch() == epoch && !self.is_closed(),
+                    Instant::now() + timeout,
+                );
+            }
+        }
+    }
+
+    /// Executes one task of the member that is next entitled to have a task executed (i.e., the
+    /// member with the lowest pass that has a task available and has not reached its maximum
+    /// number of threads), skipping the members in `stopped`. Returns `false` if no member has a
+    /// task available.
+    fn execute_next(
+        &self,
+        workers: &mut HashMap<usize, Box<dyn Any>>,
+        stopped: &mut HashSet<usize>,
+        thread_index: usize,
+    ) -> bool {
+        let candidates = self.members.lock().candidates();
+        for id in candidates {
+            if stopped.contains(&id) {
+                continue;
+            }
+            let Some(hive) = self.members.lock().try_start(id) else {
+                continue;
+            };
+            let slot = workers.entry(id).or_insert_with(|| hive.new_slot());
+            let result = hive.execute_next(slot, thread_index);
+            // release the tenant before finishing so that a hive that is being converted into a
+            // `Husk` is not held up by this thread
+            drop(hive);
+            self.members.lock().finish(id, result.is_some());
+            if let Some(stop) = result {
+                if stop {
+                    workers.remove(&id);
+                    stopped.insert(id);
+                }
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Members {
+    /// Removes members whose hives have been dropped or poisoned.
+    fn prune(&mut self) {
+        self.list.retain(|member| member.hive.is_alive());
+    }
+
+    /// Returns the ids of the members.
+    fn ids(&self) -> Vec<usize> {
+        self.list.iter().map(|member| member.id).collect()
+    }
+
+    /// Removes and returns the member with the given `key`, if there is one.
+    fn remove(&mut self, key: usize) -> Option<Member> {
+        let pos = self.list.iter().position(|member| member.key == key)?;
+        Some(self.list.remove(pos))
+    }
+
+    /// Returns the time an idle thread should wait before checking the members for tasks again,
+    /// which is at most `timeout`.
+    fn idle_timeout(&self, timeout: Duration) -> Duration {
+        self.list
+            .iter()
+            .map(|member| member.hive.idle_timeout(timeout))
+            .min()
+            .unwrap_or(timeout)
+    }
+
+    /// Calls `f` on the member with the given `key`. Returns `false` if there is no such member.
+    fn update<F: FnOnce(&mut Member)>(&mut self, key: usize, f: F) -> bool {
+        self.list
+            .iter_mut()
+            .find(|member| member.key == key)
+            .map(f)
+            .is_some()
+    }
+
+    /// Returns the ids of the members that have not reached their maximum number of threads, in
+    /// the order in which they are entitled to have a task executed.
+    fn candidates(&mut self) -> Vec<usize> {
+        self.prune();
+        let vtime = self.vtime;
+        let mut candidates: Vec<_> = self
+            .list
+            .iter()
+            .filter(|member| member.has_capacity())
+            .map(|member| (member.pass.max(vtime), member.id))
+            .collect();
+        candidates.sort();
+        candidates.into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// Marks a thread as executing a task of the member with the given `id` and returns the
+    /// member's hive. Returns `None` if the member no longer exists or has reached its maximum
+    /// number of threads.
+    fn try_start(&mut self, id: usize) -> Option<Arc<dyn Tenant>> {
+        match self.list.iter_mut().find(|member| member.id == id) {
+            Some(member) if member.has_capacity() => {
+                member.active += 1;
+                Some(Arc::clone(&member.hive))
+            }
+            _ => None,
+        }
+    }
+
+    /// Marks a thread as no longer executing a task of the member with the given `id`. If a task
+    /// was `executed`, the member's pass is advanced in inverse proportion to its weight.
+    fn finish(&mut self, id: usize, executed: bool) {
+        let vtime = self.vtime;
+        if let Some(member) = self.list.iter_mut().find(|member| member.id == id) {
+            member.active -= 1;
+            if executed {
+                let pass = member.pass.max(vtime);
+                member.pass = pass + STRIDE / member.weight as u64;
+                self.vtime = pass;
+            }
+        }
+    }
+}
+
+impl Member {
+    fn has_capacity(&self) -> bool {
+        !matches!(self.max_threads, Some(max) if self.active >= max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Apiary, ApiaryError};
+    use crate::bee::stock::{Caller, Thunk, ThunkWorker};
+    use crate::hive::{outcome_channel, Builder, ManualClock, OutcomeStore, PanicPolicy};
+    use parking_lot::Mutex;
+    use std::panic::AssertUnwindSafe;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_shared_threads() {
+        let apiary = Apiary::new(2).unwrap();
+        let mut squares = Builder::new()
+            .build_with(Caller::of(|i: u64| i * i))
+            .unwrap();
+        let mut lengths = Builder::new()
+            .build_with(Caller::of(|s: String| s.len()))
+            .unwrap();
+        apiary.register(&squares, 1).unwrap();
+        apiary.register(&lengths, 1).unwrap();
+        squares.map_store(0..10);
+        lengths.map_store(["a", "bb", "ccc"].into_iter().map(String::from));
+        squares.join();
+        lengths.join();
+        // each hive keeps its own outcomes and stats
+        assert_eq!(squares.num_successes(), 10);
+        assert_eq!(lengths.num_successes(), 3);
+        assert_eq!(squares.remove_success(3), Some(9));
+        assert_eq!(lengths.remove_success(2), Some(3));
+        assert_eq!(apiary.num_members(), 2);
+        // a dropped hive is unregistered automatically
+        drop(lengths);
+        assert_eq!(apiary.num_members(), 1);
+    }
+
+    #[test]
+    fn test_register_errors() {
+        let apiary = Apiary::new(1).unwrap();
+        let hive = Builder::new().build_with(Caller::of(|i: u8| i)).unwrap();
+        apiary.register(&hive, 1).unwrap();
+        assert_eq!(
+            apiary.register(&hive, 1),
+            Err(ApiaryError::AlreadyRegistered)
+        );
+        assert!(apiary.unregister(&hive));
+        assert!(!apiary.unregister(&hive));
+        // once unregistered, the hive can be registered with another apiary
+        let other = Apiary::new(1).unwrap();
+        other.register(&hive, 1).unwrap();
+        assert_eq!(hive.apply(5).unwrap(), 5);
+        let deterministic = Builder::new()
+            .deterministic()
+            .build_with(Caller::of(|i: u8| i))
+            .unwrap();
+        assert_eq!(
+            apiary.register(&deterministic, 1),
+            Err(ApiaryError::Deterministic)
+        );
+    }
+
+    #[test]
+    fn test_weighted_fairness() {
+        let apiary = Apiary::new(1).unwrap();
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let hive_for = |name: char| {
+            let order = Arc::clone(&order);
+            Builder::new()
+                .build_with(Caller::of(move |_: ()| order.lock().push(name)))
+                .unwrap()
+        };
+        let heavy = hive_for('h');
+        let light = hive_for('l');
+        let blocker = Builder::new()
+            .build_with_default::<ThunkWorker<()>>()
+            .unwrap();
+        apiary.register(&heavy, 3).unwrap();
+        apiary.register(&light, 1).unwrap();
+        apiary.register(&blocker, 1).unwrap();
+        // occupy the only thread until both hives have queued tasks
+        let (started_tx, started_rx) = mpsc::channel::<()>();
+        let (tx, rx) = mpsc::channel::<()>();
+        blocker.apply_store(Thunk::of(move || {
+            started_tx.send(()).unwrap();
+            rx.recv().unwrap()
+        }));
+        started_rx.recv().unwrap();
+        heavy.map_store((0..40).map(|_| ()));
+        light.map_store((0..40).map(|_| ()));
+        tx.send(()).unwrap();
+        heavy.join();
+        let order = order.lock();
+        let num_heavy = order[..20].iter().filter(|name| **name == 'h').count();
+        assert!((14..=16).contains(&num_heavy), "{num_heavy}");
+    }
+
+    #[test]
+    fn test_max_threads() {
+        let apiary = Apiary::new(4).unwrap();
+        let active = Arc::new(AtomicUsize::new(0));
+        let max_active = Arc::new(AtomicUsize::new(0));
+        let hive = {
+            let active = Arc::clone(&active);
+            let max_active = Arc::clone(&max_active);
+            Builder::new()
+                .build_with(Caller::of(move |_: ()| {
+                    let n = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_active.fetch_max(n, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(10));
+                    active.fetch_sub(1, Ordering::SeqCst);
+                }))
+                .unwrap()
+        };
+        apiary.register(&hive, 1).unwrap();
+        assert!(apiary.set_max_threads(&hive, Some(2)));
+        hive.map_store((0..20).map(|_| ()));
+        hive.join();
+        assert_eq!(hive.num_successes(), 20);
+        assert_eq!(max_active.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_suspend_member() {
+        let apiary = Apiary::new(2).unwrap();
+        let hive = Builder::new().build_with(Caller::of(|i: usize| i)).unwrap();
+        apiary.register(&hive, 1).unwrap();
+        hive.suspend();
+        hive.map_store(0..5);
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(hive.num_tasks(), (5, 0));
+        hive.resume();
+        hive.join();
+        assert_eq!(hive.num_successes(), 5);
+    }
+
+    #[test]
+    fn test_into_husk() {
+        let apiary = Apiary::new(2).unwrap();
+        let hive = Builder::new()
+            .build_with(Caller::of(|i: usize| i + 1))
+            .unwrap();
+        apiary.register(&hive, 1).unwrap();
+        hive.map_store(0..10);
+        let husk = hive.try_into_husk().unwrap();
+        assert_eq!(husk.num_successes(), 10);
+        assert_eq!(apiary.num_members(), 0);
+    }
+
+    #[test]
+    fn test_into_husk_busy() {
+        let apiary = Apiary::new(4).unwrap();
+        // another member keeps the threads busy checking the members for tasks
+        let busy = Builder::new().build_with(Caller::of(|i: usize| i)).unwrap();
+        apiary.register(&busy, 1).unwrap();
+        busy.map_store(0..10_000);
+        for _ in 0..100 {
+            let hive = Builder::new().build_with(Caller::of(|i: usize| i)).unwrap();
+            apiary.register(&hive, 1).unwrap();
+            hive.map_store(0..10);
+            let husk = hive.try_into_husk().unwrap();
+            assert_eq!(husk.num_successes() + husk.num_unprocessed(), 10);
+        }
+        assert_eq!(apiary.num_members(), 1);
+    }
+
+    #[test]
+    fn test_panic_stop() {
+        let apiary = Apiary::new(2).unwrap();
+        let hive = Builder::new()
+            .panic_policy(PanicPolicy::Stop)
+            .build_with_default::<ThunkWorker<u8>>()
+            .unwrap();
+        let other = Builder::new().build_with(Caller::of(|i: u8| i)).unwrap();
+        apiary.register(&hive, 1).unwrap();
+        apiary.register(&other, 1).unwrap();
+        // a thread that panics no longer executes the hive's tasks, so the second panic occurs on
+        // the other thread
+        hive.map_store((0..2).map(|_| Thunk::of(|| panic!("oh no!"))));
+        hive.join();
+        assert_eq!(hive.num_panics(), 2);
+        hive.apply_store(Thunk::of(|| 1));
+        assert!(!hive.join_timeout(Duration::from_millis(100)));
+        assert_eq!(hive.num_tasks(), (1, 0));
+        // the threads still execute the tasks of the other members
+        assert_eq!(other.apply(5).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_panic_propagate() {
+        let apiary = Apiary::new(1).unwrap();
+        let hive = Builder::new()
+            .panic_policy(PanicPolicy::Propagate)
+            .build_with(Caller::of(|i: u8| {
+                assert!(i != 0, "oh no!");
+                i
+            }))
+            .unwrap();
+        apiary.register(&hive, 1).unwrap();
+        // the panic is re-raised on the submitting thread
+        assert!(std::panic::catch_unwind(AssertUnwindSafe(|| hive.apply(0))).is_err());
+        assert_eq!(hive.apply(1).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_panic_respawn_max() {
+        let apiary = Apiary::new(1).unwrap();
+        let hive = Builder::new()
+            .panic_policy(PanicPolicy::RespawnMax(1))
+            .build_with_default::<ThunkWorker<u8>>()
+            .unwrap();
+        apiary.register(&hive, 1).unwrap();
+        hive.map_store((0..2).map(|_| Thunk::of(|| panic!("oh no!"))));
+        hive.join();
+        assert!(hive.is_poisoned());
+        assert_eq!(apiary.num_members(), 0);
+    }
+
+    #[test]
+    fn test_member_clock() {
+        let apiary = Apiary::new(1).unwrap();
+        let clock = ManualClock::new();
+        let hive = Builder::new()
+            .rate_limit(1, Duration::from_secs(60))
+            .rate_limit_burst(1)
+            .clock(clock.clone())
+            .build_with(Caller::of(|i: u8| i))
+            .unwrap();
+        apiary.register(&hive, 1).unwrap();
+        let (tx, rx) = outcome_channel();
+        hive.swarm_send(0..2, tx);
+        assert!(rx.recv_timeout(Duration::from_secs(1)).is_ok());
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+        // the deferred task becomes available once the member's clock has advanced
+        clock.advance(Duration::from_secs(60));
+        assert!(rx.recv_timeout(Duration::from_secs(1)).is_ok());
+    }
+}
@@ -1,5 +1,7 @@
-use super::{Config, Hive, SpawnError};
+use super::clock::{Clock, ClockRef};
+use super::{Config, Hive, PanicPolicy, RateLimit, SpawnError, StoreLimitPolicy};
 use crate::bee::{CloneQueen, DefaultQueen, Queen, Worker};
+use std::time::Duration;
 
 /// A `Builder` for a `Hive`.
 ///
@@ -15,6 +17,13 @@ use crate::bee::{CloneQueen, DefaultQueen, Queen, Worker};
 ///   `ApplyError::Retryable` error. Only available with feature `retry`.
 /// * `affinity`: List of CPU core indicies to which the threads should be pinned. Only available
 ///   with feature `affinity`.
+/// * `rate_limit`: maximum rate at which tasks are started across all threads, and
+///   `key_rate_limit`: maximum rate at which tasks with the same key are started.
+/// * `task_group`: maximum number of active tasks in a named task group.
+/// * `panic_policy`: how the [`Hive`] responds when a `Worker` panics.
+/// * `deterministic`: execute tasks on the submitting thread rather than on worker threads.
+/// * `batch`: maximum number of tasks each thread executes at once, and how long it waits for
+///   additional tasks.
 ///
 /// Calling `Builder::new()` creates an unconfigured `Builder`, while calling `Builder::default()`
 /// creates a `Builder` with `num_threads`, `max_retries`, and `retry_factor` set to the global
@@ -183,6 +192,319 @@ impl Builder {
         self
     }
 
+    /// Sets the [`Clock`] used by the built [`Hive`] to determine when a task that failed with a
+    /// retryable error may be retried, when a rate-limited task may be started, which panics fall
+    /// within the window of `PanicPolicy::RespawnWithin`, and when timeouts (of `join_timeout`,
+    /// `wait_for`, `ShutdownMode::DrainWithin`, and batches) have elapsed. By default, the system
+    /// clock is used.
+    ///
+    /// Using a [`ManualClock`] enables a backoff schedule or a timeout to be tested without
+    /// waiting for real time to pass. This is especially useful in combination with
+    /// [`Builder::deterministic`], since the submitting thread "sleeps" until each retry is
+    /// available by advancing the `ManualClock`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use beekeeper::bee::stock::Caller;
+    /// use beekeeper::hive::{Builder, ManualClock, OutcomeIteratorExt};
+    /// use std::time::Duration;
+    ///
+    /// # fn main() {
+    /// let clock = ManualClock::new();
+    /// let hive = Builder::new()
+    ///     .deterministic()
+    ///     .rate_limit(1, Duration::from_secs(60))
+    ///     .rate_limit_burst(1)
+    ///     .clock(clock.clone())
+    ///     .build_with(Caller::of(|i: usize| i * 2))
+    ///     .unwrap();
+    /// let outputs: Vec<_> = hive.map(0..3).into_outputs().collect();
+    /// assert_eq!(outputs, vec![0, 2, 4]);
+    /// // the second and third tasks each had to wait a minute for the rate limit
+    /// assert_eq!(clock.elapsed(), Duration::from_secs(120));
+    /// # }
+    /// ```
+    ///
+    /// [`Hive`]: hive/struct.Hive.html
+    /// [`Clock`]: hive/trait.Clock.html
+    /// [`ManualClock`]: hive/struct.ManualClock.html
+    /// [`Builder::deterministic`]: #method.deterministic
+    pub fn clock<C: Clock>(mut self, clock: C) -> Self {
+        let _ = self.0.clock.set(Some(ClockRef::new(clock)));
+        self
+    }
+
+    /// Limits the rate at which tasks are started by the built [`Hive`] to `n` tasks per `per`,
+    /// across all worker threads. Before starting a task, a worker thread takes a token from a
+    /// token bucket that is shared by all worker threads. If no token is available, the task is
+    /// set aside until one may be, and the worker thread moves on to the next task. Until it is
+    /// started, the task is still counted as queued rather than active.
+    ///
+    /// By default, up to `n` tasks may be started at once after a period of inactivity; use
+    /// [`rate_limit_burst`](Builder::rate_limit_burst) to change the burst size.
+    ///
+    /// [`Hive`]: hive/struct.Hive.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is `0` or `per` is zero.
+    ///
+    /// # Examples
+    ///
+    /// No more than ten tasks per second will be started by this hive:
+    ///
+    /// ```
+    /// use beekeeper::bee::stock::{Thunk, ThunkWorker};
+    /// use beekeeper::hive::{Builder, Hive};
+    /// use std::time::Duration;
+    ///
+    /// # fn main() {
+    /// let hive = Builder::new()
+    ///     .num_threads(4)
+    ///     .rate_limit(10, Duration::from_secs(1))
+    ///     .build_with_default::<ThunkWorker<()>>()
+    ///     .unwrap();
+    ///
+    /// for _ in 0..10 {
+    ///     hive.apply_store(Thunk::of(|| {
+    ///         println!("Hello from a rate-limited worker thread!")
+    ///     }));
+    /// }
+    /// # hive.join();
+    /// # }
+    /// ```
+    pub fn rate_limit(mut self, n: u32, per: Duration) -> Self {
+        let _ = self.0.rate_limit.set(Some(RateLimit::new(n, per)));
+        self
+    }
+
+    /// Sets the maximum number of tasks that may be started at once after a period of inactivity
+    /// when a rate limit is set using [`rate_limit`](Builder::rate_limit). This method may be
+    /// called before or after `rate_limit`, but it has no effect if a rate limit is never set.
+    pub fn rate_limit_burst(mut self, burst: u32) -> Self {
+        let _ = self.0.rate_limit_burst.set(Some(burst));
+        self
+    }
+
+    /// Sets a rate limit that applies separately to each distinct rate-limit key. The key of a
+    /// task is determined by the function passed to [`Hive::set_rate_limit_key`]; tasks without a
+    /// key are only subject to the global rate limit (if any).
+    ///
+    /// [`Hive::set_rate_limit_key`]: hive/struct.Hive.html#method.set_rate_limit_key
+    pub fn key_rate_limit(mut self, limit: RateLimit) -> Self {
+        let _ = self.0.key_rate_limit.set(Some(limit));
+        self
+    }
+
+    /// Adds a named task group, of which at most `max_concurrency` tasks may be active at the
+    /// same time in the built [`Hive`]. Tasks are submitted to a group using e.g.
+    /// [`Hive::apply_in_group`]. Groups without a limit do not need to be declared.
+    ///
+    /// [`Hive`]: hive/struct.Hive.html
+    /// [`Hive::apply_in_group`]: hive/struct.Hive.html#method.apply_in_group
+    pub fn task_group<S: Into<String>>(mut self, name: S, max_concurrency: usize) -> Self {
+        let mut group_limits = self.0.group_limits.get().unwrap_or_default();
+        group_limits.insert(name.into(), max_concurrency);
+        let _ = self.0.group_limits.set(Some(group_limits));
+        self
+    }
+
+    /// Sets the [`PanicPolicy`] that determines how the built [`Hive`] responds when a `Worker`
+    /// panics. By default, a worker thread that panics is always replaced with a new thread.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use beekeeper::bee::stock::{PunkWorker, Thunk};
+    /// use beekeeper::hive::{Builder, PanicPolicy};
+    ///
+    /// # fn main() {
+    /// let hive = Builder::new()
+    ///     .num_threads(2)
+    ///     .panic_policy(PanicPolicy::RespawnMax(2))
+    ///     .build_with_default::<PunkWorker<()>>()
+    ///     .unwrap();
+    /// for _ in 0..3 {
+    ///     let _ = hive.apply(Thunk::of(|| panic!("oh no!")));
+    /// }
+    /// assert!(hive.is_poisoned());
+    /// # }
+    /// ```
+    ///
+    /// [`Hive`]: hive/struct.Hive.html
+    /// [`PanicPolicy`]: hive/enum.PanicPolicy.html
+    pub fn panic_policy(mut self, policy: PanicPolicy) -> Self {
+        let _ = self.0.panic_policy.set(Some(policy));
+        self
+    }
+
+    /// Configures the built [`Hive`] to be deterministic, which is mainly useful for testing.
+    ///
+    /// A deterministic `Hive` does not spawn any worker threads (`num_threads` is ignored).
+    /// Instead, tasks are executed synchronously, in the order they were submitted, on the thread
+    /// that submits them. A new `Worker` is created each time a thread starts executing queued
+    /// tasks, and it is dropped once there are no more tasks available. Outcomes, retries
+    /// (including backoff delays), rate limits, and suspension work the same as for a
+    /// multi-threaded `Hive`: if the `Hive` is suspended, submitted tasks remain queued until it
+    /// is resumed, at which point they are executed on the thread that calls `resume`.
+    ///
+    /// A task that panics is recorded as usual, and the `Worker` is replaced with a new one.
+    /// Since there is no worker thread to stop, [`PanicPolicy::Stop`] is treated as
+    /// [`PanicPolicy::Respawn`] - the `Hive` continues to execute tasks after a panic.
+    ///
+    /// [`PanicPolicy::Stop`]: crate::hive::PanicPolicy::Stop
+    /// [`PanicPolicy::Respawn`]: crate::hive::PanicPolicy::Respawn
+    ///
+    /// A `Worker` must not submit tasks to its own deterministic `Hive` and then block waiting
+    /// for their outcomes (e.g., using `apply`), as this would deadlock. Adding threads (e.g.,
+    /// using `Hive::grow`) is allowed, but makes the `Hive` non-deterministic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use beekeeper::bee::stock::{Thunk, ThunkWorker};
+    /// use beekeeper::hive::Builder;
+    /// use std::thread;
+    ///
+    /// # fn main() {
+    /// let hive = Builder::new()
+    ///     .deterministic()
+    ///     .build_with_default::<ThunkWorker<thread::ThreadId>>()
+    ///     .unwrap();
+    /// let indices = hive.map_store((0..3).map(|_| Thunk::of(|| thread::current().id())));
+    /// // all of the tasks have already been executed on this thread
+    /// assert_eq!(hive.num_tasks(), (0, 0));
+    /// for outcome in hive.take_stored().into_values() {
+    ///     assert_eq!(outcome.unwrap(), thread::current().id());
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// [`Hive`]: hive/struct.Hive.html
+    pub fn deterministic(mut self) -> Self {
+        let _ = self.0.deterministic.set(Some(true));
+        self
+    }
+
+    /// Configures the worker threads of the built [`Hive`] to execute tasks in batches of up to
+    /// `max_size` tasks using [`Worker::apply_batch`]. After a thread receives a task, it waits
+    /// up to `max_wait` for additional tasks to become available before it executes the batch,
+    /// so a batch may contain fewer than `max_size` tasks.
+    ///
+    /// There is still one `Outcome` per task, and (if the `retry` feature is enabled) each task in
+    /// a batch that fails with a retryable error is retried individually. If the `Worker`
+    /// panics while executing a batch, the outcomes of all the tasks in the batch are lost, unless
+    /// the panic policy is [`PanicPolicy::Propagate`], in which case every task in the batch has
+    /// an `Outcome::Panic`. Only the first of these has the original panic payload; the others
+    /// have a copy of the panic message.
+    /// Batching has no effect if the `Hive` is [`deterministic`](Builder::deterministic).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_size` is `0`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use beekeeper::bee::prelude::*;
+    /// use beekeeper::bee::stock::Batched;
+    /// use beekeeper::hive::{Builder, Outcome};
+    /// use std::time::Duration;
+    ///
+    /// #[derive(Debug, Default)]
+    /// struct Squarer;
+    ///
+    /// impl BatchWorker for Squarer {
+    ///     type Input = u32;
+    ///     type Output = u32;
+    ///     type Error = ();
+    ///
+    ///     fn apply_batch(&mut self, inputs: Vec<u32>) -> Vec<BatchWorkerResult<Self>> {
+    ///         inputs.into_iter().map(|i| Ok(i * i)).collect()
+    ///     }
+    /// }
+    ///
+    /// # fn main() {
+    /// let hive = Builder::new()
+    ///     .num_threads(2)
+    ///     .batch(10, Duration::from_millis(10))
+    ///     .build_with_default::<Batched<Squarer>>()
+    ///     .unwrap();
+    /// let outputs: Vec<u32> = hive.map(0..100u32).map(Outcome::unwrap).collect();
+    /// assert_eq!(outputs, (0..100).map(|i| i * i).collect::<Vec<_>>());
+    /// # }
+    /// ```
+    ///
+    /// [`Hive`]: hive/struct.Hive.html
+    /// [`Worker::apply_batch`]: crate::bee::Worker::apply_batch
+    /// [`PanicPolicy::Propagate`]: crate::hive::PanicPolicy::Propagate
+    pub fn batch(mut self, max_size: usize, max_wait: Duration) -> Self {
+        assert!(max_size > 0, "batch size must be greater than 0");
+        let _ = self.0.batch_size.set(Some(max_size));
+        let _ = self.0.batch_timeout.set(Some(max_wait));
+        self
+    }
+
+    /// Sets the maximum number of outcomes that may be stored in the built [`Hive`] (i.e., the
+    /// outcomes of tasks submitted with e.g. `apply_store` that have not yet been removed). What
+    /// happens when a worker thread needs to store an outcome once the limit is reached is
+    /// determined by the [`StoreLimitPolicy`], which defaults to dropping the oldest stored
+    /// outcome. By default there is no limit.
+    ///
+    /// The limit applies to outcomes stored by worker threads; outcomes of tasks that are never
+    /// processed (e.g., because the `Hive` is closed or poisoned) are always stored. A function
+    /// can be set using [`Hive::set_store_limit_handler`] that is called each time the limit is
+    /// reached.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is `0`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use beekeeper::bee::stock::EchoWorker;
+    /// use beekeeper::hive::{Builder, OutcomeStore, StoreLimitPolicy};
+    ///
+    /// # fn main() {
+    /// let hive = Builder::new()
+    ///     .num_threads(1)
+    ///     .max_stored_outcomes(5)
+    ///     .store_limit_policy(StoreLimitPolicy::DropOldest)
+    ///     .build_with_default::<EchoWorker<usize>>()
+    ///     .unwrap();
+    /// hive.map_store(0..10);
+    /// hive.join();
+    /// let stored = hive.take_stored();
+    /// assert_eq!(stored.len(), 5);
+    /// assert_eq!(hive.num_dropped_outcomes(), 5);
+    /// # }
+    /// ```
+    ///
+    /// [`Hive`]: hive/struct.Hive.html
+    /// [`StoreLimitPolicy`]: hive/enum.StoreLimitPolicy.html
+    /// [`Hive::set_store_limit_handler`]: hive/struct.Hive.html#method.set_store_limit_handler
+    pub fn max_stored_outcomes(mut self, max: usize) -> Self {
+        assert!(
+            max > 0,
+            "maximum number of stored outcomes must be greater than 0"
+        );
+        let _ = self.0.max_stored_outcomes.set(Some(max));
+        self
+    }
+
+    /// Sets the [`StoreLimitPolicy`] that determines what the built [`Hive`] does when the limit
+    /// set with [`max_stored_outcomes`](Builder::max_stored_outcomes) is reached. Has no effect if
+    /// there is no limit.
+    ///
+    /// [`Hive`]: hive/struct.Hive.html
+    /// [`StoreLimitPolicy`]: hive/enum.StoreLimitPolicy.html
+    pub fn store_limit_policy(mut self, policy: StoreLimitPolicy) -> Self {
+        let _ = self.0.store_limit_policy.set(Some(policy));
+        self
+    }
+
     /// Consumes this `Builder` and returns a new `Hive` using the given `Queen` to create
     /// `Worker`s.
     ///
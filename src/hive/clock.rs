@@ -0,0 +1,188 @@
+//! Sources of time used by a `Hive` for retries, rate limits, and timeouts.
+use parking_lot::Mutex;
+use std::fmt::Debug;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A source of time. A `Hive` uses its `Clock` to determine when a task that failed with a
+/// retryable error may be retried, when a rate-limited task may be started, when a panic falls
+/// outside the window of `PanicPolicy::RespawnWithin`, and when a timeout (e.g., of `join_timeout`
+/// or of a batch) has elapsed.
+///
+/// The default clock is the `SystemClock`. A `ManualClock` may be used in tests so that backoff
+/// schedules and timeouts can be verified without waiting for real time to pass.
+pub trait Clock: Debug + Send + Sync + 'static {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+
+    /// Blocks the current thread until `duration` has elapsed according to this clock.
+    fn sleep(&self, duration: Duration);
+
+    /// Returns the longest (real) time that a thread waiting for this clock to reach a deadline
+    /// blocks before checking the clock again, or `None` if this clock advances with real time
+    /// and so it is sufficient to wait for the time remaining until the deadline. Defaults to
+    /// `None`.
+    fn poll_interval(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// A `Clock` that uses the system's monotonic clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// A `Clock` whose time only changes when it is advanced explicitly. Clones of a `ManualClock`
+/// share the same time, so a clone can be given to a `Builder` while the original is used to
+/// advance the time.
+///
+/// Calling `sleep` advances the clock by the given duration rather than blocking the thread.
+/// Threads that are waiting for a deadline check a `ManualClock` every millisecond, so they notice
+/// promptly when it is advanced.
+///
+/// # Examples
+///
+/// ```
+/// use beekeeper::hive::{Clock, ManualClock};
+/// use std::time::Duration;
+///
+/// let clock = ManualClock::new();
+/// let start = clock.now();
+/// clock.advance(Duration::from_secs(10));
+/// assert_eq!(clock.now() - start, Duration::from_secs(10));
+/// clock.sleep(Duration::from_secs(5));
+/// assert_eq!(clock.elapsed(), Duration::from_secs(15));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    start: Instant,
+    offset: Arc<Mutex<Duration>>,
+}
+
+impl ManualClock {
+    /// Creates a new `ManualClock` whose current time is the current system time.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            offset: Default::default(),
+        }
+    }
+
+    /// Advances the time of this clock (and all its clones) by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        *self.offset.lock() += duration;
+    }
+
+    /// Returns the total amount by which this clock has been advanced.
+    pub fn elapsed(&self) -> Duration {
+        *self.offset.lock()
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + *self.offset.lock()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+
+    fn poll_interval(&self) -> Option<Duration> {
+        Some(MANUAL_POLL_INTERVAL)
+    }
+}
+
+/// How often threads waiting for a deadline check a `ManualClock`.
+const MANUAL_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A shareable reference to a `Clock` that can be stored in a `Config`. Defaults to `SystemClock`.
+#[derive(Clone, Debug)]
+pub struct ClockRef(Arc<dyn Clock>);
+
+impl ClockRef {
+    pub fn new<C: Clock>(clock: C) -> Self {
+        Self(Arc::new(clock))
+    }
+
+    /// Returns the (real) time to wait before checking whether `deadline` has been reached
+    /// according to this clock.
+    pub fn timeout_until(&self, deadline: Instant) -> Duration {
+        let remaining = deadline.saturating_duration_since(self.now());
+        match self.poll_interval() {
+            Some(interval) => remaining.min(interval),
+            None => remaining,
+        }
+    }
+}
+
+impl Default for ClockRef {
+    fn default() -> Self {
+        Self::new(SystemClock)
+    }
+}
+
+/// Two `ClockRef`s are equal if they refer to the same `Clock`.
+impl PartialEq for ClockRef {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for ClockRef {}
+
+impl Deref for ClockRef {
+    type Target = dyn Clock;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, ClockRef, ManualClock, MANUAL_POLL_INTERVAL};
+    use std::time::Duration;
+
+    #[test]
+    fn test_timeout_until() {
+        let clock = ManualClock::new();
+        let clock_ref = ClockRef::new(clock.clone());
+        let deadline = clock.now() + Duration::from_secs(10);
+        // a `ManualClock` is polled rather than waited on for the remaining time
+        assert_eq!(clock_ref.timeout_until(deadline), MANUAL_POLL_INTERVAL);
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(clock_ref.timeout_until(deadline), Duration::ZERO);
+        let system = ClockRef::default();
+        let deadline = system.now() + Duration::from_secs(10);
+        assert!(system.timeout_until(deadline) > Duration::from_secs(9));
+    }
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::new();
+        let clock_ref = ClockRef::new(clock.clone());
+        let start = clock_ref.now();
+        clock.advance(Duration::from_secs(1));
+        clock_ref.sleep(Duration::from_secs(2));
+        assert_eq!(clock_ref.now() - start, Duration::from_secs(3));
+        assert_eq!(clock.elapsed(), Duration::from_secs(3));
+    }
+}
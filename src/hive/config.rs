@@ -54,8 +54,20 @@ impl Config {
             max_retries: self.max_retries.into_sync(),
             #[cfg(feature = "retry")]
             retry_factor: self.retry_factor.into_sync(),
+            clock: self.clock.into_sync(),
             #[cfg(feature = "affinity")]
             affinity: self.affinity.into_sync(),
+            rate_limit: self.rate_limit.into_sync(),
+            rate_limit_burst: self.rate_limit_burst.into_sync(),
+            key_rate_limit: self.key_rate_limit.into_sync(),
+            group_limits: self.group_limits.into_sync(),
+            panic_policy: self.panic_policy.into_sync(),
+            deterministic: self.deterministic.into_sync(),
+            batch_size: self.batch_size.into_sync(),
+            batch_timeout: self.batch_timeout.into_sync(),
+            max_stored_outcomes: self.max_stored_outcomes.into_sync(),
+            store_limit_policy: self.store_limit_policy.into_sync(),
+            next_task_index: self.next_task_index.into_sync(),
         }
     }
 
@@ -68,8 +80,20 @@ impl Config {
             max_retries: self.max_retries.into_unsync(),
             #[cfg(feature = "retry")]
             retry_factor: self.retry_factor.into_unsync(),
+            clock: self.clock.into_unsync(),
             #[cfg(feature = "affinity")]
             affinity: self.affinity.into_unsync(),
+            rate_limit: self.rate_limit.into_unsync(),
+            rate_limit_burst: self.rate_limit_burst.into_unsync(),
+            key_rate_limit: self.key_rate_limit.into_unsync(),
+            group_limits: self.group_limits.into_unsync(),
+            panic_policy: self.panic_policy.into_unsync(),
+            deterministic: self.deterministic.into_unsync(),
+            batch_size: self.batch_size.into_unsync(),
+            batch_timeout: self.batch_timeout.into_unsync(),
+            max_stored_outcomes: self.max_stored_outcomes.into_unsync(),
+            store_limit_policy: self.store_limit_policy.into_unsync(),
+            next_task_index: self.next_task_index.into_unsync(),
         }
     }
 }
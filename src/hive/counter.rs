@@ -26,8 +26,9 @@ pub enum CounterError {
 /// The two values may be different sizes, but their total size in bits must equal the size of the
 /// data type (for now fixed to `64`) used to store the value.
 ///
-/// Three operations are supported:
+/// Four operations are supported:
 /// * increment the left counter (`L`)
+/// * decrement the left counter (`L`)
 /// * decrement the right counter (`R`)
 /// * transfer an amount `N` from `L` to `R` (i.e., a simultaneous decrement of `L` and
 ///   increment of `R` by the same amount)
@@ -35,9 +36,15 @@ pub struct DualCounter<const L: usize>(AtomicU64);
 
 impl<const L: usize> DualCounter<L> {
     // validate that L is > 0
-    const L_BITS: u32 = L.checked_sub(1).expect("L must be > 0") as u32 + 1;
+    const L_BITS: u32 = {
+        assert!(L > 0, "L must be > 0");
+        L as u32
+    };
     // validate that L is < 64
-    const R_BITS: u32 = 63u32.checked_sub(Self::L_BITS).expect("L must be <= 63") + 1;
+    const R_BITS: u32 = {
+        assert!(Self::L_BITS <= 63, "L must be <= 63");
+        64 - Self::L_BITS
+    };
     // compute the maximum possible values for L and R
     const L_MAX: u64 = (1 << Self::L_BITS) - 1;
     const R_MAX: u64 = (1 << Self::R_BITS) - 1;
@@ -73,6 +80,22 @@ impl<const L: usize> DualCounter<L> {
         }
     }
 
+    /// Decrements the left counter by `n` and returns the previous value.
+    ///
+    /// Returns an error if `n` is greater than the maximum value (2^L - 1) or if the left counter
+    /// underflows when decremented by `n`.
+    pub fn decrement_left(&self, n: u64) -> Result<u64, CounterError> {
+        if n > Self::L_MAX {
+            return Err(CounterError::LeftUnderflow);
+        }
+        let prev_val = self.0.sub(n) & Self::L_MAX;
+        if prev_val >= n {
+            Ok(prev_val)
+        } else {
+            Err(CounterError::LeftUnderflow)
+        }
+    }
+
     /// Decrements the right counter by `n` and returns the previous value.
     ///
     /// Returns an error  if `n` is greater than the maximum value (2^(64-L) - 1) or if the right
@@ -143,6 +166,15 @@ mod tests {
         assert_eq!(counter.get(), (1, 0));
     }
 
+    #[test]
+    fn test_decrement_left() {
+        let counter = DualCounter::<48>::default();
+        assert!(counter.increment_left(3).is_ok());
+        assert!(counter.transfer(1).is_ok());
+        assert_eq!(counter.decrement_left(2).unwrap(), 2);
+        assert_eq!(counter.get(), (0, 1));
+    }
+
     #[test]
     fn test_increment_too_large() {
         let counter = DualCounter::<1>::default();
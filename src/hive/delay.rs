@@ -6,9 +6,10 @@ use std::time::{Duration, Instant};
 pub struct DelayQueue<T>(BinaryHeap<Delayed<T>>);
 
 impl<T> DelayQueue<T> {
-    /// Pushes an item onto the queue. Returns the `Instant` at which the item will be available.
-    pub fn push(&mut self, item: T, delay: Duration) -> Instant {
-        let delayed = Delayed::new(item, delay);
+    /// Pushes an item onto the queue that will be available `delay` after `now`. Returns the
+    /// `Instant` at which the item will be available.
+    pub fn push(&mut self, item: T, delay: Duration, now: Instant) -> Instant {
+        let delayed = Delayed::new(item, now + delay);
         let until = delayed.until;
         self.0.push(delayed);
         until
@@ -20,14 +21,10 @@ impl<T> DelayQueue<T> {
         self.0.peek().map(|head| head.until)
     }
 
-    /// Returns the item at the head of the queue, if one exists, and removes it.
-    pub fn try_pop(&mut self) -> Option<T> {
-        if self
-            .0
-            .peek()
-            .map(|head| head.until <= Instant::now())
-            .unwrap_or(false)
-        {
+    /// Returns the item at the head of the queue, if one exists and is available at `now`, and
+    /// removes it.
+    pub fn try_pop(&mut self, now: Instant) -> Option<T> {
+        if self.0.peek().map(|head| head.until <= now).unwrap_or(false) {
             Some(self.0.pop().unwrap().value)
         } else {
             None
@@ -53,11 +50,8 @@ struct Delayed<T> {
 }
 
 impl<T> Delayed<T> {
-    pub fn new(value: T, delay: Duration) -> Self {
-        Delayed {
-            value,
-            until: Instant::now() + delay,
-        }
+    pub fn new(value: T, until: Instant) -> Self {
+        Delayed { value, until }
     }
 }
 
@@ -89,40 +83,43 @@ impl<T> Eq for Delayed<T> {}
 #[cfg(test)]
 mod tests {
     use super::DelayQueue;
-    use std::{thread, time::Duration};
+    use crate::hive::{Clock, ManualClock};
+    use std::time::{Duration, Instant};
 
     #[test]
     fn test_works() {
+        let clock = ManualClock::new();
         let mut queue = DelayQueue::default();
 
-        queue.push(1, Duration::from_secs(1));
-        queue.push(2, Duration::from_secs(2));
-        queue.push(3, Duration::from_secs(3));
+        queue.push(1, Duration::from_secs(1), clock.now());
+        queue.push(2, Duration::from_secs(2), clock.now());
+        queue.push(3, Duration::from_secs(3), clock.now());
 
         assert_eq!(queue.0.len(), 3);
-        assert_eq!(queue.try_pop(), None);
+        assert_eq!(queue.try_pop(clock.now()), None);
 
-        thread::sleep(Duration::from_secs(1));
-        assert_eq!(queue.try_pop(), Some(1));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(queue.try_pop(clock.now()), Some(1));
         assert_eq!(queue.0.len(), 2);
 
-        thread::sleep(Duration::from_secs(1));
-        assert_eq!(queue.try_pop(), Some(2));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(queue.try_pop(clock.now()), Some(2));
         assert_eq!(queue.0.len(), 1);
 
-        thread::sleep(Duration::from_secs(1));
-        assert_eq!(queue.try_pop(), Some(3));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(queue.try_pop(clock.now()), Some(3));
         assert_eq!(queue.0.len(), 0);
 
-        assert_eq!(queue.try_pop(), None);
+        assert_eq!(queue.try_pop(clock.now()), None);
     }
 
     #[test]
     fn test_into_vec() {
+        let now = Instant::now();
         let mut queue = DelayQueue::default();
-        queue.push(1, Duration::from_secs(1));
-        queue.push(2, Duration::from_secs(2));
-        queue.push(3, Duration::from_secs(3));
+        queue.push(1, Duration::from_secs(1), now);
+        queue.push(2, Duration::from_secs(2), now);
+        queue.push(3, Duration::from_secs(3), now);
         let mut v: Vec<_> = queue.drain().collect();
         v.sort();
         assert_eq!(v, vec![1, 2, 3]);
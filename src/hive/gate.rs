@@ -1,5 +1,6 @@
 use parking_lot::{Condvar, Mutex};
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
 
 /// Wraps a `Mutex` and a `Condvar`, and provides methods for threads to wait on a condition and be
 /// notified when the condition may have changed.
@@ -22,6 +23,20 @@ impl Gate {
         }
     }
 
+    /// Like `wait_while`, but stops waiting at `deadline`. Returns `true` if the condition evaluated
+    /// to `false` before the deadline, otherwise `false`.
+    pub fn wait_while_until<F: Fn() -> bool>(&self, condition: F, deadline: Instant) -> bool {
+        if condition() {
+            let mut lock = self.mutex.lock();
+            while condition() {
+                if self.condvar.wait_until(&mut lock, deadline).timed_out() {
+                    return !condition();
+                }
+            }
+        }
+        true
+    }
+
     /// Notifies all waiting threads that the condition may have changed.
     pub fn notify_all(&self) {
         let _lock = self.mutex.lock();
@@ -61,6 +76,28 @@ impl PhasedGate {
         }
     }
 
+    /// Like `wait_while`, but stops waiting at `deadline`. Returns `true` if waiting finished
+    /// before the deadline (i.e., because the condition evaluated to `false` or the phase changed),
+    /// or `false` if the deadline was reached.
+    pub fn wait_while_until<F: Fn() -> bool>(&self, condition: F, deadline: Instant) -> bool {
+        if condition() {
+            let phase = self.phase.load(Ordering::SeqCst);
+            let mut lock = self.mutex.lock();
+            while phase == self.phase.load(Ordering::Relaxed) && condition() {
+                if self.condvar.wait_until(&mut lock, deadline).timed_out() {
+                    return !condition();
+                }
+            }
+            let _ = self.phase.compare_exchange(
+                phase,
+                phase.wrapping_add(1),
+                Ordering::SeqCst,
+                Ordering::Relaxed,
+            );
+        }
+        true
+    }
+
     /// Notifies all waiting threads that the condition may have changed.
     pub fn notify_all(&self) {
         let _lock = self.mutex.lock();
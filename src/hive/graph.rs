@@ -0,0 +1,467 @@
+//! A `TaskGraph` executes tasks in a `Hive` according to their dependencies.
+use super::{outcome_channel, Hive, Outcome, OutcomeSender};
+use crate::bee::{Queen, Worker};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Debug;
+use std::hash::Hash;
+
+/// The outputs of the predecessors of a node, keyed by node ID.
+pub type Predecessors<'a, K, O> = BTreeMap<&'a K, &'a O>;
+
+type InputFn<'a, K, W> =
+    Box<dyn FnOnce(&Predecessors<K, <W as Worker>::Output>) -> <W as Worker>::Input + 'a>;
+
+/// Error returned when running a `TaskGraph` that is not valid. No tasks are submitted if the graph
+/// is not valid.
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum GraphError<K: Debug> {
+    /// More than one node was added with the same ID.
+    #[error("Node {0:?} was added more than once")]
+    DuplicateNode(K),
+    /// An edge refers to a node ID that was never added.
+    #[error("Edge refers to unknown node {0:?}")]
+    UnknownNode(K),
+    /// The graph contains a cycle. Contains the (sorted) IDs of the nodes on one of the cycles;
+    /// nodes that only depend on a cycle are not included.
+    #[error("Graph contains a cycle involving nodes {0:?}")]
+    Cycle(Vec<K>),
+}
+
+/// The result of executing one node of a `TaskGraph`.
+pub enum NodeOutcome<K, W: Worker> {
+    /// The node's task was submitted to the `Hive`, and this is its `Outcome`.
+    Executed(Outcome<W>),
+    /// The node's task was never submitted because one of its (direct or indirect) predecessors,
+    /// `failed`, did not succeed.
+    Unprocessed { failed: K },
+}
+
+impl<K, W: Worker> NodeOutcome<K, W> {
+    /// Returns `true` if the node's task was executed successfully.
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Executed(Outcome::Success { .. }))
+    }
+
+    /// Returns the output of the node's task if it was executed successfully.
+    pub fn success(self) -> Option<W::Output> {
+        match self {
+            Self::Executed(Outcome::Success { value, .. }) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl<K: Debug, W: Worker> Debug for NodeOutcome<K, W>
+where
+    Outcome<W>: Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Executed(outcome) => f.debug_tuple("Executed").field(outcome).finish(),
+            Self::Unprocessed { failed } => f
+                .debug_struct("Unprocessed")
+                .field("failed", failed)
+                .finish(),
+        }
+    }
+}
+
+struct Node<'a, K, W: Worker> {
+    id: K,
+    input_fn: Option<InputFn<'a, K, W>>,
+    predecessors: Vec<usize>,
+    successors: Vec<usize>,
+}
+
+/// A directed acyclic graph of tasks. Each node has a unique ID and a function that creates the
+/// node's input from the outputs of its predecessors. When the graph is run, a node's task is
+/// submitted to the `Hive` once all of its predecessors have completed successfully. If any
+/// predecessor does not succeed, the node (and all of its dependents) are not executed and their
+/// outcomes are `NodeOutcome::Unprocessed`. A node whose task is lost (e.g., because its `Worker`
+/// panicked) has the outcome `NodeOutcome::Executed(Outcome::Missing)`.
+///
+/// # Examples
+///
+/// ```
+/// use beekeeper::bee::stock::Caller;
+/// use beekeeper::hive::{Builder, TaskGraph};
+///
+/// # fn main() {
+/// let hive = Builder::new()
+///     .num_threads(4)
+///     .build_with(Caller::of(|i: u32| i * 10))
+///     .unwrap();
+/// let results = TaskGraph::new()
+///     .node("a", |_| 1)
+///     .node("b", |_| 2)
+///     .node("c", |preds| preds.values().copied().sum())
+///     .edge("a", "c")
+///     .edge("b", "c")
+///     .run(&hive)
+///     .unwrap();
+/// let outputs: Vec<_> = results
+///     .into_iter()
+///     .map(|(id, outcome)| (id, outcome.success().unwrap()))
+///     .collect();
+/// assert_eq!(outputs, vec![("a", 10), ("b", 20), ("c", 300)]);
+/// # }
+/// ```
+pub struct TaskGraph<'a, K, W: Worker> {
+    nodes: Vec<Node<'a, K, W>>,
+    edges: Vec<(K, K)>,
+}
+
+impl<'a, K, W> TaskGraph<'a, K, W>
+where
+    K: Clone + Debug + Eq + Hash + Ord,
+    W: Worker,
+{
+    /// Creates a new empty `TaskGraph`.
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    /// Adds a node with the given `id`. When all of the node's predecessors have completed
+    /// successfully, `input_fn` is called with their outputs (keyed by node ID) to create the
+    /// node's input.
+    pub fn node<F>(mut self, id: K, input_fn: F) -> Self
+    where
+        F: FnOnce(&Predecessors<K, W::Output>) -> W::Input + 'a,
+    {
+        self.nodes.push(Node {
+            id,
+            input_fn: Some(Box::new(input_fn)),
+            predecessors: Vec::new(),
+            successors: Vec::new(),
+        });
+        self
+    }
+
+    /// Adds an edge specifying that node `to` depends on node `from`.
+    pub fn edge(mut self, from: K, to: K) -> Self {
+        self.edges.push((from, to));
+        self
+    }
+
+    /// Validates the graph and links the nodes. Returns an error if a node ID is duplicated, an
+    /// edge refers to an unknown node, or the graph has a cycle.
+    fn link(&mut self) -> Result<(), GraphError<K>> {
+        let mut positions = HashMap::with_capacity(self.nodes.len());
+        for (i, node) in self.nodes.iter().enumerate() {
+            if positions.insert(node.id.clone(), i).is_some() {
+                return Err(GraphError::DuplicateNode(node.id.clone()));
+            }
+        }
+        for (from, to) in self.edges.drain(..) {
+            let from = *positions.get(&from).ok_or(GraphError::UnknownNode(from))?;
+            let to = *positions.get(&to).ok_or(GraphError::UnknownNode(to))?;
+            self.nodes[from].successors.push(to);
+            self.nodes[to].predecessors.push(from);
+        }
+        // Kahn's algorithm - any nodes that are never visited are part of (or depend on) a cycle
+        let mut in_degrees: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|node| node.predecessors.len())
+            .collect();
+        let mut ready: VecDeque<_> = (0..self.nodes.len())
+            .filter(|&i| in_degrees[i] == 0)
+            .collect();
+        let mut num_visited = 0;
+        while let Some(i) = ready.pop_front() {
+            num_visited += 1;
+            for &j in &self.nodes[i].successors {
+                in_degrees[j] -= 1;
+                if in_degrees[j] == 0 {
+                    ready.push_back(j);
+                }
+            }
+        }
+        if num_visited < self.nodes.len() {
+            return Err(GraphError::Cycle(self.find_cycle(&in_degrees)));
+        }
+        Ok(())
+    }
+
+    /// Returns the sorted IDs of the nodes on a cycle, given the in-degrees that remain after
+    /// Kahn's algorithm. A node that was not visited has a remaining in-degree > 0, i.e., it has a
+    /// predecessor that was not visited either. So walking backwards along unvisited predecessors
+    /// from any unvisited node eventually revisits a node, and the nodes walked since its first
+    /// visit form a cycle.
+    fn find_cycle(&self, in_degrees: &[usize]) -> Vec<K> {
+        let mut path_positions = vec![None; self.nodes.len()];
+        let mut path = Vec::new();
+        let mut i = in_degrees
+            .iter()
+            .position(|&in_degree| in_degree > 0)
+            .expect("no unvisited nodes");
+        while path_positions[i].is_none() {
+            path_positions[i] = Some(path.len());
+            path.push(i);
+            i = *self.nodes[i]
+                .predecessors
+                .iter()
+                .find(|&&j| in_degrees[j] > 0)
+                .expect("unvisited node has no unvisited predecessor");
+        }
+        let mut cycle: Vec<_> = path[path_positions[i].unwrap()..]
+            .iter()
+            .map(|&j| self.nodes[j].id.clone())
+            .collect();
+        cycle.sort();
+        cycle
+    }
+
+    /// Creates the input for node `i` from the outputs of its predecessors and submits it to
+    /// `hive`. Returns the index of the submitted task.
+    fn submit<Q: Queen<Kind = W>>(
+        &mut self,
+        i: usize,
+        outcomes: &[Option<NodeOutcome<K, W>>],
+        hive: &Hive<W, Q>,
+        tx: &OutcomeSender<W>,
+    ) -> usize {
+        let input_fn = self.nodes[i].input_fn.take().expect("node submitted twice");
+        let predecessors: Predecessors<K, W::Output> = self.nodes[i]
+            .predecessors
+            .iter()
+            .filter_map(|&p| match &outcomes[p] {
+                Some(NodeOutcome::Executed(Outcome::Success { value, .. })) => {
+                    Some((&self.nodes[p].id, value))
+                }
+                _ => None,
+            })
+            .collect();
+        let input = input_fn(&predecessors);
+        hive.apply_send(input, tx.clone())
+    }
+
+    /// Validates this graph and then executes it on `hive`, blocking until every node has either
+    /// been executed or marked as unprocessed. Returns the outcome of each node keyed by node ID.
+    ///
+    /// Returns an error without executing any tasks if a node ID is duplicated, an edge refers to
+    /// an unknown node, or the graph has a cycle.
+    pub fn run<Q: Queen<Kind = W>>(
+        mut self,
+        hive: &Hive<W, Q>,
+    ) -> Result<BTreeMap<K, NodeOutcome<K, W>>, GraphError<K>> {
+        self.link()?;
+        let num_nodes = self.nodes.len();
+        let mut remaining: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|node| node.predecessors.len())
+            .collect();
+        let mut outcomes: Vec<Option<NodeOutcome<K, W>>> =
+            std::iter::repeat_with(|| None).take(num_nodes).collect();
+        let mut task_nodes = HashMap::new();
+        let (tx, rx) = outcome_channel();
+        let roots: Vec<_> = (0..num_nodes).filter(|&i| remaining[i] == 0).collect();
+        let mut num_pending = roots.len();
+        for i in roots {
+            task_nodes.insert(self.submit(i, &outcomes, hive, &tx), i);
+        }
+        let mut num_resolved = 0;
+        while num_pending > 0 {
+            // `tx` is held until the loop ends, so the channel never disconnects - a task that is
+            // lost because its worker panicked is reported by the hive as `Outcome::Missing`
+            let outcome = rx.recv().expect("graph outcome channel disconnected");
+            let i = match task_nodes.remove(outcome.index()) {
+                Some(i) => i,
+                None => continue,
+            };
+            num_pending -= 1;
+            num_resolved += 1;
+            let succeeded = outcome.is_success();
+            outcomes[i] = Some(NodeOutcome::Executed(outcome));
+            if succeeded {
+                for j in self.nodes[i].successors.clone() {
+                    remaining[j] -= 1;
+                    if remaining[j] == 0 && outcomes[j].is_none() {
+                        task_nodes.insert(self.submit(j, &outcomes, hive, &tx), j);
+                        num_pending += 1;
+                    }
+                }
+            } else {
+                // mark every transitive dependent as unprocessed
+                let failed = self.nodes[i].id.clone();
+                let mut dependents: VecDeque<_> = self.nodes[i].successors.iter().collect();
+                while let Some(&j) = dependents.pop_front() {
+                    if outcomes[j].is_none() {
+                        outcomes[j] = Some(NodeOutcome::Unprocessed {
+                            failed: failed.clone(),
+                        });
+                        num_resolved += 1;
+                        dependents.extend(self.nodes[j].successors.iter());
+                    }
+                }
+            }
+        }
+        debug_assert_eq!(num_resolved, num_nodes);
+        Ok(self
+            .nodes
+            .into_iter()
+            .zip(outcomes)
+            .map(|(node, outcome)| (node.id, outcome.expect("node was not resolved")))
+            .collect())
+    }
+}
+
+impl<K, W> Default for TaskGraph<'_, K, W>
+where
+    K: Clone + Debug + Eq + Hash + Ord,
+    W: Worker,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GraphError, NodeOutcome, TaskGraph};
+    use crate::bee::stock::{Caller, OnceCaller, Thunk, ThunkWorker};
+    use crate::hive::{Builder, Outcome};
+
+    #[test]
+    fn test_diamond() {
+        let hive = Builder::new()
+            .num_threads(4)
+            .build_with(Caller::of(|s: String| s))
+            .unwrap();
+        let results = TaskGraph::new()
+            .node(4, |preds| {
+                preds
+                    .values()
+                    .map(|s: &&String| s.as_str())
+                    .collect::<Vec<_>>()
+                    .join("+")
+            })
+            .node(1, |_| "a".to_string())
+            .node(2, |preds| format!("b({})", preds[&1]))
+            .node(3, |preds| format!("c({})", preds[&1]))
+            .edge(1, 2)
+            .edge(1, 3)
+            .edge(2, 4)
+            .edge(3, 4)
+            .run(&hive)
+            .unwrap();
+        assert_eq!(
+            results.keys().copied().collect::<Vec<_>>(),
+            vec![1, 2, 3, 4]
+        );
+        assert!(results.values().all(NodeOutcome::is_success));
+        assert_eq!(
+            results.into_values().last().unwrap().success().unwrap(),
+            "b(a)+c(a)"
+        );
+    }
+
+    #[test]
+    fn test_failure() {
+        let hive = Builder::new()
+            .num_threads(4)
+            .build_with(OnceCaller::of(
+                |i: u32| if i == 0 { Err("zero") } else { Ok(i) },
+            ))
+            .unwrap();
+        let results = TaskGraph::new()
+            .node("a", |_| 1)
+            .node("b", |_| 0)
+            .node("c", |_| 1)
+            .node("d", |_| 1)
+            .node("e", |_| 1)
+            .edge("a", "c")
+            .edge("b", "c")
+            .edge("c", "d")
+            .edge("a", "e")
+            .run(&hive)
+            .unwrap();
+        assert!(results["a"].is_success());
+        assert!(matches!(
+            results["b"],
+            NodeOutcome::Executed(Outcome::Failure { .. })
+        ));
+        assert!(matches!(
+            results["c"],
+            NodeOutcome::Unprocessed { failed: "b" }
+        ));
+        assert!(matches!(
+            results["d"],
+            NodeOutcome::Unprocessed { failed: "b" }
+        ));
+        assert!(results["e"].is_success());
+    }
+
+    #[test]
+    fn test_panic() {
+        let hive = Builder::new()
+            .num_threads(2)
+            .build_with_default::<ThunkWorker<u32>>()
+            .unwrap();
+        let results = TaskGraph::new()
+            .node("a", |_| Thunk::of(|| 1))
+            // `ThunkWorker` does not catch panics, so the outcome is lost
+            .node("b", |_| Thunk::of(|| panic!("oh no!")))
+            .node("c", |_| Thunk::of(|| 1))
+            .node("d", |_| Thunk::of(|| 1))
+            .edge("a", "b")
+            .edge("b", "c")
+            .edge("a", "d")
+            .run(&hive)
+            .unwrap();
+        assert!(results["a"].is_success());
+        assert!(matches!(
+            results["b"],
+            NodeOutcome::Executed(Outcome::Missing { .. })
+        ));
+        assert!(matches!(
+            results["c"],
+            NodeOutcome::Unprocessed { failed: "b" }
+        ));
+        assert!(results["d"].is_success());
+    }
+
+    #[test]
+    fn test_errors() {
+        let hive = Builder::new()
+            .num_threads(1)
+            .build_with(Caller::of(|i: u32| i))
+            .unwrap();
+        let result = TaskGraph::new()
+            .node("a", |_| 1)
+            .node("a", |_| 2)
+            .run(&hive);
+        assert_eq!(result.unwrap_err(), GraphError::DuplicateNode("a"));
+        let result = TaskGraph::new().node("a", |_| 1).edge("a", "b").run(&hive);
+        assert_eq!(result.unwrap_err(), GraphError::UnknownNode("b"));
+        let result = TaskGraph::new()
+            .node("a", |_| 1)
+            .node("b", |_| 1)
+            .node("c", |_| 1)
+            .node("d", |_| 1)
+            .edge("a", "b")
+            .edge("b", "c")
+            .edge("c", "b")
+            .edge("c", "d")
+            .run(&hive);
+        // "d" depends on the cycle but is not part of it
+        assert_eq!(result.unwrap_err(), GraphError::Cycle(vec!["b", "c"]));
+        let result = TaskGraph::new()
+            .node("d", |_| 1)
+            .node("c", |_| 1)
+            .node("b", |_| 1)
+            .node("a", |_| 1)
+            .edge("a", "b")
+            .edge("b", "c")
+            .edge("c", "a")
+            .edge("c", "d")
+            .run(&hive);
+        assert_eq!(result.unwrap_err(), GraphError::Cycle(vec!["a", "b", "c"]));
+        // nothing was submitted
+        assert_eq!(hive.num_tasks(), (0, 0));
+    }
+}
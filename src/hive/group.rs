@@ -0,0 +1,202 @@
+//! Named task groups, each of which may have a limit on the number of its tasks that are active
+//! at the same time.
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+/// A snapshot of the state of a task group.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TaskGroupStats {
+    /// The maximum number of tasks in the group that may be active at the same time, or `None` if
+    /// the group is unlimited.
+    pub max_concurrency: Option<usize>,
+    /// The number of tasks in the group that are currently active.
+    pub active: usize,
+    /// The number of tasks in the group that were skipped by a worker thread because the group
+    /// was saturated, and which are waiting to be started.
+    pub deferred: usize,
+    /// The number of tasks in the group that have completed.
+    pub completed: u64,
+}
+
+#[derive(Debug)]
+struct TaskGroup<T> {
+    max_concurrency: Option<usize>,
+    active: usize,
+    deferred: VecDeque<T>,
+    completed: u64,
+}
+
+impl<T> TaskGroup<T> {
+    fn new(max_concurrency: Option<usize>) -> Self {
+        Self {
+            max_concurrency,
+            active: 0,
+            deferred: VecDeque::new(),
+            completed: 0,
+        }
+    }
+
+    fn is_saturated(&self) -> bool {
+        self.max_concurrency
+            .is_some_and(|max_concurrency| self.active >= max_concurrency)
+    }
+}
+
+/// The task groups of a `Hive`. A group is created when a limit is set for it, or when the first
+/// task is submitted to it (in which case it is unlimited).
+#[derive(Debug)]
+pub struct TaskGroups<T>(HashMap<Arc<str>, TaskGroup<T>>);
+
+impl<T> TaskGroups<T> {
+    /// Creates a new `TaskGroups` with the given concurrency limits.
+    pub fn new(limits: HashMap<String, usize>) -> Self {
+        Self(
+            limits
+                .into_iter()
+                .map(|(name, limit)| (name.into(), TaskGroup::new(Some(limit))))
+                .collect(),
+        )
+    }
+
+    /// Sets the maximum concurrency of the `group`.
+    pub fn set_limit(&mut self, group: &str, max_concurrency: Option<usize>) {
+        match self.0.get_mut(group) {
+            Some(task_group) => task_group.max_concurrency = max_concurrency,
+            None => {
+                self.0.insert(group.into(), TaskGroup::new(max_concurrency));
+            }
+        }
+    }
+
+    /// Returns the shared name of `group`, creating an unlimited group if it doesn't exist.
+    pub fn intern(&mut self, group: &str) -> Arc<str> {
+        if let Some((name, _)) = self.0.get_key_value(group) {
+            Arc::clone(name)
+        } else {
+            let name: Arc<str> = group.into();
+            self.0.insert(Arc::clone(&name), TaskGroup::new(None));
+            name
+        }
+    }
+
+    /// Attempts to start `item` in `group`. If the group is saturated, `item` is deferred and
+    /// `None` is returned, otherwise the group's active count is incremented and `item` is
+    /// returned.
+    pub fn try_start(&mut self, group: &str, item: T) -> Option<T> {
+        if !self.0.contains_key(group) {
+            self.0.insert(group.into(), TaskGroup::new(None));
+        }
+        let task_group = self.0.get_mut(group).unwrap();
+        if task_group.is_saturated() {
+            task_group.deferred.push_back(item);
+            None
+        } else {
+            task_group.active += 1;
+            Some(item)
+        }
+    }
+
+    /// Removes and returns a deferred item from a group that is not saturated, if there is one.
+    /// The group's active count is incremented.
+    pub fn next_deferred(&mut self) -> Option<T> {
+        self.0
+            .values_mut()
+            .filter(|task_group| !task_group.is_saturated())
+            .find_map(|task_group| {
+                let item = task_group.deferred.pop_front();
+                if item.is_some() {
+                    task_group.active += 1;
+                }
+                item
+            })
+    }
+
+    /// Returns `true` if there are any deferred items.
+    pub fn has_deferred(&self) -> bool {
+        self.0
+            .values()
+            .any(|task_group| !task_group.deferred.is_empty())
+    }
+
+    /// Returns `true` if there are any deferred items in a group that is not saturated.
+    pub fn has_startable_deferred(&self) -> bool {
+        self.0
+            .values()
+            .any(|task_group| !task_group.deferred.is_empty() && !task_group.is_saturated())
+    }
+
+    /// Called when an item in `group` completes.
+    pub fn finish(&mut self, group: &str) {
+        if let Some(task_group) = self.0.get_mut(group) {
+            task_group.active = task_group.active.saturating_sub(1);
+            task_group.completed += 1;
+        }
+    }
+
+    /// Removes and returns all deferred items.
+    pub fn drain_deferred(&mut self) -> impl Iterator<Item = T> + '_ {
+        self.0
+            .values_mut()
+            .flat_map(|task_group| task_group.deferred.drain(..))
+    }
+
+    /// Returns a snapshot of the state of each group.
+    pub fn stats(&self) -> HashMap<String, TaskGroupStats> {
+        self.0
+            .iter()
+            .map(|(name, task_group)| {
+                let stats = TaskGroupStats {
+                    max_concurrency: task_group.max_concurrency,
+                    active: task_group.active,
+                    deferred: task_group.deferred.len(),
+                    completed: task_group.completed,
+                };
+                (name.to_string(), stats)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TaskGroupStats, TaskGroups};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_groups() {
+        let mut groups = TaskGroups::new(HashMap::from([("db".to_string(), 2)]));
+        assert_eq!(groups.try_start("db", 0), Some(0));
+        assert_eq!(groups.try_start("db", 1), Some(1));
+        assert_eq!(groups.try_start("db", 2), None);
+        assert_eq!(groups.try_start("cpu", 3), Some(3));
+        assert!(groups.has_deferred());
+        assert!(!groups.has_startable_deferred());
+        assert_eq!(groups.next_deferred(), None);
+        groups.finish("db");
+        assert!(groups.has_startable_deferred());
+        assert_eq!(groups.next_deferred(), Some(2));
+        assert!(!groups.has_deferred());
+        let stats = groups.stats();
+        assert_eq!(
+            stats["db"],
+            TaskGroupStats {
+                max_concurrency: Some(2),
+                active: 2,
+                deferred: 0,
+                completed: 1
+            }
+        );
+        assert_eq!(stats["cpu"].max_concurrency, None);
+        assert_eq!(stats["cpu"].active, 1);
+    }
+
+    #[test]
+    fn test_drain() {
+        let mut groups = TaskGroups::new(HashMap::from([("db".to_string(), 1)]));
+        assert_eq!(groups.try_start("db", 0), Some(0));
+        assert_eq!(groups.try_start("db", 1), None);
+        assert_eq!(groups.try_start("db", 2), None);
+        assert_eq!(groups.drain_deferred().collect::<Vec<_>>(), vec![1, 2]);
+        assert!(!groups.has_deferred());
+    }
+}
@@ -27,18 +27,25 @@
 //   - There is also `InfallibleFunc<I, O>`, which wraps a function pointer `fn(I) -> O`.
 // - `Identity<T>`, which simply returns the input value.
 
+use super::stored::StoredIter;
+use super::stream::Streaming;
 use super::{
     outcome_channel, Config, DerefOutcomes, Hive, HiveInner, Husk, Outcome, OutcomeBatch,
-    OutcomeIteratorExt, OutcomeSender, OutcomeStore, Shared, TaskSender,
+    OutcomeIteratorExt, OutcomeSender, OutcomeStore, PanicPolicy, Shared, Task, TaskGroupStats,
+    TaskProgress, TaskSender,
 };
 use crate::atomic::Atomic;
-use crate::bee::{Queen, Worker};
+use crate::bee::{ApplyError, Context, Queen, Worker, WorkerResult};
+use crate::channel::SenderExt;
+use crate::panic::Panic;
 use crossbeam_utils::Backoff;
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::hash::{BuildHasher, Hash, RandomState};
 use std::ops::{Deref, DerefMut, Range};
 use std::sync::{mpsc, Arc};
 use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 #[derive(thiserror::Error, Debug)]
 pub enum SpawnError {
@@ -48,6 +55,29 @@ pub enum SpawnError {
     Poisoned,
 }
 
+/// Specifies which indices are assigned to tasks that are resubmitted from stored outcomes (e.g.,
+/// by `Hive::resubmit_failures_with`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResubmitMode {
+    /// Each resubmitted task is assigned a new index, as if it were submitted for the first time.
+    #[default]
+    NewIndices,
+    /// Each resubmitted task keeps the index of the outcome from which it was resubmitted, so the
+    /// new outcome can be correlated with the original task.
+    OriginalIndices,
+}
+
+/// Specifies how `Hive::shutdown` handles tasks that are queued or active.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShutdownMode {
+    /// Wait for all queued and active tasks to complete.
+    Drain,
+    /// Wait up to the given duration for queued and active tasks to complete, then `Abort`.
+    DrainWithin(Duration),
+    /// Signal cancellation to active tasks and convert all queued tasks to `Outcome::Unprocessed`.
+    Abort,
+}
+
 impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
     /// Spawns a new worker thread.
     fn spawn(index: usize, shared: Arc<Shared<W, Q>>) -> Result<JoinHandle<()>, SpawnError> {
@@ -59,18 +89,40 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
         shared
             .thread_builder()
             .spawn(move || {
+                crate::panic::set_thread_index(index);
                 Self::init_thread(index, &shared);
                 // Will spawn a new thread on panic until it is cancelled
                 let sentinel = Sentinel::new(index, Arc::clone(&shared));
                 let mut worker = shared.create_worker();
                 // Get the next task - increments the counter
-                while let Ok(task) = shared.next_task() {
+                while let Ok(mut task) = shared.next_task() {
+                    if shared.batch_size() > 1 {
+                        // Collects additional tasks and executes them together
+                        let batch = shared.next_batch(task);
+                        let panicked = Self::execute_batch(batch, &mut worker, &shared, index);
+                        if panicked && shared.panic_policy() == PanicPolicy::Stop {
+                            break;
+                        }
+                        continue;
+                    }
+                    // Releases the task's slot in its group (if any) when dropped, including if
+                    // the worker panics
+                    let group_guard = shared.group_guard(&task);
+                    // Tracks the task's progress until it is dropped
+                    let progress_guard = shared.track_progress(&mut task, index);
                     // Execute the task until it succeeds or we reach maximum retries - this
                     // should be the only place where a panic might occur
-                    Self::execute(task, &mut worker, &shared);
+                    let panicked = Self::execute(task, &mut worker, &shared);
+                    drop(progress_guard);
+                    drop(group_guard);
                     // Finish the task - decrements the counter and notifies other threads
                     //dbg!("Finish task in worker thread: {}", index);
                     shared.finish_task(false);
+                    // The panic was caught, so the thread survives unless the policy says
+                    // otherwise
+                    if panicked && shared.panic_policy() == PanicPolicy::Stop {
+                        break;
+                    }
                 }
                 // Cancel the sentinel if the receiver hung up, thus avoiding the thread
                 // being restarted when it is dropped
@@ -79,8 +131,123 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
             .map_err(SpawnError::Spawn)
     }
 
+    /// Calls `worker.apply` with the given `input` and `ctx`. If the panic policy is
+    /// `PanicPolicy::Propagate`, a panic is caught and converted into an `ApplyError::Panic`.
+    #[inline]
+    fn apply_worker(
+        worker: &mut W,
+        input: W::Input,
+        ctx: &Context,
+        shared: &Shared<W, Q>,
+    ) -> WorkerResult<W> {
+        if shared.panic_policy() == PanicPolicy::Propagate {
+            Panic::try_call(None, || worker.apply(input, ctx)).unwrap_or_else(|payload| {
+                Err(ApplyError::Panic {
+                    input: None,
+                    payload,
+                })
+            })
+        } else {
+            worker.apply(input, ctx)
+        }
+    }
+
+    /// Executes the task and either queues it for retry (if the `retry` feature is enabled) or
+    /// sends or stores the outcome. Returns `true` if the `Worker` panicked (and the panic was
+    /// caught).
+    #[inline]
+    fn execute(task: Task<W>, worker: &mut W, shared: &Shared<W, Q>) -> bool {
+        let (input, ctx, outcome_tx) = task.into_parts();
+        let outcome_guard = OutcomeGuard::new(ctx.index(), outcome_tx);
+        let result = Self::apply_worker(worker, input, &ctx, shared);
+        Self::handle_result(result, ctx, outcome_guard.disarm(), shared)
+    }
+
+    /// Executes a batch of tasks with a single call to `worker.apply_batch` and handles the
+    /// result of each task as in `execute`, then finishes all the tasks in the batch. A task
+    /// without a result is given an `Outcome::Missing`. Returns `true` if the `Worker` panicked
+    /// (and the panic was caught).
+    fn execute_batch(
+        mut batch: Vec<Task<W>>,
+        worker: &mut W,
+        shared: &Shared<W, Q>,
+        thread_index: usize,
+    ) -> bool {
+        // Finishes the rest of the batch if the worker panics - the `Sentinel` finishes one task
+        let batch_guard = BatchGuard {
+            shared,
+            size: batch.len(),
+        };
+        let group_guards: Vec<_> = batch.iter().map(|task| shared.group_guard(task)).collect();
+        let progress_guards: Vec<_> = batch
+            .iter_mut()
+            .map(|task| shared.track_progress(task, thread_index))
+            .collect();
+        let mut inputs = Vec::with_capacity(batch.len());
+        let mut ctxs = Vec::with_capacity(batch.len());
+        let mut outcome_guards = Vec::with_capacity(batch.len());
+        for task in batch {
+            let (input, ctx, outcome_tx) = task.into_parts();
+            inputs.push(input);
+            outcome_guards.push(OutcomeGuard::new(ctx.index(), outcome_tx));
+            ctxs.push(ctx);
+        }
+        let results = if shared.panic_policy() == PanicPolicy::Propagate {
+            // every task gets the panic: the first task gets the payload, and the other tasks get
+            // copies of the panic message
+            Panic::try_call(None, || worker.apply_batch(inputs, &ctxs)).unwrap_or_else(|payload| {
+                let copies: Vec<_> = (1..ctxs.len()).map(|_| payload.duplicate()).collect();
+                std::iter::once(payload)
+                    .chain(copies)
+                    .map(|payload| {
+                        Err(ApplyError::Panic {
+                            input: None,
+                            payload,
+                        })
+                    })
+                    .collect()
+            })
+        } else {
+            worker.apply_batch(inputs, &ctxs)
+        };
+        let mut results = results.into_iter();
+        let mut panicked = false;
+        for (ctx, outcome_guard) in ctxs.into_iter().zip(outcome_guards) {
+            let outcome_tx = outcome_guard.disarm();
+            match results.next() {
+                Some(result) => panicked |= Self::handle_result(result, ctx, outcome_tx, shared),
+                None => {
+                    let outcome = Outcome::Missing { index: ctx.index() };
+                    shared.send_or_store_outcome(outcome, outcome_tx);
+                }
+            }
+        }
+        drop(progress_guards);
+        drop(group_guards);
+        for _ in 0..batch_guard.size {
+            shared.finish_task(false);
+        }
+        batch_guard.disarm();
+        panicked
+    }
+
+    /// Returns a function that resumes unwinding if its argument is an `Outcome::Panic` and the
+    /// panic policy is `PanicPolicy::Propagate`, otherwise returns its argument.
+    fn propagate_panic(&self) -> impl Fn(Outcome<W>) -> Outcome<W> {
+        let propagate = self.shared().panic_policy() == PanicPolicy::Propagate;
+        move |outcome| match outcome {
+            Outcome::Panic { payload, .. } if propagate => payload.resume(),
+            outcome => outcome,
+        }
+    }
+
     pub(super) fn new(config: Config, queen: Q) -> Result<Self, SpawnError> {
-        let num_threads = config.num_threads.get().unwrap_or(0);
+        // a deterministic hive executes tasks on the submitting thread
+        let num_threads = if config.deterministic.get_or_default() {
+            0
+        } else {
+            config.num_threads.get().unwrap_or(0)
+        };
         let (task_tx, task_rx) = mpsc::channel();
         let shared = Arc::new(Shared::new(config.into_sync(), queen, task_rx));
         let hive = Self(Some(HiveInner { task_tx, shared }));
@@ -102,7 +269,7 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
     }
 
     #[inline]
-    fn shared(&self) -> &Arc<Shared<W, Q>> {
+    pub(super) fn shared(&self) -> &Arc<Shared<W, Q>> {
         &self.0.as_ref().unwrap().shared
     }
 
@@ -148,40 +315,130 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
             .collect::<Vec<_>>()
     }
 
+    /// Called after tasks are submitted to this `Hive`. Notifies the `Apiary` with which this
+    /// `Hive` is registered (if any), and executes the tasks if this `Hive` is deterministic.
+    fn tasks_available(&self) {
+        self.shared().wake_apiary();
+        self.run_inline();
+    }
+
+    /// If this `Hive` is deterministic, executes queued tasks on the current thread until there
+    /// are no more tasks available (or the `Hive` is suspended). Does nothing if another thread is
+    /// already executing tasks, since that thread will also execute any tasks queued by this
+    /// thread.
+    fn run_inline(&self) {
+        let shared = self.shared();
+        if !shared.is_deterministic() {
+            return;
+        }
+        while let Some(lock) = shared.try_lock_inline() {
+            let mut worker = shared.create_worker();
+            while let Some(task) = shared.try_next_task() {
+                Self::execute_inline(task, &mut worker, shared, 0);
+            }
+            drop(lock);
+            // tasks may have been queued by another thread after we stopped looking for tasks
+            if !shared.has_inline_pending() {
+                break;
+            }
+        }
+    }
+
+    /// Executes the next available task of the `Hive` with the given shared data on the current
+    /// thread, first creating the `Worker` if `worker` is `None`. Returns `None` if there is no
+    /// task available, otherwise whether the `Worker` panicked. This is used by the threads of an
+    /// `Apiary`.
+    pub(super) fn execute_next(
+        shared: &Shared<W, Q>,
+        worker: &mut Option<W>,
+        thread_index: usize,
+    ) -> Option<bool> {
+        let task = shared.next_task_until(shared.now())?;
+        let worker = worker.get_or_insert_with(|| shared.create_worker());
+        Some(Self::execute_inline(task, worker, shared, thread_index))
+    }
+
+    /// Executes `task` on the current thread rather than on one of this `Hive`'s worker threads,
+    /// then finishes the task. An uncaught panic is handled as if the worker thread had been
+    /// respawned, i.e., `worker` is replaced with a new `Worker`. Returns `true` if the `Worker`
+    /// panicked (whether or not the panic was caught).
+    fn execute_inline(
+        mut task: Task<W>,
+        worker: &mut W,
+        shared: &Shared<W, Q>,
+        thread_index: usize,
+    ) -> bool {
+        let group_guard = shared.group_guard(&task);
+        let progress_guard = shared.track_progress(&mut task, thread_index);
+        let result = Panic::<String>::try_call(None, || Self::execute(task, worker, shared));
+        drop(progress_guard);
+        drop(group_guard);
+        let uncaught = result.is_err();
+        if uncaught {
+            *worker = shared.create_worker();
+        }
+        shared.finish_task(uncaught);
+        result.unwrap_or(true)
+    }
+
     /// Sends one input to the `Hive` for processing and returns its index. The `Outcome`
     /// of the task is sent to the `outcome_tx` channel if provided, otherwise it is retained in
     /// the `Hive` for later retrieval.
     ///
     /// This method is called by all the `*apply*` methods.
     fn send_one(&self, input: W::Input, outcome_tx: Option<OutcomeSender<W>>) -> usize {
+        self.send_one_in_group(input, None, outcome_tx)
+    }
+
+    /// Sends one input to the `Hive` for processing as part of the task `group` (if any) and
+    /// returns its index.
+    fn send_one_in_group(
+        &self,
+        input: W::Input,
+        group: Option<&str>,
+        outcome_tx: Option<OutcomeSender<W>>,
+    ) -> usize {
         #[cfg(debug_assertions)]
-        if self.num_threads() == 0 {
+        if self.num_threads() == 0
+            && !self.shared().is_deterministic()
+            && !self.shared().has_apiary()
+        {
             dbg!("WARNING: no worker threads are active for hive");
         }
-        let task = self.shared().prepare_task(input, outcome_tx);
+        let mut task = self.shared().prepare_task(input, outcome_tx);
+        if let Some(group) = group {
+            task.ctx.set_group(self.shared().intern_group(group));
+        }
+        self.send_task(task)
+    }
+
+    /// Sends a prepared `task` to the `Hive` for processing and returns its index.
+    fn send_task(&self, task: Task<W>) -> usize {
         let index = task.index();
-        if !self.is_poisoned() {
+        if self.shared().is_accepting() {
             self.task_tx()
                 .send(task)
                 .expect("unable to send task into queue");
-        } else if let Some(outcome) = task.into_unprocessed_try_send() {
-            self.shared().add_outcome(outcome);
+        } else {
+            self.shared().reject_tasks(std::iter::once(task));
         }
+        self.tasks_available();
         index
     }
 
     /// Sends one `input` to the `Hive` for procesing and returns the result, blocking until the
     /// result is available.
     ///
-    /// Returns an error with the task index if the `Hive` is poisoned or is dropped before the
-    /// task finishes processing - it may still be possible to retrieve the unprocessed input.
+    /// Returns an error with the task index if the `Hive` is poisoned, closed, or is dropped before
+    /// the task finishes processing - it may still be possible to retrieve the unprocessed input.
     ///
     /// Creates a channel to send the input and receive the outcome. Panics if the channel hangs
     /// up before the outcome is received.
     pub fn apply(&self, input: W::Input) -> Outcome<W> {
         let (tx, rx) = outcome_channel();
         let index = self.send_one(input, Some(tx));
-        rx.recv().unwrap_or_else(|_| Outcome::Missing { index })
+        let outcome = rx.recv().unwrap_or_else(|_| Outcome::Missing { index });
+        self.propagate_panic()(outcome)
     }
 
     /// Sends one `input` to the `Hive` for processing and returns its index. The `Outcome` of the
@@ -196,6 +453,55 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
         self.send_one(input, None)
     }
 
+    /// Sends one `input` to the `Hive` for processing as part of the named task `group`, and
+    /// returns the result, blocking until the result is available.
+    ///
+    /// A group may have a maximum number of tasks that can be active at the same time (see
+    /// [`Builder::task_group`]). When a worker thread receives a task whose group is saturated,
+    /// it sets the task aside and moves on to the next task rather than blocking; the deferred
+    /// task is started once a task in the same group completes. A group without a limit is
+    /// created the first time a task is submitted to it.
+    ///
+    /// [`Builder::task_group`]: crate::hive::Builder::task_group
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use beekeeper::bee::stock::Caller;
+    /// use beekeeper::hive::Builder;
+    ///
+    /// # fn main() {
+    /// let hive = Builder::new()
+    ///     .num_threads(4)
+    ///     .task_group("db", 1)
+    ///     .build_with(Caller::of(|i: usize| i * 2))
+    ///     .unwrap();
+    /// assert_eq!(hive.apply_in_group("db", 21).unwrap(), 42);
+    /// // the outcome is sent before the task is marked as completed
+    /// hive.join();
+    /// assert_eq!(hive.group_stats()["db"].completed, 1);
+    /// # }
+    /// ```
+    pub fn apply_in_group(&self, group: &str, input: W::Input) -> Outcome<W> {
+        let (tx, rx) = outcome_channel();
+        let index = self.send_one_in_group(input, Some(group), Some(tx));
+        let outcome = rx.recv().unwrap_or_else(|_| Outcome::Missing { index });
+        self.propagate_panic()(outcome)
+    }
+
+    /// Sends one `input` to the `Hive` for processing as part of the named task `group`, and
+    /// returns its index. The `Outcome` of the task will be sent to `tx` upon completion.
+    pub fn apply_send_in_group(&self, group: &str, input: W::Input, tx: OutcomeSender<W>) -> usize {
+        self.send_one_in_group(input, Some(group), Some(tx))
+    }
+
+    /// Sends one `input` to the `Hive` for processing as part of the named task `group`, and
+    /// returns its index immediately. The `Outcome` of the task will be retained and available
+    /// for later retrieval.
+    pub fn apply_store_in_group(&self, group: &str, input: W::Input) -> usize {
+        self.send_one_in_group(input, Some(group), None)
+    }
+
     /// Sends a `batch` of inputs to the `Hive` for processing, and returns a `Vec` of their
     /// indices. The `Outcome`s of the tasks are sent to the `outcome_tx` channel if provided,
     /// otherwise they are retained in the `Hive` for later retrieval.
@@ -210,14 +516,14 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
         T::IntoIter: ExactSizeIterator,
     {
         #[cfg(debug_assertions)]
-        if self.num_threads() == 0 {
+        if self.num_threads() == 0 && !self.shared().is_deterministic() {
             dbg!("WARNING: no worker threads are active for hive");
         }
         let task_tx = self.task_tx();
         let iter = batch.into_iter();
         let (batch_size, _) = iter.size_hint();
         let batch = self.shared().prepare_batch(batch_size, iter, outcome_tx);
-        if !self.is_poisoned() {
+        let indices = if self.shared().is_accepting() {
             batch
                 .map(|task| {
                     let index = task.index();
@@ -226,8 +532,10 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
                 })
                 .collect()
         } else {
-            self.shared().send_or_store_as_unprocessed(batch)
-        }
+            self.shared().reject_tasks(batch)
+        };
+        self.tasks_available();
+        indices
     }
 
     /// Sends a `batch` of inputs to the `Hive` for processing, and returns an iterator over the
@@ -244,7 +552,7 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
     {
         let (tx, rx) = outcome_channel();
         let indices = self.send_batch(batch, Some(tx));
-        rx.take_ordered(indices)
+        rx.take_ordered(indices).map(self.propagate_panic())
     }
 
     /// Sends a `batch` of inputs to the `Hive` for processing, and returns an iterator over the
@@ -263,7 +571,7 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
     {
         let (tx, rx) = outcome_channel();
         let num_tasks = self.send_batch(batch, Some(tx)).len();
-        rx.into_iter().take(num_tasks)
+        rx.into_iter().take(num_tasks).map(self.propagate_panic())
     }
 
     /// Sends a `batch`` of inputs to the `Hive` for processing, and returns a range of indices.
@@ -306,7 +614,7 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
             .into_iter()
             .map(|task| self.apply_send(task, tx.clone()))
             .collect();
-        rx.take_ordered(indices)
+        rx.take_ordered(indices).map(self.propagate_panic())
     }
 
     /// Iterates over `inputs`, sends each one to the `Hive` for processing, and returns an
@@ -327,7 +635,65 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
             .into_iter()
             .map(|task| self.apply_send(task, tx.clone()))
             .count();
-        rx.into_iter().take(num_tasks)
+        rx.into_iter().take(num_tasks).map(self.propagate_panic())
+    }
+
+    /// Lazily pulls inputs from `inputs`, sends each one to the `Hive` for processing, and returns
+    /// an iterator over the `Outcome`s in the same order as the inputs.
+    ///
+    /// Unlike `map`, inputs are only pulled as the returned iterator is advanced, and at most
+    /// `window` tasks are in flight (i.e., submitted, but their outcomes not yet yielded) at any
+    /// time. This means that `inputs` may be arbitrarily large, or even infinite. If the outcome
+    /// of a task is lost (e.g., because the `Worker` panicked), `Outcome::Missing` is yielded in
+    /// its place.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is `0`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use beekeeper::bee::stock::Caller;
+    /// use beekeeper::hive::{Builder, Outcome};
+    ///
+    /// # fn main() {
+    /// let hive = Builder::new()
+    ///     .num_threads(4)
+    ///     .build_with(Caller::of(|i: u64| i * i))
+    ///     .unwrap();
+    /// // squares all the natural numbers, but only ten at a time
+    /// let squares = hive.map_streaming(0.., 10).map(Outcome::unwrap);
+    /// assert_eq!(squares.take(4).collect::<Vec<_>>(), vec![0, 1, 4, 9]);
+    /// # }
+    /// ```
+    pub fn map_streaming<'a, I>(
+        &'a self,
+        inputs: I,
+        window: usize,
+    ) -> impl Iterator<Item = Outcome<W>> + 'a
+    where
+        I: IntoIterator<Item = W::Input>,
+        I::IntoIter: 'a,
+    {
+        Streaming::new(self, inputs.into_iter(), window, true).map(self.propagate_panic())
+    }
+
+    /// Lazily pulls inputs from `inputs`, sends each one to the `Hive` for processing, and returns
+    /// an iterator over the `Outcome`s in the order they become available. At most `window` tasks
+    /// are in flight at any time.
+    ///
+    /// See [`map_streaming`](Self::map_streaming) for details.
+    pub fn map_streaming_unordered<'a, I>(
+        &'a self,
+        inputs: I,
+        window: usize,
+    ) -> impl Iterator<Item = Outcome<W>> + 'a
+    where
+        I: IntoIterator<Item = W::Input>,
+        I::IntoIter: 'a,
+    {
+        Streaming::new(self, inputs.into_iter(), window, false).map(self.propagate_panic())
     }
 
     /// Iterates over `inputs` and sends each one to the `Hive` for processing. Returns a `Vec` of
@@ -373,7 +739,7 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
     {
         let (tx, rx) = outcome_channel();
         let (indices, fold_value) = self.scan_send(items, tx, init, f);
-        let outcomes = rx.into_iter().take(indices.len()).map(Outcome::into).into();
+        let outcomes = rx.into_iter().take(indices.len()).into();
         (outcomes, fold_value)
     }
 
@@ -395,7 +761,7 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
     {
         let (tx, rx) = outcome_channel();
         let (indices, fold_value) = self.try_scan_send(items, tx, init, f)?;
-        let outcomes = rx.into_iter().take(indices.len()).map(Outcome::into).into();
+        let outcomes = rx.into_iter().take(indices.len()).into();
         Ok((outcomes, fold_value))
     }
 
@@ -491,6 +857,141 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
             })
     }
 
+    /// Iterates over `inputs`, sends each one to the `Hive` for processing, and reduces the
+    /// outputs of all successful tasks to a single value using `reduce`, starting from the value
+    /// returned by `identity`.
+    ///
+    /// Outputs are reduced on the calling thread in the order they become available, so the
+    /// result should not depend on the order in which values are combined.
+    ///
+    /// This function returns the reduced value and an `OutcomeBatch` of the tasks that did not
+    /// succeed. `Outcome`s of successful tasks are not retained.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use beekeeper::bee::stock::Caller;
+    /// use beekeeper::hive::prelude::*;
+    ///
+    /// # fn main() {
+    /// let hive = Builder::new()
+    ///     .num_threads(4)
+    ///     .build_with(Caller::of(|i: u64| i * i))
+    ///     .unwrap();
+    /// let (sum, failures) = hive.map_reduce(1..=10, || 0, |a, b| a + b);
+    /// assert_eq!(sum, 385);
+    /// assert!(failures.is_empty());
+    /// # }
+    /// ```
+    pub fn map_reduce<I, R>(
+        &self,
+        inputs: impl IntoIterator<Item = W::Input>,
+        identity: I,
+        reduce: R,
+    ) -> (W::Output, OutcomeBatch<W>)
+    where
+        I: FnOnce() -> W::Output,
+        R: Fn(W::Output, W::Output) -> W::Output,
+    {
+        self.fold_outcomes(inputs, identity(), reduce)
+    }
+
+    /// Iterates over `inputs`, sends each one to the `Hive` for processing, and folds the outputs
+    /// of successful tasks into `init` on the calling thread as they become available. Returns
+    /// the folded value and an `OutcomeBatch` of the unsuccessful tasks.
+    pub(crate) fn fold_outcomes<A, F>(
+        &self,
+        inputs: impl IntoIterator<Item = W::Input>,
+        init: A,
+        mut fold: F,
+    ) -> (A, OutcomeBatch<W>)
+    where
+        F: FnMut(A, W::Output) -> A,
+    {
+        let (tx, rx) = outcome_channel();
+        for input in inputs {
+            self.apply_send(input, tx.clone());
+        }
+        drop(tx);
+        // the channel disconnects once the last task has been completed
+        let propagate = self.propagate_panic();
+        let (value, failures) = rx.into_iter().fold(
+            (init, Vec::new()),
+            |(acc, mut failures), outcome| match outcome {
+                Outcome::Success { value, .. } => (fold(acc, value), failures),
+                outcome => {
+                    failures.push(propagate(outcome));
+                    (acc, failures)
+                }
+            },
+        );
+        (value, failures.into())
+    }
+
+    /// Sets the function used to determine the rate-limit key of each task from its input. Tasks
+    /// that share a key are subject to the limit set with [`Builder::key_rate_limit`]. If `f`
+    /// returns `None`, the task is only subject to the global rate limit (if any). Has no effect
+    /// if the `Hive` was not built with a key rate limit.
+    ///
+    /// The key function is preserved when the `Hive` is converted into a `Husk`, and it is set on
+    /// a `Hive` created from the `Husk` (but not on one built from [`Husk::as_builder`]).
+    ///
+    /// [`Builder::key_rate_limit`]: crate::hive::Builder::key_rate_limit
+    pub fn set_rate_limit_key<K, F>(&self, f: F)
+    where
+        K: Hash,
+        F: Fn(&W::Input) -> Option<K> + Send + Sync + 'static,
+    {
+        let hasher = RandomState::new();
+        self.shared().set_rate_limit_key(Box::new(move |input| {
+            f(input).map(|key| hasher.hash_one(key))
+        }));
+    }
+
+    /// Sets the maximum number of tasks in the named task `group` that may be active at the same
+    /// time. If `max_concurrency` is `None`, the group is unlimited.
+    pub fn set_group_max_concurrency(&self, group: &str, max_concurrency: Option<usize>) {
+        self.shared().set_group_limit(group, max_concurrency);
+    }
+
+    /// Sets the channel to which stored outcomes are sent when they are evicted because the
+    /// maximum number of stored outcomes has been reached. Has no effect unless the `Hive` was
+    /// built with [`StoreLimitPolicy::Overflow`].
+    ///
+    /// The overflow channel is not preserved when the `Hive` is converted into a `Husk`.
+    ///
+    /// [`StoreLimitPolicy::Overflow`]: crate::hive::StoreLimitPolicy::Overflow
+    pub fn set_store_overflow(&self, tx: OutcomeSender<W>) {
+        self.shared().set_store_overflow(tx);
+    }
+
+    /// Sets a function that is called when the maximum number of stored outcomes (set with
+    /// [`Builder::max_stored_outcomes`]) is reached. The function is called with the maximum
+    /// number of stored outcomes from the worker thread that reached the limit. It is called
+    /// again only after the number of stored outcomes has dropped below the limit and then
+    /// reached it again.
+    ///
+    /// The handler is not preserved when the `Hive` is converted into a `Husk`.
+    ///
+    /// [`Builder::max_stored_outcomes`]: crate::hive::Builder::max_stored_outcomes
+    pub fn set_store_limit_handler<F>(&self, f: F)
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        self.shared().set_store_limit_handler(Box::new(f));
+    }
+
+    /// Returns the number of outcomes that have been dropped (i.e., not stored, and not sent to
+    /// an overflow channel) because the maximum number of stored outcomes was reached.
+    pub fn num_dropped_outcomes(&self) -> usize {
+        self.shared().num_dropped_outcomes()
+    }
+
+    /// Returns a snapshot of the state of each named task group, keyed by group name.
+    pub fn group_stats(&self) -> HashMap<String, TaskGroupStats> {
+        self.shared().group_stats()
+    }
+
     /// Returns the `MutexGuard` for the `Queen`.
     ///
     /// Note that the `Queen` will remain locked until the returned guard is dropped, and that
@@ -499,6 +1000,13 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
         self.shared().queen.lock()
     }
 
+    /// Returns a snapshot of the progress of each active task, keyed by task index. The snapshot
+    /// includes the progress most recently reported by the task via `Context::report_progress`,
+    /// the worker thread on which it is running, and how long it has been running.
+    pub fn progress(&self) -> HashMap<usize, TaskProgress> {
+        self.shared().progress()
+    }
+
     /// Returns the number of worker threads, i.e., the maximum number of tasks that can be
     /// processed concurrently.
     pub fn num_threads(&self) -> usize {
@@ -524,6 +1032,44 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
         self.shared().is_poisoned()
     }
 
+    /// Closes this `Hive` so that it no longer accepts new tasks. Since all clones of a `Hive` share
+    /// the same state, this closes every clone. Queued and active tasks continue to be processed.
+    ///
+    /// Tasks submitted after the `Hive` is closed are not queued - each one immediately produces an
+    /// `Outcome::Unprocessed` that is sent or stored in the same way as any other outcome. After
+    /// closing, once `join` returns, all work submitted to this `Hive` has been completed.
+    ///
+    /// Returns `true` if this call closed the `Hive`, or `false` if it was already closed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use beekeeper::bee::stock::{Thunk, ThunkWorker};
+    /// use beekeeper::hive::{Builder, Outcome};
+    ///
+    /// # fn main() {
+    /// let hive = Builder::new()
+    ///     .num_threads(4)
+    ///     .build_with_default::<ThunkWorker<u8>>()
+    ///     .unwrap();
+    /// let clone = hive.clone();
+    /// assert!(hive.close());
+    /// assert!(clone.is_closed());
+    /// assert!(matches!(
+    ///     clone.apply(Thunk::of(|| 1)),
+    ///     Outcome::Unprocessed { .. }
+    /// ));
+    /// # }
+    /// ```
+    pub fn close(&self) -> bool {
+        self.shared().close()
+    }
+
+    /// Returns `true` if this `Hive` has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.shared().is_closed()
+    }
+
     /// Returns `true` if the cancelled flag is set.
     pub fn is_suspended(&self) -> bool {
         self.shared().is_suspended()
@@ -574,6 +1120,7 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
     /// Unsets the suspended flag, allowing worker threads to continue processing queued tasks.
     pub fn resume(&self) {
         self.shared().set_suspended(false);
+        self.tasks_available();
     }
 
     fn take_unprocessed_inputs(&self) -> impl ExactSizeIterator<Item = W::Input> {
@@ -589,19 +1136,143 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
     /// Resume this `Hive` and re-submit any unprocessed tasks for processing, with their results
     /// to be sent to `tx`. Returns a `Vec` of task indices that were resumed.
     pub fn resume_send(&self, outcome_tx: OutcomeSender<W>) -> Vec<usize> {
-        self.shared()
-            .set_suspended(false)
-            .then(|| self.swarm_send(self.take_unprocessed_inputs(), outcome_tx))
-            .unwrap_or_default()
+        if self.shared().set_suspended(false) {
+            self.swarm_send(self.take_unprocessed_inputs(), outcome_tx)
+        } else {
+            Vec::new()
+        }
     }
 
     /// Resume this `Hive` and re-submit any unprocessed tasks for processing, with their results
     /// to be stored in the queue. Returns a `Vec` of task indices that were resumed.
     pub fn resume_store(&self) -> Vec<usize> {
-        self.shared()
-            .set_suspended(false)
-            .then(|| self.swarm_store(self.take_unprocessed_inputs()))
-            .unwrap_or_default()
+        if self.shared().set_suspended(false) {
+            self.swarm_store(self.take_unprocessed_inputs())
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Removes all failed outcomes from which the input can be recovered (i.e.,
+    /// `Outcome::Failure` and `Outcome::Panic` with an input, and `Outcome::MaxRetriesAttempted`)
+    /// from `store` and resubmits their inputs to this `Hive`, in order of their original
+    /// indices. The outcomes of the resubmitted tasks are retained in this `Hive`. Returns the
+    /// new indices of the resubmitted tasks.
+    ///
+    /// Note that the failed outcomes are removed from `store` even if this `Hive` is closed, in
+    /// which case the resubmitted tasks are stored in this `Hive` as `Outcome::Unprocessed`. To
+    /// resubmit the failures stored in this `Hive`, pass a clone of it as `store` (e.g.,
+    /// `hive.resubmit_failures(&mut hive.clone())`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use beekeeper::bee::stock::RetryCaller;
+    /// use beekeeper::bee::ApplyError;
+    /// use beekeeper::hive::{Builder, OutcomeBatch, OutcomeStore};
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    ///
+    /// # fn main() {
+    /// static FLAKY: AtomicBool = AtomicBool::new(true);
+    /// let hive = Builder::new()
+    ///     .num_threads(4)
+    ///     .build_with(RetryCaller::of(|i: u8, _| {
+    ///         if i % 2 == 1 && FLAKY.load(Ordering::SeqCst) {
+    ///             Err(ApplyError::Fatal { input: Some(i), error: "flaky" })
+    ///         } else {
+    ///             Ok(i)
+    ///         }
+    ///     }))
+    ///     .unwrap();
+    /// let mut outcomes: OutcomeBatch<_> = hive.map(0..10u8).into();
+    /// FLAKY.store(false, Ordering::SeqCst);
+    /// let indices = hive.resubmit_failures(&mut outcomes);
+    /// assert_eq!(indices.len(), 5);
+    /// assert_eq!(outcomes.num_failures(), 0);
+    /// hive.join();
+    /// assert_eq!(hive.num_successes(), 5);
+    /// # }
+    /// ```
+    pub fn resubmit_failures<S: OutcomeStore<W>>(&self, store: &mut S) -> Vec<usize> {
+        self.resubmit_failures_with(store, |_| true, ResubmitMode::NewIndices)
+    }
+
+    /// Like [`resubmit_failures`](Self::resubmit_failures), but only resubmits the failed outcomes
+    /// for which `filter` returns `true`, and assigns indices to the resubmitted tasks according
+    /// to `mode`. Returns the indices of the resubmitted tasks.
+    ///
+    /// With `ResubmitMode::OriginalIndices`, the outcome of each resubmitted task is stored under
+    /// the same index as the outcome that was removed from `store`. If `store` is neither this
+    /// `Hive` nor the `Husk` from which this `Hive` was created (which continues the sequence of
+    /// indices of the former `Hive`), the original indices may be the same as the indices of other
+    /// tasks submitted to this `Hive`, in which case their stored outcomes will replace one
+    /// another.
+    pub fn resubmit_failures_with<S, F>(
+        &self,
+        store: &mut S,
+        mut filter: F,
+        mode: ResubmitMode,
+    ) -> Vec<usize>
+    where
+        S: OutcomeStore<W>,
+        F: FnMut(&Outcome<W>) -> bool,
+    {
+        self.resubmit(
+            store,
+            |outcome| is_resubmittable(outcome) && filter(outcome),
+            mode,
+        )
+    }
+
+    /// Removes all `Outcome::Unprocessed` outcomes from `store` and resubmits their inputs to this
+    /// `Hive`, in order of their original indices, with indices assigned according to `mode`. The
+    /// outcomes of the resubmitted tasks are retained in this `Hive`. Returns the indices of the
+    /// resubmitted tasks.
+    ///
+    /// See [`resubmit_failures_with`](Self::resubmit_failures_with) for caveats when using
+    /// `ResubmitMode::OriginalIndices`.
+    pub fn resubmit_unprocessed<S: OutcomeStore<W>>(
+        &self,
+        store: &mut S,
+        mode: ResubmitMode,
+    ) -> Vec<usize> {
+        self.resubmit(store, Outcome::is_unprocessed, mode)
+    }
+
+    /// Removes the outcomes for which `filter` returns `true` from `store` and resubmits their
+    /// inputs in order of their indices. `filter` must only return `true` for outcomes from which
+    /// the input can be recovered.
+    fn resubmit<S, F>(&self, store: &mut S, mut filter: F, mode: ResubmitMode) -> Vec<usize>
+    where
+        S: OutcomeStore<W>,
+        F: FnMut(&Outcome<W>) -> bool,
+    {
+        let mut outcomes: Vec<_> = {
+            let mut outcomes = store.outcomes_deref_mut();
+            let indices: Vec<_> = outcomes
+                .iter()
+                .filter(|(_, outcome)| filter(outcome))
+                .map(|(index, _)| *index)
+                .collect();
+            indices
+                .into_iter()
+                .filter_map(|index| outcomes.remove(&index))
+                .collect()
+        };
+        outcomes.sort_unstable_by_key(|outcome| *outcome.index());
+        outcomes
+            .into_iter()
+            .map(|outcome| {
+                let index = *outcome.index();
+                let input = outcome.into_input().unwrap();
+                match mode {
+                    ResubmitMode::NewIndices => self.send_one(input, None),
+                    ResubmitMode::OriginalIndices => {
+                        self.send_task(self.shared().prepare_task_with_index(input, index, None))
+                    }
+                }
+            })
+            .collect()
     }
 
     /// Returns any stored `Outcome`s.
@@ -609,11 +1280,122 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
         self.shared().take_outcomes()
     }
 
-    /// Blocks this thread until all tasks finish.
+    /// Returns an iterator that removes and yields each stored `Outcome` as soon as it becomes
+    /// available, blocking while waiting for more outcomes to be stored. The iterator ends once
+    /// there are no more stored outcomes and all tasks have finished (i.e., once `join` would
+    /// return). Outcomes that are already stored when this method is called are yielded first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use beekeeper::bee::stock::Caller;
+    /// use beekeeper::hive::{Builder, Outcome};
+    ///
+    /// # fn main() {
+    /// let hive = Builder::new()
+    ///     .num_threads(4)
+    ///     .build_with(Caller::of(|i: u64| i * i))
+    ///     .unwrap();
+    /// hive.map_store(0..10u64);
+    /// let sum: u64 = hive.stored_iter().map(Outcome::unwrap).sum();
+    /// assert_eq!(sum, 285);
+    /// # }
+    /// ```
+    pub fn stored_iter(&self) -> impl Iterator<Item = Outcome<W>> + '_ {
+        StoredIter::new(self.shared(), None)
+    }
+
+    /// Like [`stored_iter`](Self::stored_iter), but only yields the outcomes of the tasks with
+    /// the given `indices` (e.g., the indices returned by `swarm_store`); any other stored
+    /// outcomes are left in the `Hive`. The iterator ends once the outcomes of all the tasks have
+    /// been yielded. If all tasks have finished and the outcome of one of the tasks has not been
+    /// stored (e.g., because its outcome was sent to a channel, or was lost because the `Worker`
+    /// panicked), `Outcome::Missing` is yielded in its place.
+    pub fn stored_iter_for<I>(&self, indices: I) -> impl Iterator<Item = Outcome<W>> + '_
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        StoredIter::new(self.shared(), Some(indices.into_iter().collect()))
+    }
+
+    /// Blocks this thread until all tasks finish, removing each stored `Outcome` as soon as it
+    /// becomes available, and returns the outcomes. Unlike calling `join` followed by
+    /// `take_stored`, this does not block forever if the `Hive` was built with the
+    /// [`StoreLimitPolicy::Block`] policy, and it makes it less likely that outcomes are dropped
+    /// when there is a limit on the number of stored outcomes.
+    ///
+    /// [`StoreLimitPolicy::Block`]: crate::hive::StoreLimitPolicy::Block
+    pub fn drain_stored_blocking(&self) -> OutcomeBatch<W> {
+        self.stored_iter().into()
+    }
+
+    /// Blocks this thread until all tasks finish. If this `Hive` has been closed, no more tasks can
+    /// be queued, so once this method returns, all of the work submitted to the `Hive` is complete.
+    ///
+    /// If the `Hive` was built with the [`StoreLimitPolicy::Block`] policy, a task does not finish
+    /// until there is room to store its outcome, so this method blocks forever if the store is
+    /// full and no other thread removes stored outcomes. Use
+    /// [`drain_stored_blocking`](Self::drain_stored_blocking) instead.
+    ///
+    /// [`StoreLimitPolicy::Block`]: crate::hive::StoreLimitPolicy::Block
     pub fn join(&self) {
         self.shared().wait_on_done();
     }
 
+    /// Blocks this thread until all tasks finish or until `timeout` has elapsed. Returns `true` if
+    /// all tasks finished before the timeout.
+    pub fn join_timeout(&self, timeout: Duration) -> bool {
+        self.shared()
+            .wait_on_done_until(self.shared().now() + timeout)
+    }
+
+    /// Blocks this thread until the outcomes of all the tasks with the given `indices` have been
+    /// stored in this `Hive`, or until `timeout` has elapsed. Returns `true` if all the outcomes
+    /// were stored before the timeout.
+    ///
+    /// Only tasks submitted with one of the `*_store` methods have their outcomes stored in the
+    /// `Hive` - waiting for any other task will always time out.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use beekeeper::bee::stock::{Thunk, ThunkWorker};
+    /// use beekeeper::hive::{Builder, OutcomeStore};
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// # fn main() {
+    /// let hive = Builder::new()
+    ///     .num_threads(2)
+    ///     .build_with_default::<ThunkWorker<u64>>()
+    ///     .unwrap();
+    /// let indices = hive.map_store((1..=4).map(|i| {
+    ///     Thunk::of(move || {
+    ///         thread::sleep(Duration::from_millis(i * 100));
+    ///         i
+    ///     })
+    /// }));
+    /// assert!(hive.wait_for(&indices[..2], Duration::from_secs(5)));
+    /// assert!(hive.num_successes() >= 2);
+    /// # }
+    /// ```
+    pub fn wait_for(&self, indices: &[usize], timeout: Duration) -> bool {
+        self.shared()
+            .wait_for_stored(indices, self.shared().now() + timeout)
+    }
+
+    /// Blocks this thread until the outcome of any of the tasks with the given `indices` has been
+    /// stored in this `Hive`, and returns its index. The outcome is not removed from the `Hive`.
+    /// Returns `None` immediately if `indices` is empty.
+    ///
+    /// Only tasks submitted with one of the `*_store` methods have their outcomes stored in the
+    /// `Hive`. If none of the outcomes has been stored by the time there are no more active tasks
+    /// (and no more queued tasks, unless the `Hive` is suspended), or if the `Hive` is poisoned,
+    /// this method returns `None`.
+    pub fn wait_any(&self, indices: &[usize]) -> Option<usize> {
+        self.shared().wait_for_any_stored(indices)
+    }
+
     /// Consumes this `Hive` and attempts to return a `Husk` containing the remnants of this `Hive`,
     /// including any stored task outcomes, and all the data necessary to create a new `Hive`.
     ///
@@ -629,8 +1411,76 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
         let inner = self.0.take().unwrap();
         // wait for all tasks to finish
         inner.shared.wait_on_done();
+        Some(Self::into_husk(inner))
+    }
+
+    /// Consumes this `Hive` and shuts it down according to `mode`, then returns a `Husk` containing
+    /// the remnants of this `Hive`. Tasks that did not finish are stored in the `Husk` as
+    /// `Outcome::Unprocessed` (unless they were submitted with an outcome channel, in which case
+    /// the unprocessed outcome is sent to the channel).
+    ///
+    /// If this `Hive` is suspended, it is resumed before draining. When the hive is aborted (either
+    /// with `ShutdownMode::Abort` or when the `ShutdownMode::DrainWithin` timeout expires),
+    /// cancellation is signaled to active tasks via `Context::is_cancelled`, and this method waits
+    /// for the active tasks to return.
+    ///
+    /// If this `Hive` has been cloned, and those clones have not been dropped, this method returns
+    /// `None` without shutting down the `Hive`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use beekeeper::bee::stock::{Thunk, ThunkWorker};
+    /// use beekeeper::hive::{Builder, OutcomeStore, ShutdownMode};
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// # fn main() {
+    /// let hive = Builder::new()
+    ///     .num_threads(1)
+    ///     .build_with_default::<ThunkWorker<()>>()
+    ///     .unwrap();
+    /// hive.map_store((0..5).map(|_| Thunk::of(|| thread::sleep(Duration::from_millis(500)))));
+    /// let husk = hive
+    ///     .shutdown(ShutdownMode::DrainWithin(Duration::from_millis(750)))
+    ///     .unwrap();
+    /// assert_eq!(husk.num_successes(), 2);
+    /// assert_eq!(husk.num_unprocessed(), 3);
+    /// # }
+    /// ```
+    pub fn shutdown(mut self, mode: ShutdownMode) -> Option<Husk<W, Q>> {
+        if self.shared().num_referrers() > 1 {
+            return None;
+        }
+        if mode != ShutdownMode::Abort {
+            self.resume();
+        }
+        let inner = self.0.take().unwrap();
+        match mode {
+            ShutdownMode::Drain => inner.shared.wait_on_done(),
+            ShutdownMode::DrainWithin(timeout) => {
+                if !inner
+                    .shared
+                    .wait_on_done_until(inner.shared.now() + timeout)
+                {
+                    inner.shared.abort();
+                }
+            }
+            ShutdownMode::Abort => inner.shared.abort(),
+        }
+        Some(Self::into_husk(inner))
+    }
+
+    /// Disconnects the task channel, waits for all worker threads to terminate, and converts the
+    /// shared data into a `Husk`.
+    fn into_husk(inner: HiveInner<W, Q>) -> Husk<W, Q> {
         // drop the task sender so receivers will drop automatically
         drop(inner.task_tx);
+        // stop the threads of the apiary (if any) from executing tasks or otherwise referencing
+        // the shared data
+        if let Some(link) = inner.shared.take_apiary() {
+            link.unregister(&inner.shared);
+        }
         // wait for worker threads to drop
         let mut backoff = None::<Backoff>;
         while Arc::strong_count(&inner.shared) > 1 {
@@ -639,7 +1489,7 @@ impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
         // take the shared data out of the Arc
         let shared = Arc::into_inner(inner.shared).expect("Arc::try_unwrap failed");
         // convert the shared data into a Husk
-        Some(shared.try_into_husk())
+        shared.try_into_husk()
     }
 }
 
@@ -689,6 +1539,16 @@ impl<W: Worker, Q: Queen<Kind = W>> DerefOutcomes<W> for Hive<W, Q> {
 
 impl<W: Worker, Q: Queen<Kind = W>> OutcomeStore<W> for Hive<W, Q> {}
 
+/// Returns `true` if `outcome` is a failure from which the input can be recovered.
+fn is_resubmittable<W: Worker>(outcome: &Outcome<W>) -> bool {
+    match outcome {
+        Outcome::Failure { input, .. } | Outcome::Panic { input, .. } => input.is_some(),
+        #[cfg(feature = "retry")]
+        Outcome::MaxRetriesAttempted { .. } => true,
+        _ => false,
+    }
+}
+
 impl<W: Worker, Q: Queen<Kind = W>> Drop for Hive<W, Q> {
     fn drop(&mut self) {
         // if this Hive has already been turned into a Husk, it's inner value will be `None`
@@ -730,15 +1590,74 @@ impl<W: Worker, Q: Queen<Kind = W>> Sentinel<W, Q> {
 
 impl<W: Worker, Q: Queen<Kind = W>> Drop for Sentinel<W, Q> {
     fn drop(&mut self) {
-        self.shared.finish_task(thread::panicking());
+        // if the thread is panicking, then it did not finish its current task; otherwise, the
+        // worker loop has already finished all of its tasks
+        if thread::panicking() {
+            self.shared.finish_task(true);
+        }
         // the thread is only respawned if the sentinel is active
-        if self.active && !self.shared.is_poisoned() {
+        if self.active
+            && !self.shared.is_poisoned()
+            && self.shared.panic_policy() != PanicPolicy::Stop
+        {
             // nothing we can do if we fail to re-spawn the thread
             let _ = Hive::spawn(self.thread_index, Arc::clone(&self.shared));
         }
     }
 }
 
+/// Guard for a batch of tasks being executed by a worker thread. If the thread panics while the
+/// guard is armed, all but one of the tasks in the batch are finished when the guard is dropped
+/// (the remaining task is finished by the thread's `Sentinel`).
+/// Holds the outcome sender of a task while its `Worker` is executing. If the `Worker` panics and
+/// the panic is not caught, the task's outcome is lost, so `Outcome::Missing` is sent instead when
+/// this guard is dropped during unwinding.
+struct OutcomeGuard<W: Worker> {
+    index: usize,
+    outcome_tx: Option<OutcomeSender<W>>,
+}
+
+impl<W: Worker> OutcomeGuard<W> {
+    fn new(index: usize, outcome_tx: Option<OutcomeSender<W>>) -> Self {
+        Self { index, outcome_tx }
+    }
+
+    /// Disarms this guard and returns the outcome sender.
+    fn disarm(mut self) -> Option<OutcomeSender<W>> {
+        self.outcome_tx.take()
+    }
+}
+
+impl<W: Worker> Drop for OutcomeGuard<W> {
+    fn drop(&mut self) {
+        if let Some(tx) = self.outcome_tx.take() {
+            let _ = tx.try_send_msg(Outcome::Missing { index: self.index });
+        }
+    }
+}
+
+struct BatchGuard<'a, W: Worker, Q: Queen<Kind = W>> {
+    shared: &'a Shared<W, Q>,
+    size: usize,
+}
+
+impl<W: Worker, Q: Queen<Kind = W>> BatchGuard<'_, W, Q> {
+    /// Disarms and destroys this guard.
+    fn disarm(mut self) {
+        self.size = 0;
+    }
+}
+
+impl<W: Worker, Q: Queen<Kind = W>> Drop for BatchGuard<'_, W, Q> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            for _ in 1..self.size {
+                self.shared.finish_task(false);
+            }
+        }
+    }
+}
+
 #[cfg(not(feature = "affinity"))]
 mod no_affinity {
     use crate::bee::{Queen, Worker};
@@ -786,37 +1705,64 @@ mod affinity {
 
 #[cfg(not(feature = "retry"))]
 mod no_retry {
-    use crate::bee::{Queen, Worker};
-    use crate::hive::{Hive, Outcome, Shared, Task};
+    use crate::bee::{ApplyError, Context, Queen, Worker, WorkerResult};
+    use crate::hive::{Hive, Outcome, OutcomeSender, Shared};
 
     impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
         #[inline]
-        pub(super) fn execute(task: Task<W>, worker: &mut W, shared: &Shared<W, Q>) {
-            let (input, ctx, outcome_tx) = task.into_parts();
-            let result = worker.apply(input, &ctx);
+        /// Sends or stores the outcome for the result of executing a task. Returns `true` if the
+        /// `Worker` panicked (and the panic was caught).
+        pub(super) fn handle_result(
+            result: WorkerResult<W>,
+            ctx: Context,
+            outcome_tx: Option<OutcomeSender<W>>,
+            shared: &Shared<W, Q>,
+        ) -> bool {
+            let panicked = matches!(result, Err(ApplyError::Panic { .. }));
+            if panicked {
+                shared.record_panic();
+            }
             let outcome = Outcome::from_worker_result(result, ctx.index());
             shared.send_or_store_outcome(outcome, outcome_tx);
+            panicked
         }
     }
 }
 
 #[cfg(feature = "retry")]
 mod retry {
-    use crate::bee::{ApplyError, Queen, Worker};
-    use crate::hive::{Hive, Outcome, Shared, Task};
+    use crate::bee::{ApplyError, Context, Queen, Worker, WorkerResult};
+    use crate::hive::{Hive, Outcome, OutcomeSender, Shared};
 
     impl<W: Worker, Q: Queen<Kind = W>> Hive<W, Q> {
         #[inline]
-        pub(super) fn execute(task: Task<W>, worker: &mut W, shared: &Shared<W, Q>) {
-            let (input, mut ctx, outcome_tx) = task.into_parts();
-            match worker.apply(input, &ctx) {
+        /// Either queues the task for retry or sends or stores the outcome for the result of
+        /// executing the task. Returns `true` if the `Worker` panicked (and the panic was caught).
+        pub(super) fn handle_result(
+            result: WorkerResult<W>,
+            mut ctx: Context,
+            outcome_tx: Option<OutcomeSender<W>>,
+            shared: &Shared<W, Q>,
+        ) -> bool {
+            match result {
                 Err(ApplyError::Retryable { input, .. }) if shared.can_retry(&ctx) => {
                     ctx.inc_attempt();
                     shared.queue_retry(input, ctx, outcome_tx);
+                    false
                 }
-                result => {
+                mut result => {
+                    let panicked = if let Err(ApplyError::Panic { payload, .. }) = &mut result {
+                        payload.set_attempt(ctx.attempt());
+                        true
+                    } else {
+                        false
+                    };
+                    if panicked {
+                        shared.record_panic();
+                    }
                     let outcome = Outcome::from_worker_result(result, ctx.index());
                     shared.send_or_store_outcome(outcome, outcome_tx);
+                    panicked
                 }
             }
         }
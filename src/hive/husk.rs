@@ -1,8 +1,9 @@
 use super::{
     Builder, Config, DerefOutcomes, Hive, Outcome, OutcomeBatch, OutcomeSender, OutcomeStore,
-    OwnedOutcomes, SpawnError,
+    OwnedOutcomes, RateLimitKeyFn, ResubmitMode, SpawnError,
 };
 use crate::bee::{Queen, Worker};
+use crate::panic::Panic;
 use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
 
@@ -12,6 +13,7 @@ pub struct Husk<W: Worker, Q: Queen<Kind = W>> {
     queen: Q,
     num_panics: usize,
     outcomes: HashMap<usize, Outcome<W>>,
+    rate_limit_key: Option<RateLimitKeyFn<W>>,
 }
 
 impl<W: Worker, Q: Queen<Kind = W>> Husk<W, Q> {
@@ -20,12 +22,14 @@ impl<W: Worker, Q: Queen<Kind = W>> Husk<W, Q> {
         queen: Q,
         num_panics: usize,
         outcomes: HashMap<usize, Outcome<W>>,
+        rate_limit_key: Option<RateLimitKeyFn<W>>,
     ) -> Self {
         Self {
             config,
             queen,
             num_panics,
             outcomes,
+            rate_limit_key,
         }
     }
 
@@ -39,57 +43,130 @@ impl<W: Worker, Q: Queen<Kind = W>> Husk<W, Q> {
         self.num_panics
     }
 
+    /// The index that would have been assigned to the next task submitted to the former `Hive`.
+    /// A `Hive` created from this `Husk` (or from a `Builder` returned by `as_builder`) assigns
+    /// this index to the first task submitted to it, so task indices are not reused.
+    pub fn next_task_index(&self) -> usize {
+        self.config.next_task_index.get_or_default()
+    }
+
+    /// Returns an iterator over the index and `Panic` of each `Outcome::Panic` in this `Husk`.
+    pub fn iter_panics(&self) -> impl Iterator<Item = (&usize, &Panic<String>)> {
+        self.outcomes
+            .iter()
+            .filter_map(|(index, outcome)| outcome.as_panic().map(|panic| (index, panic)))
+    }
+
     /// Consumes this `Husk` and returns the `Queen` and `Outcome`s.
     pub fn into_parts(self) -> (Q, OutcomeBatch<W>) {
         (self.queen, OutcomeBatch::new(self.outcomes))
     }
 
     /// Returns a new `Builder` that will create a `Hive` with the same configuration as the one
-    /// that produced this `Husk`.
+    /// that produced this `Husk`, and that continues its sequence of task indices.
     pub fn as_builder(&self) -> Builder {
         self.config.clone().into()
     }
 
     /// Consumes this `Husk` and returns a new `Hive` with the same configuration as the one that
-    /// produced this `Husk`.
+    /// produced this `Husk`. The rate-limit key function of the former `Hive` (if any) is also
+    /// set on the new `Hive`.
     pub fn into_hive(self) -> Result<Hive<W, Q>, SpawnError> {
-        self.as_builder().build(self.queen)
+        Self::build_hive(&self.config, self.queen, self.rate_limit_key)
+    }
+
+    /// Builds a new `Hive` with `config` and `queen`, and sets its rate-limit key function.
+    fn build_hive(
+        config: &Config,
+        queen: Q,
+        rate_limit_key: Option<RateLimitKeyFn<W>>,
+    ) -> Result<Hive<W, Q>, SpawnError> {
+        let hive = Builder::from(config.clone()).build(queen)?;
+        if let Some(key_fn) = rate_limit_key {
+            hive.shared().set_rate_limit_key(key_fn);
+        }
+        Ok(hive)
     }
 
+    /// Returns the inputs of the `Outcome::Unprocessed` outcomes in order of their indices.
     fn collect_unprocessed(outcomes: HashMap<usize, Outcome<W>>) -> Vec<W::Input> {
-        outcomes
-            .into_values()
-            .filter_map(|outcome| match outcome {
-                Outcome::Unprocessed { input: value, .. } => Some(value),
+        let mut unprocessed: Vec<_> = outcomes
+            .into_iter()
+            .filter_map(|(index, outcome)| match outcome {
+                Outcome::Unprocessed { input: value, .. } => Some((index, value)),
                 _ => None,
             })
-            .collect()
+            .collect();
+        unprocessed.sort_unstable_by_key(|(index, _)| *index);
+        unprocessed.into_iter().map(|(_, value)| value).collect()
     }
 
     /// Consumes this `Husk` and creates a new `Hive` with the same configuration as the one that
-    /// produced this `Husk`, and queues all the `Outcome::Unprocessed` values. The results will
-    /// be sent to `tx`. Returns the new `Hive` and the indices of the tasks that were queued.
+    /// produced this `Husk`, and queues all the `Outcome::Unprocessed` values in order of their
+    /// original indices. The results will be sent to `tx`. Returns the new `Hive` and the (new)
+    /// indices of the tasks that were queued.
     ///
     /// This method panics if there is an error creating the new `Hive`.
     pub fn into_hive_swarm_unprocessed_to(self, tx: OutcomeSender<W>) -> (Hive<W, Q>, Vec<usize>) {
-        let hive = self.as_builder().build(self.queen).unwrap();
+        let hive = Self::build_hive(&self.config, self.queen, self.rate_limit_key).unwrap();
         let unprocessed = Self::collect_unprocessed(self.outcomes);
         let indices = hive.swarm_send(unprocessed, tx);
         (hive, indices)
     }
 
     /// Consumes this `Husk` and creates a new `Hive` with the same configuration as the one that
-    /// produced this `Husk`, and queues all the `Outcome::Unprocessed` values. The results will
-    /// be retained in the new `Hive` for later retrieval. Returns the new `Hive` and the indices
-    /// of the tasks that were queued.
+    /// produced this `Husk`, and queues all the `Outcome::Unprocessed` values in order of their
+    /// original indices. The results will be retained in the new `Hive` for later retrieval.
+    /// Returns the new `Hive` and the (new) indices of the tasks that were queued.
+    ///
+    /// Use [`into_hive_resubmit_unprocessed`](Self::into_hive_resubmit_unprocessed) to keep the
+    /// original indices of the tasks and the other stored outcomes.
     ///
     /// This method panics if there is an error creating the new `Hive`.
     pub fn into_hive_swarm_unprocessed_store(self) -> (Hive<W, Q>, Vec<usize>) {
-        let hive = self.as_builder().build(self.queen).unwrap();
+        let hive = Self::build_hive(&self.config, self.queen, self.rate_limit_key).unwrap();
         let unprocessed = Self::collect_unprocessed(self.outcomes);
         let indices = hive.swarm_store(unprocessed);
         (hive, indices)
     }
+
+    /// Consumes this `Husk` and creates a new `Hive` with the same configuration as the one that
+    /// produced this `Husk`, and resubmits the inputs of the failed outcomes for which `filter`
+    /// returns `true` (see [`Hive::resubmit_failures_with`] for which outcomes are considered).
+    /// The indices of the resubmitted tasks are assigned according to `mode`. All other outcomes
+    /// are retained in the new `Hive`, and since the new `Hive` continues the sequence of task
+    /// indices, the indices of tasks submitted to it do not conflict with them. Returns the new
+    /// `Hive` and the indices of the resubmitted tasks.
+    ///
+    /// This method panics if there is an error creating the new `Hive`.
+    ///
+    /// [`Hive::resubmit_failures_with`]: crate::hive::Hive::resubmit_failures_with
+    pub fn into_hive_resubmit<F>(self, filter: F, mode: ResubmitMode) -> (Hive<W, Q>, Vec<usize>)
+    where
+        F: FnMut(&Outcome<W>) -> bool,
+    {
+        let mut hive = Self::build_hive(&self.config, self.queen, self.rate_limit_key).unwrap();
+        let mut outcomes = OutcomeBatch::new(self.outcomes);
+        let indices = hive.resubmit_failures_with(&mut outcomes, filter, mode);
+        hive.outcomes_deref_mut().extend(outcomes.outcomes());
+        (hive, indices)
+    }
+
+    /// Consumes this `Husk` and creates a new `Hive` with the same configuration as the one that
+    /// produced this `Husk`, and resubmits the inputs of all the `Outcome::Unprocessed` outcomes
+    /// in order of their original indices. The indices of the resubmitted tasks are assigned
+    /// according to `mode` - with `ResubmitMode::OriginalIndices`, the outcome of each task is
+    /// stored under the same index as in the former `Hive`. All other outcomes are retained in the
+    /// new `Hive`. Returns the new `Hive` and the indices of the resubmitted tasks.
+    ///
+    /// This method panics if there is an error creating the new `Hive`.
+    pub fn into_hive_resubmit_unprocessed(self, mode: ResubmitMode) -> (Hive<W, Q>, Vec<usize>) {
+        let mut hive = Self::build_hive(&self.config, self.queen, self.rate_limit_key).unwrap();
+        let mut outcomes = OutcomeBatch::new(self.outcomes);
+        let indices = hive.resubmit_unprocessed(&mut outcomes, mode);
+        hive.outcomes_deref_mut().extend(outcomes.outcomes());
+        (hive, indices)
+    }
 }
 
 impl<W: Worker, Q: Queen<Kind = W>> DerefOutcomes<W> for Husk<W, Q> {
@@ -117,7 +194,10 @@ impl<W: Worker, Q: Queen<Kind = W>> OutcomeStore<W> for Husk<W, Q> {}
 #[cfg(test)]
 mod tests {
     use crate::bee::stock::{PunkWorker, Thunk, ThunkWorker};
-    use crate::hive::{outcome_channel, Builder, Outcome, OutcomeIteratorExt, OutcomeStore};
+    use crate::bee::{Context, RefWorker, RefWorkerResult};
+    use crate::hive::{
+        outcome_channel, Builder, Outcome, OutcomeIteratorExt, OutcomeStore, ResubmitMode,
+    };
 
     #[test]
     fn test_unprocessed() {
@@ -145,6 +225,46 @@ mod tests {
         assert_eq!(husk.remove_all_unprocessed().len(), 10);
     }
 
+    #[derive(Clone, Debug)]
+    struct PanicWorker;
+
+    impl RefWorker for PanicWorker {
+        type Input = u8;
+        type Output = u8;
+        type Error = ();
+
+        fn apply_ref(&mut self, input: &u8, _: &Context) -> RefWorkerResult<Self> {
+            if *input % 2 == 0 {
+                panic!("even input");
+            }
+            Ok(*input)
+        }
+    }
+
+    #[test]
+    fn test_panics() {
+        let hive = Builder::new()
+            .num_threads(2)
+            .thread_name("panicky")
+            .build_with(PanicWorker)
+            .unwrap();
+        hive.map_store(0..10);
+        hive.join();
+        let husk = hive.try_into_husk().unwrap();
+        let mut indices = husk
+            .iter_panics()
+            .map(|(index, panic)| {
+                assert!(panic.thread_index().is_some_and(|i| i < 2));
+                assert_eq!(panic.thread_name(), Some("panicky"));
+                assert_eq!(panic.attempt(), 0);
+                *index
+            })
+            .collect::<Vec<_>>();
+        indices.sort();
+        assert_eq!(indices, vec![0, 2, 4, 6, 8]);
+        assert!(husk.get(1).unwrap().as_panic().is_none());
+    }
+
     #[test]
     fn test_reprocess_unprocessed() {
         // don't spin up any worker threads so that no tasks will be processed
@@ -192,6 +312,52 @@ mod tests {
         assert_eq!(outputs, (0..10).collect::<Vec<_>>());
     }
 
+    #[test]
+    fn test_next_task_index() {
+        let hive1 = Builder::new()
+            .num_threads(0)
+            .build_with_default::<ThunkWorker<u8>>()
+            .unwrap();
+        let _ = hive1.map_store((0..10).map(|i| Thunk::of(move || i)));
+        hive1.suspend();
+        let husk1 = hive1.try_into_husk().unwrap();
+        assert_eq!(husk1.next_task_index(), 10);
+        let (hive2, indices) = husk1.into_hive_swarm_unprocessed_store();
+        // the new hive continues the sequence of indices, and the tasks are queued in order
+        assert_eq!(indices, (10..20).collect::<Vec<_>>());
+        hive2.grow(1);
+        hive2.join();
+        let mut outcomes = hive2.take_stored();
+        for (i, index) in indices.into_iter().enumerate() {
+            assert_eq!(outcomes.remove(&index).unwrap().unwrap(), i as u8);
+        }
+    }
+
+    #[test]
+    fn test_resubmit_unprocessed_original_indices() {
+        let hive1 = Builder::new()
+            .num_threads(0)
+            .build_with_default::<ThunkWorker<u8>>()
+            .unwrap();
+        let indices1 = hive1.map_store((0..10).map(|i| Thunk::of(move || i)));
+        hive1.suspend();
+        let husk1 = hive1.try_into_husk().unwrap();
+        let (hive2, indices2) = husk1.into_hive_resubmit_unprocessed(ResubmitMode::OriginalIndices);
+        assert_eq!(indices1, indices2);
+        // new tasks do not reuse the original indices
+        assert_eq!(hive2.apply_store(Thunk::of(|| 10)), 10);
+        hive2.grow(8);
+        hive2.join();
+        let husk2 = hive2.try_into_husk().unwrap();
+        assert_eq!(husk2.next_task_index(), 11);
+        let outputs: Vec<_> = husk2
+            .into_sorted_vec()
+            .into_iter()
+            .map(Outcome::unwrap)
+            .collect();
+        assert_eq!(outputs, (0..=10).collect::<Vec<_>>());
+    }
+
     #[test]
     fn test_into_result() {
         let hive = Builder::new()
@@ -0,0 +1,395 @@
+//! Rate limiting of task dispatch using token buckets.
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// A limit of `n` tasks per `per` duration, with up to `burst` tasks allowed to be dispatched at
+/// once after a period of inactivity.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    n: u32,
+    per: Duration,
+    burst: u32,
+}
+
+impl RateLimit {
+    /// Creates a new `RateLimit` of `n` tasks per `per` duration. The burst size defaults to `n`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is `0` or `per` is zero.
+    pub fn new(n: u32, per: Duration) -> Self {
+        assert!(n > 0, "rate limit must allow at least one task");
+        assert!(!per.is_zero(), "rate limit period must be non-zero");
+        Self { n, per, burst: n }
+    }
+
+    /// Sets the maximum number of tasks that may be dispatched at once after a period of
+    /// inactivity. Must be at least `1`.
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+
+    /// Returns the number of tasks allowed per period.
+    pub fn n(&self) -> u32 {
+        self.n
+    }
+
+    /// Returns the period over which `n` tasks are allowed.
+    pub fn per(&self) -> Duration {
+        self.per
+    }
+
+    /// Returns the burst size.
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+
+    /// Returns the time it takes to refill a single token.
+    fn interval(&self) -> Duration {
+        self.per / self.n
+    }
+}
+
+/// A token bucket that starts full (i.e., with `burst` tokens) and refills at a rate of one token
+/// per `per / n`.
+#[derive(Debug)]
+struct TokenBucket {
+    limit: RateLimit,
+    tokens: u32,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst,
+            last_refill: now,
+        }
+    }
+
+    /// Adds the tokens that have accumulated since the last refill.
+    fn refill(&mut self, now: Instant) {
+        if self.tokens < self.limit.burst {
+            let interval = self.limit.interval();
+            let elapsed = now.saturating_duration_since(self.last_refill);
+            let refill = (elapsed.as_nanos() / interval.as_nanos().max(1)) as u32;
+            if refill > 0 {
+                self.tokens = self.tokens.saturating_add(refill).min(self.limit.burst);
+                self.last_refill = if self.tokens == self.limit.burst {
+                    now
+                } else {
+                    self.last_refill + interval * refill
+                };
+            }
+        } else {
+            self.last_refill = now;
+        }
+    }
+
+    /// Returns `None` if a token is available, otherwise the time to wait until the next token
+    /// becomes available. Must be called after `refill`.
+    fn wait_time(&self, now: Instant) -> Option<Duration> {
+        (self.tokens == 0).then(|| {
+            (self.last_refill + self.limit.interval())
+                .saturating_duration_since(now)
+                .max(MIN_WAIT)
+        })
+    }
+
+    /// Returns `true` if the bucket is full, in which case it is equivalent to a new bucket and
+    /// may be discarded. Must be called after `refill`.
+    fn is_full(&self) -> bool {
+        self.tokens == self.limit.burst
+    }
+
+    /// Takes a token if one is available, otherwise returns the time to wait until the next token
+    /// becomes available.
+    #[cfg(test)]
+    fn try_take(&mut self, now: Instant) -> Result<(), Duration> {
+        self.refill(now);
+        match self.wait_time(now) {
+            Some(wait) => Err(wait),
+            None => {
+                self.tokens -= 1;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The shortest time that a task waits for a token, so that a task is not retried in a busy loop
+/// due to rounding.
+const MIN_WAIT: Duration = Duration::from_micros(100);
+/// The minimum number of key buckets above which full buckets are discarded.
+const MIN_PRUNE_LEN: usize = 64;
+
+/// The buckets of the keyed rate limit.
+#[derive(Debug)]
+struct KeyedBuckets {
+    buckets: HashMap<u64, TokenBucket>,
+    // the number of buckets above which full buckets are discarded
+    prune_len: usize,
+}
+
+impl Default for KeyedBuckets {
+    fn default() -> Self {
+        Self {
+            buckets: HashMap::new(),
+            prune_len: MIN_PRUNE_LEN,
+        }
+    }
+}
+
+impl KeyedBuckets {
+    /// Discards all buckets that are full, if there are more than `prune_len` of them. A key whose
+    /// bucket has been discarded gets a new (full) bucket the next time it is used, which is
+    /// indistinguishable from its old bucket.
+    fn prune(&mut self, now: Instant) {
+        if self.buckets.len() > self.prune_len {
+            self.buckets.retain(|_, bucket| {
+                bucket.refill(now);
+                !bucket.is_full()
+            });
+            self.prune_len = (self.buckets.len() * 2).max(MIN_PRUNE_LEN);
+        }
+    }
+}
+
+/// Rate limiter consulted by worker threads before starting a task. There is an optional global
+/// limit, and an optional limit that applies independently to each key.
+///
+/// Keys are hashes, so two distinct keys whose hashes collide share a bucket. Buckets that have
+/// refilled completely are discarded once there are many of them, so the number of buckets is
+/// bounded by the number of keys that were used recently rather than by all keys ever used.
+#[derive(Debug)]
+pub struct RateLimiter {
+    global: Option<Mutex<TokenBucket>>,
+    key_limit: Option<RateLimit>,
+    keyed: Mutex<KeyedBuckets>,
+}
+
+impl RateLimiter {
+    /// Returns a new `RateLimiter` whose global bucket (if any) is full at time `now`, or `None` if
+    /// neither limit is specified.
+    pub fn new(
+        global: Option<RateLimit>,
+        key_limit: Option<RateLimit>,
+        now: Instant,
+    ) -> Option<Self> {
+        if global.is_none() && key_limit.is_none() {
+            return None;
+        }
+        Some(Self {
+            global: global.map(|limit| Mutex::new(TokenBucket::new(limit, now))),
+            key_limit,
+            keyed: Default::default(),
+        })
+    }
+
+    /// Takes a token from the bucket for `key` (if any) and from the global bucket (if any) if
+    /// both have a token available. Otherwise, no token is taken and the time to wait until both
+    /// buckets may have a token available is returned.
+    pub fn try_acquire(&self, key: Option<u64>, now: Instant) -> Result<(), Duration> {
+        let mut keyed = self.keyed.lock();
+        let key_bucket = match (key, self.key_limit) {
+            (Some(hash), Some(key_limit)) => Some(
+                keyed
+                    .buckets
+                    .entry(hash)
+                    .or_insert_with(|| TokenBucket::new(key_limit, now)),
+            ),
+            _ => None,
+        };
+        let mut global = self.global.as_ref().map(|global| global.lock());
+        let mut buckets: Vec<&mut TokenBucket> = key_bucket
+            .into_iter()
+            .chain(global.as_deref_mut())
+            .collect();
+        let wait = buckets
+            .iter_mut()
+            .filter_map(|bucket| {
+                bucket.refill(now);
+                bucket.wait_time(now)
+            })
+            .max();
+        if let Some(wait) = wait {
+            return Err(wait);
+        }
+        buckets.into_iter().for_each(|bucket| bucket.tokens -= 1);
+        drop(global);
+        keyed.prune(now);
+        Ok(())
+    }
+}
+
+/// Tasks that were not permitted to start by the `RateLimiter`, in the order they were deferred,
+/// each with the time after which it should be offered to the `RateLimiter` again.
+#[derive(Debug)]
+pub struct RateDeferred<T>(VecDeque<(Instant, T)>);
+
+impl<T> Default for RateDeferred<T> {
+    fn default() -> Self {
+        Self(VecDeque::new())
+    }
+}
+
+impl<T> RateDeferred<T> {
+    /// Defers `item` until `ready_at`.
+    pub fn push(&mut self, item: T, ready_at: Instant) {
+        self.0.push_back((ready_at, item));
+    }
+
+    /// Removes and returns the first item whose ready time has passed and for which `acquire`
+    /// returns `Ok`. Each ready item for which `acquire` returns `Err(wait)` is deferred again
+    /// until `now + wait`.
+    pub fn pop_ready<F>(&mut self, now: Instant, mut acquire: F) -> Option<T>
+    where
+        F: FnMut(&T) -> Result<(), Duration>,
+    {
+        let index = self.0.iter_mut().position(|(ready_at, item)| {
+            *ready_at <= now
+                && match acquire(item) {
+                    Ok(_) => true,
+                    Err(wait) => {
+                        *ready_at = now + wait;
+                        false
+                    }
+                }
+        })?;
+        self.0.remove(index).map(|(_, item)| item)
+    }
+
+    /// Returns the earliest time at which an item may be ready, or `None` if there are no items.
+    pub fn next_ready(&self) -> Option<Instant> {
+        self.0.iter().map(|(ready_at, _)| *ready_at).min()
+    }
+
+    /// Returns `true` if there are no deferred items.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Removes and returns all deferred items.
+    pub fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        self.0.drain(..).map(|(_, item)| item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RateDeferred, RateLimit, RateLimiter, TokenBucket};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_burst() {
+        let now = Instant::now();
+        let limit = RateLimit::new(10, Duration::from_secs(1)).with_burst(3);
+        let mut bucket = TokenBucket::new(limit, now);
+        assert!(bucket.try_take(now).is_ok());
+        assert!(bucket.try_take(now).is_ok());
+        assert!(bucket.try_take(now).is_ok());
+        let wait = bucket.try_take(now).unwrap_err();
+        assert!(wait <= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_rate() {
+        let start = Instant::now();
+        let limit = RateLimit::new(20, Duration::from_secs(1)).with_burst(1);
+        let mut bucket = TokenBucket::new(limit, start);
+        // first token is available immediately, then one every 50ms
+        let mut now = start;
+        for _ in 0..11 {
+            while let Err(wait) = bucket.try_take(now) {
+                now += wait;
+            }
+        }
+        assert_eq!(now - start, Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_keyed() {
+        let now = Instant::now();
+        let limiter =
+            RateLimiter::new(None, Some(RateLimit::new(1, Duration::from_secs(10))), now).unwrap();
+        // each key has its own bucket
+        assert!(limiter.try_acquire(Some(1), now).is_ok());
+        assert!(limiter.try_acquire(Some(2), now).is_ok());
+        assert!(limiter.try_acquire(None, now).is_ok());
+        assert!(limiter.try_acquire(None, now).is_ok());
+        assert!(limiter.try_acquire(Some(1), now).is_err());
+        let later = now + Duration::from_secs(10);
+        assert!(limiter.try_acquire(Some(1), later).is_ok());
+    }
+
+    #[test]
+    fn test_keyed_and_global() {
+        let now = Instant::now();
+        let limiter = RateLimiter::new(
+            Some(RateLimit::new(1, Duration::from_secs(1))),
+            Some(RateLimit::new(1, Duration::from_secs(10))),
+            now,
+        )
+        .unwrap();
+        assert!(limiter.try_acquire(Some(1), now).is_ok());
+        // the global bucket is empty, so no token is taken from the bucket for key 2
+        let wait = limiter.try_acquire(Some(2), now).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(1));
+        assert!(limiter.try_acquire(Some(2), now + wait).is_ok());
+    }
+
+    #[test]
+    fn test_prune() {
+        let now = Instant::now();
+        let limiter =
+            RateLimiter::new(None, Some(RateLimit::new(1, Duration::from_secs(1))), now).unwrap();
+        for key in 0..65 {
+            assert!(limiter.try_acquire(Some(key), now).is_ok());
+        }
+        assert_eq!(limiter.keyed.lock().buckets.len(), 65);
+        // once the buckets have refilled, they are discarded the next time the number of buckets
+        // exceeds the (doubled) threshold
+        let later = now + Duration::from_secs(1);
+        for key in 100..166 {
+            assert!(limiter.try_acquire(Some(key), later).is_ok());
+        }
+        assert_eq!(limiter.keyed.lock().buckets.len(), 66);
+    }
+
+    #[test]
+    fn test_deferred() {
+        let now = Instant::now();
+        let mut deferred = RateDeferred::default();
+        deferred.push(1, now + Duration::from_millis(20));
+        deferred.push(2, now + Duration::from_millis(10));
+        assert_eq!(deferred.next_ready(), Some(now + Duration::from_millis(10)));
+        assert_eq!(deferred.pop_ready(now, |_| Ok(())), None);
+        let later = now + Duration::from_millis(20);
+        // item 1 is not permitted, so it is deferred again
+        let item = deferred.pop_ready(later, |item| match item {
+            1 => Err(Duration::from_millis(5)),
+            _ => Ok(()),
+        });
+        assert_eq!(item, Some(2));
+        assert_eq!(
+            deferred.next_ready(),
+            Some(later + Duration::from_millis(5))
+        );
+        assert_eq!(deferred.drain().collect::<Vec<_>>(), vec![1]);
+        assert!(deferred.is_empty());
+    }
+
+    #[test]
+    fn test_none() {
+        assert!(RateLimiter::new(None, None, Instant::now()).is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_zero() {
+        RateLimit::new(0, Duration::from_secs(1));
+    }
+}
@@ -1,14 +1,26 @@
+mod apiary;
 mod builder;
+mod clock;
 mod config;
 mod counter;
 mod gate;
+mod graph;
+mod group;
 #[allow(clippy::module_inception)]
 mod hive;
 mod husk;
+mod limit;
 mod outcome;
+mod par;
+mod pipeline;
+mod policy;
+mod progress;
+mod retention;
 // TODO: scoped hive is still a WIP
 //mod scoped;
 mod shared;
+mod stored;
+mod stream;
 mod task;
 
 #[cfg(feature = "affinity")]
@@ -16,13 +28,26 @@ pub mod cores;
 #[cfg(feature = "retry")]
 mod delay;
 
+pub use apiary::{Apiary, ApiaryError};
 pub use builder::Builder;
+pub(crate) use clock::ClockRef;
+pub use clock::{Clock, ManualClock, SystemClock};
 pub use config::{reset_defaults, set_num_threads_default, set_num_threads_default_all};
 #[cfg(feature = "retry")]
 pub use config::{set_max_retries_default, set_retries_default_disabled, set_retry_factor_default};
-pub use hive::SpawnError;
+pub use graph::{GraphError, NodeOutcome, Predecessors, TaskGraph};
+pub use group::TaskGroupStats;
+pub use hive::{ResubmitMode, ShutdownMode, SpawnError};
 pub use husk::Husk;
-pub use outcome::{Outcome, OutcomeBatch, OutcomeIteratorExt, OutcomeStore};
+pub use limit::RateLimit;
+pub use outcome::{Outcome, OutcomeBatch, OutcomeIteratorExt, OutcomeStore, OutcomeSummary};
+pub use par::{HiveIteratorExt, ParHive, ParIter};
+pub use pipeline::{Pipeline, PipelineBuilder, PipelineFailure, Stages};
+pub use policy::PanicPolicy;
+pub use progress::TaskProgress;
+pub use retention::StoreLimitPolicy;
+
+pub(crate) use progress::ProgressTracker;
 
 pub type OutcomeSender<W> = crate::channel::Sender<Outcome<W>>;
 pub type OutcomeReceiver<W> = crate::channel::Receiver<Outcome<W>>;
@@ -34,33 +59,37 @@ pub fn outcome_channel<W: Worker>() -> (OutcomeSender<W>, OutcomeReceiver<W>) {
 
 pub mod prelude {
     pub use super::{
-        outcome_channel, Builder, Hive, Husk, Outcome, OutcomeBatch, OutcomeIteratorExt,
-        OutcomeStore, SpawnError,
+        outcome_channel, Builder, Hive, HiveIteratorExt, Husk, Outcome, OutcomeBatch,
+        OutcomeIteratorExt, OutcomeStore, SpawnError,
     };
 }
 
 use self::counter::DualCounter;
 use self::outcome::{DerefOutcomes, OwnedOutcomes};
-use crate::atomic::{AtomicAny, AtomicBool, AtomicOption, AtomicUsize};
+use crate::atomic::{AtomicAny, AtomicBool, AtomicOption, AtomicU32, AtomicUsize};
 use crate::bee::{Context, Queen, Worker};
 use gate::{Gate, PhasedGate};
 use parking_lot::Mutex;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 type TaskSender<W> = std::sync::mpsc::Sender<Task<W>>;
 type TaskReceiver<W> = std::sync::mpsc::Receiver<Task<W>>;
+type Bool = AtomicOption<bool, AtomicBool>;
+type U32 = AtomicOption<u32, AtomicU32>;
 type Usize = AtomicOption<usize, AtomicUsize>;
 type Any<T> = AtomicOption<T, AtomicAny<T>>;
+type RateLimitKeyFn<W> = Box<dyn Fn(&<W as Worker>::Input) -> Option<u64> + Send + Sync>;
+type StoreLimitFn = Box<dyn Fn(usize) + Send + Sync>;
 
 #[cfg(feature = "retry")]
 mod retry_prelude {
     pub use parking_lot::RwLock;
     pub use std::time::Instant;
 
-    use crate::atomic::{AtomicOption, AtomicU32, AtomicU64};
+    use crate::atomic::{AtomicOption, AtomicU64};
 
-    pub type U32 = AtomicOption<u32, AtomicU32>;
     pub type U64 = AtomicOption<u64, AtomicU64>;
 }
 #[cfg(feature = "retry")]
@@ -79,7 +108,8 @@ use retry_prelude::*;
 /// retained in the `Hive` for later retrieval.
 ///
 /// A `Worker` should never panic, but if it does, the worker thread will terminate and the `Hive`
-/// will spawn a new worker thread with a new `Worker`.
+/// will spawn a new worker thread with a new `Worker`. This behavior can be changed by setting a
+/// [`PanicPolicy`] on the `Builder`.
 ///
 /// When a `Hive` is dropped, all the worker threads are terminated automatically. Prior to
 /// dropping the `Hive`, the `try_into_husk()` method can be called to retrieve all of the `Hive` data
@@ -116,9 +146,34 @@ struct Config {
     /// Multiplier for the retry backoff strategy
     #[cfg(feature = "retry")]
     retry_factor: U64,
+    /// Source of time used for retries, rate limits, and timeouts
+    clock: Any<clock::ClockRef>,
     /// CPU cores to which worker threads can be pinned
     #[cfg(feature = "affinity")]
     affinity: Any<cores::Cores>,
+    /// Maximum rate at which tasks are started across all worker threads
+    rate_limit: Any<RateLimit>,
+    /// Maximum number of tasks started at once after a period of inactivity under `rate_limit`
+    rate_limit_burst: U32,
+    /// Maximum rate at which tasks with the same rate-limit key are started
+    key_rate_limit: Any<RateLimit>,
+    /// Maximum number of active tasks in each named task group
+    group_limits: Any<HashMap<String, usize>>,
+    /// How the hive responds when a worker panics
+    panic_policy: Any<PanicPolicy>,
+    /// Whether tasks are executed on the submitting thread rather than by worker threads
+    deterministic: Bool,
+    /// Maximum number of tasks a worker thread executes as a single batch
+    batch_size: Usize,
+    /// Maximum time a worker thread waits for additional tasks to fill a batch
+    batch_timeout: Any<Duration>,
+    /// Maximum number of outcomes stored in the hive
+    max_stored_outcomes: Usize,
+    /// What the hive does when the maximum number of stored outcomes is reached
+    store_limit_policy: Any<StoreLimitPolicy>,
+    /// Index assigned to the first task submitted to the hive; set when the hive is converted
+    /// into a `Husk` so that a hive created from the `Husk` continues the sequence of indices
+    next_task_index: Usize,
 }
 
 /// Data shared by all worker threads in a `Hive`.
@@ -138,6 +193,9 @@ struct Shared<W: Worker, Q: Queen<Kind = W>> {
     // processed (new tasks may be queued but they will never be processed); currently, this can
     // only happen if the task counter somehow get corrupted
     poisoned: AtomicBool,
+    // whether the hive has been closed - if true, new tasks are rejected (i.e., converted to
+    // `Outcome::Unprocessed`), but queued tasks are still processed
+    closed: AtomicBool,
     // whether the hive is suspended - if true, active tasks may complete and new tasks may be
     // queued, but new tasks will not be processed
     suspended: Arc<AtomicBool>,
@@ -147,33 +205,68 @@ struct Shared<W: Worker, Q: Queen<Kind = W>> {
     join_gate: PhasedGate,
     // outcomes stored in the hive
     outcomes: Mutex<HashMap<usize, Outcome<W>>>,
+    // gate used by client threads to wait until specific outcomes have been stored, and by worker
+    // threads to wait until there is room to store an outcome
+    stored_gate: Gate,
+    // enforces the maximum number of stored outcomes, if there is one
+    store_limiter: Option<retention::StoreLimiter>,
+    // channel to which outcomes are sent when they are evicted from the store
+    store_overflow: Mutex<Option<OutcomeSender<W>>>,
+    // function that is called when the maximum number of stored outcomes is reached
+    store_limit_handler: parking_lot::RwLock<Option<StoreLimitFn>>,
+    // link to the `Apiary` with which the hive is registered, if any
+    apiary: parking_lot::RwLock<Option<apiary::ApiaryLink>>,
+    // token buckets consulted before starting each task, if the hive is rate-limited
+    rate_limiter: Option<limit::RateLimiter>,
+    // function that maps a task input to the key of its rate limit
+    rate_limit_key: parking_lot::RwLock<Option<RateLimitKeyFn<W>>>,
+    // tasks that were not yet permitted to start by the rate limiter
+    rate_deferred: Mutex<limit::RateDeferred<Task<W>>>,
+    // gate used by worker threads to wait for deferred tasks when there are no new tasks
+    deferred_gate: Gate,
+    // named task groups, including tasks that are deferred because their group is saturated
+    groups: Mutex<group::TaskGroups<Task<W>>>,
+    // progress reported by active tasks
+    progress: Arc<ProgressTracker>,
+    // applies the panic policy
+    panic_monitor: policy::PanicMonitor,
+    // held by the thread that is executing tasks, if the hive is deterministic
+    inline_lock: Mutex<()>,
+    // set by a thread that submitted tasks while another thread held the inline worker
+    inline_pending: AtomicBool,
     // queue used for tasks that are waiting to be retried after a failure
     #[cfg(feature = "retry")]
     retry_queue: Mutex<delay::DelayQueue<Task<W>>>,
     // the next time at which a task will be ready to be retried
     #[cfg(feature = "retry")]
     next_retry: RwLock<Option<Instant>>,
+    // source of time used for retries, rate limits, and timeouts
+    clock: clock::ClockRef,
 }
 
 #[cfg(test)]
 mod test {
-    use super::{Builder, Hive, Outcome, OutcomeIteratorExt, OutcomeStore};
+    use super::{
+        Builder, Hive, ManualClock, Outcome, OutcomeBatch, OutcomeIteratorExt, OutcomeStore,
+        PanicPolicy, RateLimit, ResubmitMode, ShutdownMode,
+    };
     use crate::bee::stock::{Caller, OnceCaller, RefCaller, Thunk, ThunkWorker};
     use crate::bee::{
-        ApplyError, ApplyRefError, Context, DefaultQueen, Queen, RefWorker, RefWorkerResult,
-        Worker, WorkerResult,
+        ApplyError, ApplyRefError, CloneQueen, Context, DefaultQueen, Queen, RefWorker,
+        RefWorkerResult, Worker, WorkerResult,
     };
     use crate::channel::{Message, ReceiverExt};
     use crate::hive::outcome::DerefOutcomes;
     use std::fmt::Debug;
     use std::io::{self, BufRead, BufReader, Write};
+    use std::panic::AssertUnwindSafe;
     use std::process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Stdio};
     use std::sync::{
-        atomic::{AtomicUsize, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         mpsc, Arc, Barrier,
     };
     use std::thread;
-    use std::time::Duration;
+    use std::time::{Duration, Instant};
 
     const TEST_TASKS: usize = 4;
     const ONE_SEC: Duration = Duration::from_secs(1);
@@ -376,6 +469,343 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_deterministic() {
+        let hive = Builder::new()
+            .num_threads(TEST_TASKS)
+            .deterministic()
+            .build_with_default::<ThunkWorker<(usize, thread::ThreadId)>>()
+            .unwrap();
+        let order = Arc::new(AtomicUsize::new(0));
+        let outputs = hive
+            .map((0..10).map(|i| {
+                let order = Arc::clone(&order);
+                Thunk::of(move || {
+                    assert_eq!(order.fetch_add(1, Ordering::SeqCst), i);
+                    (i, thread::current().id())
+                })
+            }))
+            .map(Outcome::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(outputs.len(), 10);
+        assert!(outputs
+            .into_iter()
+            .all(|(_, thread_id)| thread_id == thread::current().id()));
+        assert_eq!(hive.num_tasks(), (0, 0));
+    }
+
+    #[test]
+    fn test_deterministic_suspend() {
+        let hive = Builder::new()
+            .deterministic()
+            .build_with_default::<ThunkWorker<u8>>()
+            .unwrap();
+        hive.suspend();
+        let indices = hive.map_store((0..5).map(|i| Thunk::of(move || i)));
+        assert_eq!(hive.num_tasks(), (5, 0));
+        assert!(hive.outcomes_deref().is_empty());
+        hive.resume();
+        assert_eq!(hive.num_tasks(), (0, 0));
+        let outcomes = hive.take_stored();
+        assert!(indices.iter().all(|index| outcomes[index].is_success()));
+    }
+
+    #[test]
+    fn test_deterministic_panic() {
+        let hive = Builder::new()
+            .deterministic()
+            .build_with_default::<ThunkWorker<u8>>()
+            .unwrap();
+        let outcome = hive.apply(Thunk::of(|| panic!("intentional panic")));
+        assert!(matches!(outcome, Outcome::Missing { .. }));
+        assert_eq!(hive.num_panics(), 1);
+        assert!(hive.apply(Thunk::of(|| 1)).is_success());
+        assert_eq!(hive.num_tasks(), (0, 0));
+    }
+
+    /// Fails with odd inputs while the flag is `true`.
+    #[derive(Clone, Debug)]
+    struct FlakyWorker(Arc<AtomicBool>);
+
+    impl Worker for FlakyWorker {
+        type Input = usize;
+        type Output = usize;
+        type Error = ();
+
+        fn apply(&mut self, i: Self::Input, _: &Context) -> WorkerResult<Self> {
+            if i % 2 == 1 && self.0.load(Ordering::SeqCst) {
+                Err(ApplyError::Fatal {
+                    input: Some(i),
+                    error: (),
+                })
+            } else {
+                Ok(i)
+            }
+        }
+    }
+
+    fn flaky_hive(flaky: &Arc<AtomicBool>) -> Hive<FlakyWorker, CloneQueen<FlakyWorker>> {
+        Builder::new()
+            .num_threads(2)
+            .build_with(FlakyWorker(Arc::clone(flaky)))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_resubmit_failures() {
+        let flaky = Arc::new(AtomicBool::new(true));
+        let hive = flaky_hive(&flaky);
+        let mut outcomes: OutcomeBatch<_> = hive.map(0..6).into();
+        assert_eq!(outcomes.num_failures(), 3);
+        flaky.store(false, Ordering::SeqCst);
+        let indices = hive.resubmit_failures(&mut outcomes);
+        assert_eq!(indices.len(), 3);
+        assert_eq!(outcomes.num_failures(), 0);
+        hive.join();
+        let mut outputs: Vec<_> = hive
+            .take_stored()
+            .into_values()
+            .map(Outcome::unwrap)
+            .collect();
+        outputs.sort();
+        assert_eq!(outputs, vec![1, 3, 5]);
+    }
+
+    #[test]
+    fn test_resubmit_failures_original_indices() {
+        let flaky = Arc::new(AtomicBool::new(true));
+        let hive = flaky_hive(&flaky);
+        let indices = hive.map_store(0..6);
+        hive.join();
+        flaky.store(false, Ordering::SeqCst);
+        let resubmitted = hive.resubmit_failures_with(
+            &mut hive.clone(),
+            |outcome| *outcome.index() != indices[5],
+            ResubmitMode::OriginalIndices,
+        );
+        assert_eq!(resubmitted, vec![indices[1], indices[3]]);
+        hive.join();
+        let mut outcomes = hive.take_stored();
+        assert!(outcomes.remove(&indices[5]).unwrap().is_failure());
+        let outputs: Vec<_> = indices[..5]
+            .iter()
+            .map(|index| outcomes.remove(index).unwrap().unwrap())
+            .collect();
+        assert_eq!(outputs, (0..5).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_husk_into_hive_resubmit() {
+        let flaky = Arc::new(AtomicBool::new(true));
+        let hive = flaky_hive(&flaky);
+        let indices = hive.map_store(0..6);
+        let husk = hive.try_into_husk().unwrap();
+        flaky.store(false, Ordering::SeqCst);
+        let (hive, resubmitted) = husk.into_hive_resubmit(
+            |outcome| *outcome.index() == indices[1],
+            ResubmitMode::NewIndices,
+        );
+        // the new index does not conflict with the outcomes carried over from the husk
+        assert_eq!(resubmitted.len(), 1);
+        assert!(!indices.contains(&resubmitted[0]));
+        hive.join();
+        assert_eq!(hive.num_successes(), 4);
+        assert_eq!(hive.num_failures(), 2);
+        assert_eq!(
+            hive.take_stored().remove(&resubmitted[0]).unwrap().unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_max_stored_outcomes() {
+        let hive = Builder::new()
+            .num_threads(1)
+            .max_stored_outcomes(3)
+            .build_with(Caller::of(|i: usize| i))
+            .unwrap();
+        let num_warnings = Arc::new(AtomicUsize::new(0));
+        let num_warnings_clone = Arc::clone(&num_warnings);
+        hive.set_store_limit_handler(move |max| {
+            assert_eq!(max, 3);
+            num_warnings_clone.fetch_add(1, Ordering::SeqCst);
+        });
+        let indices = hive.map_store(0..10);
+        hive.join();
+        // with a single thread, the outcomes are stored in the same order as the tasks
+        let mut stored: Vec<_> = hive.take_stored().into_keys().collect();
+        stored.sort();
+        assert_eq!(stored, indices[7..]);
+        assert_eq!(hive.num_dropped_outcomes(), 7);
+        assert_eq!(num_warnings.load(Ordering::SeqCst), 1);
+        // the handler is called again once the limit is reached after outcomes have been removed
+        hive.map_store(0..3);
+        hive.join();
+        assert_eq!(num_warnings.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_store_limit_block() {
+        let hive = Builder::new()
+            .num_threads(2)
+            .max_stored_outcomes(2)
+            .store_limit_policy(super::StoreLimitPolicy::Block)
+            .build_with(Caller::of(|i: usize| i))
+            .unwrap();
+        let mut indices = hive.map_store(0..6);
+        thread::sleep(ONE_SEC);
+        // both threads are blocked waiting for room to store their outcomes
+        assert_eq!(hive.num_tasks(), (2, 2));
+        // the blocked tasks are still active, so `join` would block forever
+        assert!(!hive.join_timeout(Duration::from_millis(100)));
+        let mut stored = Vec::new();
+        let deadline = Instant::now() + LONG_TASK;
+        while stored.len() < 6 && Instant::now() < deadline {
+            let outcomes = hive.take_stored();
+            assert!(outcomes.len() <= 2);
+            stored.extend(outcomes.into_keys());
+            thread::sleep(Duration::from_millis(10));
+        }
+        hive.join();
+        stored.sort();
+        indices.sort();
+        assert_eq!(stored, indices);
+        assert_eq!(hive.num_dropped_outcomes(), 0);
+    }
+
+    #[test]
+    fn test_store_limit_overflow() {
+        let hive = Builder::new()
+            .num_threads(1)
+            .max_stored_outcomes(2)
+            .store_limit_policy(super::StoreLimitPolicy::Overflow)
+            .build_with(Caller::of(|i: usize| i))
+            .unwrap();
+        let (tx, rx) = super::outcome_channel();
+        hive.set_store_overflow(tx);
+        let indices = hive.map_store(0..5);
+        hive.join();
+        let overflow: Vec<_> = rx.try_iter().map(|outcome| *outcome.index()).collect();
+        assert_eq!(overflow, indices[..3]);
+        let mut stored: Vec<_> = hive.take_stored().into_keys().collect();
+        stored.sort();
+        assert_eq!(stored, indices[3..]);
+        assert_eq!(hive.num_dropped_outcomes(), 0);
+    }
+
+    #[test]
+    fn test_panic_policy_respawn_max() {
+        let hive = Builder::new()
+            .num_threads(1)
+            .panic_policy(PanicPolicy::RespawnMax(2))
+            .build_with_default::<ThunkWorker<()>>()
+            .unwrap();
+        let (tx, _) = super::outcome_channel();
+        for _ in 0..2 {
+            hive.apply_send(Thunk::of(|| panic!("intentional panic")), tx.clone());
+        }
+        hive.join();
+        assert_eq!(hive.num_panics(), 2);
+        assert!(!hive.is_poisoned());
+        hive.apply_send(Thunk::of(|| panic!("intentional panic")), tx.clone());
+        hive.join();
+        assert_eq!(hive.num_panics(), 3);
+        assert!(hive.is_poisoned());
+    }
+
+    #[test]
+    fn test_panic_policy_caught() {
+        let hive = Builder::new()
+            .num_threads(TEST_TASKS)
+            .panic_policy(PanicPolicy::RespawnWithin {
+                max_panics: 1,
+                window: LONG_TASK,
+            })
+            .build_with(RefCaller::of(|_: &u8| -> Result<u8, String> {
+                panic!("intentional panic")
+            }))
+            .unwrap();
+        assert!(matches!(hive.apply(0), Outcome::Panic { .. }));
+        assert!(!hive.is_poisoned());
+        assert!(matches!(hive.apply(1), Outcome::Panic { .. }));
+        assert!(hive.is_poisoned());
+        // caught panics are not included in the count of thread panics
+        assert_eq!(hive.num_panics(), 0);
+    }
+
+    #[test]
+    fn test_panic_policy_stop() {
+        let hive = Builder::new()
+            .num_threads(1)
+            .panic_policy(PanicPolicy::Stop)
+            .build_with(RefCaller::of(|i: &u8| -> Result<u8, String> {
+                if *i == 0 {
+                    panic!("intentional panic")
+                }
+                Ok(*i)
+            }))
+            .unwrap();
+        let indices = hive.swarm_store(0..2u8);
+        thread::sleep(ONE_SEC);
+        // the only thread stopped after the first task panicked
+        assert_eq!(hive.num_tasks(), (1, 0));
+        hive.grow(1);
+        hive.join();
+        let outcomes = hive.take_stored();
+        assert!(matches!(outcomes[&indices[0]], Outcome::Panic { .. }));
+        assert!(outcomes[&indices[1]].is_success());
+    }
+
+    #[test]
+    fn test_panic_policy_propagate() {
+        let hive = Builder::new()
+            .num_threads(TEST_TASKS)
+            .panic_policy(PanicPolicy::Propagate)
+            .build_with_default::<ThunkWorker<u8>>()
+            .unwrap();
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            hive.apply(Thunk::of(|| panic!("intentional panic")))
+        }));
+        assert!(result.is_err());
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            hive.map((0..4u8).map(|i| {
+                Thunk::of(move || {
+                    if i == 2 {
+                        panic!("intentional panic")
+                    }
+                    i
+                })
+            }))
+            .count()
+        }));
+        assert!(result.is_err());
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            hive.apply_in_group("group", Thunk::of(|| panic!("intentional panic")))
+        }));
+        assert!(result.is_err());
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let inputs = (0..4u8).map(|i| {
+                Thunk::of(move || {
+                    if i == 2 {
+                        panic!("intentional panic")
+                    }
+                    i
+                })
+            });
+            hive.map_reduce(inputs, || 0, |a, b| a + b)
+        }));
+        assert!(result.is_err());
+        // the worker threads survive and outcomes that are not returned directly are unaffected
+        assert_eq!(hive.num_panics(), 0);
+        assert_eq!(hive.apply(Thunk::of(|| 1)).unwrap(), 1);
+        let index = hive.apply_store(Thunk::of(|| panic!("intentional panic")));
+        hive.join();
+        assert!(matches!(
+            hive.take_stored().remove(&index),
+            Some(Outcome::Panic { .. })
+        ));
+    }
+
     #[test]
     fn test_should_not_panic_on_drop_if_subtasks_panic_after_drop() {
         let hive = thunk_hive(TEST_TASKS);
@@ -840,6 +1270,378 @@ mod test {
         assert_eq!(indices, outcome_indices);
     }
 
+    #[test]
+    fn test_rate_limit() {
+        let hive = Builder::new()
+            .num_threads(4)
+            .rate_limit(20, Duration::from_secs(1))
+            .rate_limit_burst(1)
+            .build_with_default::<ThunkWorker<()>>()
+            .unwrap();
+        let start = std::time::Instant::now();
+        hive.map_store((0..11).map(|_| Thunk::of(|| ())));
+        // tasks waiting for a token are not active
+        thread::sleep(Duration::from_millis(10));
+        let (queued, active) = hive.num_tasks();
+        assert!(queued > 4);
+        assert_eq!(active, 0);
+        hive.join();
+        // the first task starts immediately, then one every 50ms
+        assert!(start.elapsed() >= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_key_rate_limit() {
+        let hive = Builder::new()
+            .num_threads(4)
+            .key_rate_limit(RateLimit::new(1, Duration::from_secs(1)).with_burst(1))
+            .build_with(Caller::of(|i: usize| i))
+            .unwrap();
+        hive.set_rate_limit_key(|i: &usize| if *i < 2 { None } else { Some(*i % 2) });
+        let start = std::time::Instant::now();
+        // inputs 0 and 1 have no key and 2 and 3 have different keys, so none has to wait
+        hive.map_store(0..4);
+        hive.join();
+        assert!(start.elapsed() < Duration::from_millis(500));
+        // 4 and 5 share keys with 2 and 3, so they have to wait for new tokens
+        hive.map_store(4..6);
+        hive.join();
+        assert!(start.elapsed() >= Duration::from_millis(900));
+        assert_eq!(hive.take_stored().len(), 6);
+    }
+
+    #[test]
+    fn test_key_rate_limit_no_blocking() {
+        let hive = Builder::new()
+            .num_threads(1)
+            .key_rate_limit(RateLimit::new(1, Duration::from_secs(60)).with_burst(1))
+            .build_with(Caller::of(|i: usize| i))
+            .unwrap();
+        hive.set_rate_limit_key(|i: &usize| Some((*i % 2) as u64));
+        let (tx, rx) = super::outcome_channel();
+        // the second task has to wait for a token, but it does not keep the only worker thread
+        // from starting the third task, which has a different key
+        hive.swarm_send([0, 2, 1], tx);
+        let mut values: Vec<_> = (0..2)
+            .map(|_| rx.recv_timeout(ONE_SEC).unwrap().unwrap())
+            .collect();
+        values.sort();
+        assert_eq!(values, vec![0, 1]);
+        assert_eq!(hive.num_tasks(), (1, 0));
+    }
+
+    #[test]
+    fn test_rate_limit_burst_first() {
+        let hive = Builder::new()
+            .num_threads(1)
+            .rate_limit_burst(2)
+            .rate_limit(1, Duration::from_secs(60))
+            .build_with(Caller::of(|i: usize| i))
+            .unwrap();
+        let (tx, rx) = super::outcome_channel();
+        hive.swarm_send(0..3, tx);
+        for _ in 0..2 {
+            assert!(rx.recv_timeout(ONE_SEC).is_ok());
+        }
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn test_rate_limit_manual_clock() {
+        let clock = ManualClock::new();
+        let hive = Builder::new()
+            .num_threads(2)
+            .rate_limit(1, Duration::from_secs(60))
+            .rate_limit_burst(1)
+            .clock(clock.clone())
+            .build_with(Caller::of(|i: usize| i))
+            .unwrap();
+        let (tx, rx) = super::outcome_channel();
+        hive.swarm_send(0..2, tx);
+        assert!(rx.recv_timeout(ONE_SEC).is_ok());
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+        // the worker threads notice that the clock has advanced well before the receive timeout
+        clock.advance(Duration::from_secs(60));
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_ok());
+    }
+
+    #[test]
+    fn test_join_timeout_manual_clock() {
+        let clock = ManualClock::new();
+        let done = Arc::new(AtomicBool::new(false));
+        let hive = {
+            let done = done.clone();
+            Builder::new()
+                .num_threads(1)
+                .clock(clock.clone())
+                .build_with(Caller::of(move |_: ()| {
+                    while !done.load(Ordering::Acquire) {
+                        thread::sleep(Duration::from_millis(1));
+                    }
+                }))
+                .unwrap()
+        };
+        hive.apply_store(());
+        let advance = {
+            let clock = clock.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                clock.advance(Duration::from_secs(10));
+            })
+        };
+        let start = Instant::now();
+        assert!(!hive.join_timeout(Duration::from_secs(10)));
+        assert!(start.elapsed() < ONE_SEC);
+        advance.join().unwrap();
+        done.store(true, Ordering::Release);
+        hive.join();
+    }
+
+    #[test]
+    fn test_husk_rate_limit_key() {
+        let hive = Builder::new()
+            .num_threads(1)
+            .key_rate_limit(RateLimit::new(1, Duration::from_secs(1)))
+            .build_with(Caller::of(|i: usize| i))
+            .unwrap();
+        hive.set_rate_limit_key(|i: &usize| Some(*i));
+        let husk = hive.try_into_husk().unwrap();
+        let hive = husk.into_hive().unwrap();
+        assert!(hive.shared().rate_limit_key.read().is_some());
+    }
+
+    #[test]
+    fn test_task_group() {
+        let hive = Builder::new()
+            .num_threads(8)
+            .task_group("db", 2)
+            .build_with_default::<ThunkWorker<()>>()
+            .unwrap();
+        let active = Arc::new(AtomicUsize::new(0));
+        let max_active = Arc::new(AtomicUsize::new(0));
+        for _ in 0..10 {
+            let active = Arc::clone(&active);
+            let max_active = Arc::clone(&max_active);
+            hive.apply_store_in_group(
+                "db",
+                Thunk::of(move || {
+                    let cur = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_active.fetch_max(cur, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(100));
+                    active.fetch_sub(1, Ordering::SeqCst);
+                }),
+            );
+        }
+        // tasks outside the saturated group are not blocked
+        let (tx, rx) = super::outcome_channel();
+        for _ in 0..10 {
+            hive.apply_send(Thunk::of(|| ()), tx.clone());
+        }
+        assert_eq!(rx.iter().take(10).count(), 10);
+        let stats = &hive.group_stats()["db"];
+        assert_eq!(stats.max_concurrency, Some(2));
+        assert!(stats.completed < 10);
+        hive.join();
+        assert_eq!(max_active.load(Ordering::SeqCst), 2);
+        let stats = &hive.group_stats()["db"];
+        assert_eq!(stats.active, 0);
+        assert_eq!(stats.deferred, 0);
+        assert_eq!(stats.completed, 10);
+    }
+
+    #[test]
+    fn test_task_group_rate_limit() {
+        let hive = Builder::new()
+            .num_threads(1)
+            .task_group("db", 1)
+            .key_rate_limit(RateLimit::new(1, Duration::from_secs(60)).with_burst(1))
+            .build_with(Caller::of(|i: usize| i))
+            .unwrap();
+        hive.set_rate_limit_key(|i: &usize| Some((*i % 2) as u64));
+        let (tx, rx) = super::outcome_channel();
+        // the second task has to wait for a token, but it does not hold the group's only slot
+        // while it waits, so the third task (which has a different key) is started
+        for i in [0, 2, 1] {
+            hive.apply_send_in_group("db", i, tx.clone());
+        }
+        let mut values: Vec<_> = (0..2)
+            .map(|_| rx.recv_timeout(ONE_SEC).unwrap().unwrap())
+            .collect();
+        values.sort();
+        assert_eq!(values, vec![0, 1]);
+        // the group slot is released shortly after the outcome is sent
+        thread::sleep(Duration::from_millis(100));
+        let stats = &hive.group_stats()["db"];
+        assert_eq!(stats.active, 0);
+        assert_eq!(stats.deferred, 0);
+    }
+
+    #[test]
+    fn test_task_group_drain() {
+        let hive = Builder::new()
+            .num_threads(2)
+            .task_group("db", 2)
+            .rate_limit(1, Duration::from_secs(60))
+            .rate_limit_burst(1)
+            .build_with_default::<ThunkWorker<()>>()
+            .unwrap();
+        let done = Arc::new(AtomicBool::new(false));
+        {
+            let done = Arc::clone(&done);
+            hive.apply_store_in_group(
+                "db",
+                Thunk::of(move || {
+                    while !done.load(Ordering::Acquire) {
+                        thread::sleep(Duration::from_millis(1));
+                    }
+                }),
+            );
+        }
+        // the other tasks are deferred because there are no more tokens - they do not take slots
+        // in the group while they wait
+        hive.apply_store_in_group("db", Thunk::of(|| ()));
+        hive.apply_store_in_group("db", Thunk::of(|| ()));
+        thread::sleep(Duration::from_millis(100));
+        let stats = &hive.group_stats()["db"];
+        assert_eq!(stats.active, 1);
+        assert_eq!(stats.deferred, 0);
+        // draining the deferred tasks leaves only the active task holding a slot in the group
+        hive.shared().poison();
+        let stats = &hive.group_stats()["db"];
+        assert_eq!(stats.active, 1);
+        assert_eq!(stats.deferred, 0);
+        // `join` returns immediately once the hive is poisoned
+        done.store(true, Ordering::Release);
+        thread::sleep(Duration::from_millis(100));
+        let stats = &hive.group_stats()["db"];
+        assert_eq!(stats.active, 0);
+        assert_eq!(stats.completed, 1);
+        assert_eq!(
+            hive.take_stored()
+                .values()
+                .filter(|o| o.is_unprocessed())
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_task_group_context() {
+        #[derive(Debug, Default)]
+        struct GroupWorker;
+
+        impl Worker for GroupWorker {
+            type Input = ();
+            type Output = Option<String>;
+            type Error = ();
+
+            fn apply(&mut self, _: Self::Input, ctx: &Context) -> WorkerResult<Self> {
+                Ok(ctx.group().map(String::from))
+            }
+        }
+
+        let hive = Builder::new()
+            .num_threads(2)
+            .build_with_default::<GroupWorker>()
+            .unwrap();
+        assert_eq!(hive.apply_in_group("cpu", ()).unwrap(), Some("cpu".into()));
+        assert_eq!(hive.apply(()).unwrap(), None);
+        // the group slot is released after the outcome is sent
+        hive.join();
+        let stats = &hive.group_stats()["cpu"];
+        assert_eq!(stats.max_concurrency, None);
+        assert_eq!(stats.completed, 1);
+    }
+
+    #[test]
+    fn test_progress() {
+        #[derive(Debug, Default)]
+        struct ProgressWorker;
+
+        impl Worker for ProgressWorker {
+            type Input = u64;
+            type Output = ();
+            type Error = ();
+
+            fn apply(&mut self, input: Self::Input, ctx: &Context) -> WorkerResult<Self> {
+                ctx.report_progress(0.5, format!("task {input} halfway"));
+                thread::sleep(Duration::from_millis(input));
+                ctx.report_progress(1.0, "done");
+                Ok(())
+            }
+        }
+
+        let hive = Builder::new()
+            .num_threads(2)
+            .thread_name("progress")
+            .build_with_default::<ProgressWorker>()
+            .unwrap();
+        let indices = hive.swarm_store([1000u64, 1000]);
+        thread::sleep(Duration::from_millis(500));
+        let progress = hive.progress();
+        assert_eq!(progress.len(), 2);
+        for index in indices.iter() {
+            let task = &progress[index];
+            assert_eq!(task.fraction, Some(0.5));
+            assert_eq!(task.message.as_deref(), Some("task 1000 halfway"));
+            assert!(task.thread_index < 2);
+            assert_eq!(task.thread_name.as_deref(), Some("progress"));
+            assert!(task.elapsed >= Duration::from_millis(400));
+        }
+        assert_ne!(
+            progress[&indices[0]].thread_index,
+            progress[&indices[1]].thread_index
+        );
+        hive.join();
+        assert!(hive.progress().is_empty());
+    }
+
+    #[test]
+    fn test_progress_manual_clock() {
+        let clock = ManualClock::new();
+        let done = Arc::new(AtomicBool::new(false));
+        let hive = {
+            let done = Arc::clone(&done);
+            Builder::new()
+                .num_threads(1)
+                .clock(clock.clone())
+                .build_with(Caller::of(move |_: ()| {
+                    while !done.load(Ordering::Acquire) {
+                        thread::sleep(Duration::from_millis(1));
+                    }
+                }))
+                .unwrap()
+        };
+        let index = hive.apply_store(());
+        thread::sleep(Duration::from_millis(100));
+        // the elapsed time is measured by the hive's clock
+        assert_eq!(hive.progress()[&index].elapsed, Duration::ZERO);
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(hive.progress()[&index].elapsed, Duration::from_secs(5));
+        done.store(true, Ordering::Release);
+        hive.join();
+    }
+
+    #[test]
+    fn test_map_reduce() {
+        let hive = Builder::new()
+            .num_threads(4)
+            .build_with(OnceCaller::of(
+                |i: u64| if i == 50 { Err(i) } else { Ok(i) },
+            ))
+            .unwrap();
+        let (sum, failures) = hive.map_reduce(0..100, || 0, |a, b| a + b);
+        assert_eq!(sum, 4950 - 50);
+        assert_eq!(failures.num_failures(), 1);
+        assert!(matches!(
+            failures.iter_failures().next().unwrap(),
+            Outcome::Failure { error: 50, .. }
+        ));
+        assert_eq!(failures.num_successes(), 0);
+        // nothing is stored in the hive
+        assert!(hive.take_stored().is_empty());
+    }
+
     #[test]
     fn test_scan() {
         let hive = Builder::new()
@@ -1096,6 +1898,140 @@ mod test {
         assert_eq!(outputs1, outputs3);
     }
 
+    #[test]
+    fn test_join_timeout() {
+        let hive = thunk_hive(TEST_TASKS);
+        hive.map_store((0..TEST_TASKS).map(|_| Thunk::of(|| thread::sleep(SHORT_TASK))));
+        assert!(!hive.join_timeout(Duration::from_millis(500)));
+        assert_eq!(hive.num_tasks().1, TEST_TASKS as u64);
+        assert!(hive.join_timeout(LONG_TASK));
+        assert_eq!(hive.num_tasks(), (0, 0));
+        // no tasks
+        assert!(hive.join_timeout(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_wait_for() {
+        let hive = thunk_hive::<u64>(2);
+        let indices = hive.map_store((1..=4u64).map(|i| {
+            Thunk::of(move || {
+                thread::sleep(Duration::from_millis(i * 250));
+                i
+            })
+        }));
+        assert!(!hive.wait_for(&indices, Duration::from_millis(100)));
+        assert!(hive.wait_for(&indices[..1], ONE_SEC));
+        assert!(hive.num_successes() < 4);
+        assert!(hive.wait_for(&indices, SHORT_TASK));
+        assert_eq!(hive.num_successes(), 4);
+        assert!(hive.wait_for(&[], Duration::ZERO));
+    }
+
+    #[test]
+    fn test_wait_any() {
+        let hive = thunk_hive::<u64>(2);
+        let indices = hive.map_store([500u64, 100].into_iter().map(|ms| {
+            Thunk::of(move || {
+                thread::sleep(Duration::from_millis(ms));
+                ms
+            })
+        }));
+        assert_eq!(hive.wait_any(&indices), Some(indices[1]));
+        assert_eq!(hive.wait_any(&indices[..1]), Some(indices[0]));
+        assert_eq!(hive.wait_any(&[]), None);
+        // the outcome of a task submitted with an outcome channel is never stored
+        let (tx, _rx) = super::outcome_channel();
+        let index = hive.apply_send(
+            Thunk::of(|| {
+                thread::sleep(Duration::from_millis(100));
+                0
+            }),
+            tx,
+        );
+        assert_eq!(hive.wait_any(&[index]), None);
+    }
+
+    #[test]
+    fn test_close() {
+        let hive = thunk_hive::<u8>(TEST_TASKS);
+        let clone = hive.clone();
+        let indices = hive.map_store((0..8u8).map(|i| {
+            Thunk::of(move || {
+                thread::sleep(Duration::from_millis(100));
+                i
+            })
+        }));
+        assert!(clone.close());
+        assert!(!hive.close());
+        assert!(hive.is_closed());
+        // queued tasks are still processed
+        let rejected = clone.map_store((0..4u8).map(|i| Thunk::of(move || i)));
+        hive.join();
+        assert_eq!(hive.num_tasks(), (0, 0));
+        let outcomes = hive.take_stored();
+        assert!(indices.iter().all(|i| outcomes[i].is_success()));
+        assert!(rejected.iter().all(|i| outcomes[i].is_unprocessed()));
+        let (tx, rx) = super::outcome_channel();
+        let index = hive.apply_send(Thunk::of(|| 0), tx);
+        assert!(matches!(
+            rx.recv().unwrap(),
+            Outcome::Unprocessed { index: i, .. } if i == index
+        ));
+    }
+
+    #[test]
+    fn test_shutdown_drain() {
+        let hive = thunk_hive::<u8>(TEST_TASKS);
+        hive.map_store((0..8u8).map(|i| {
+            Thunk::of(move || {
+                thread::sleep(Duration::from_millis(100));
+                i
+            })
+        }));
+        hive.suspend();
+        let husk = hive.shutdown(ShutdownMode::Drain).unwrap();
+        assert_eq!(husk.num_successes(), 8);
+        assert_eq!(husk.num_unprocessed(), 0);
+    }
+
+    #[test]
+    fn test_shutdown_abort() {
+        let hive = Builder::new()
+            .num_threads(TEST_TASKS)
+            .build_with_default::<MyRefWorker>()
+            .unwrap();
+        hive.swarm_store(0..(2 * TEST_TASKS) as u8);
+        thread::sleep(Duration::from_millis(500));
+        let start = Instant::now();
+        let husk = hive.shutdown(ShutdownMode::Abort).unwrap();
+        // active tasks are cancelled, queued tasks are never started
+        assert!(start.elapsed() < SHORT_TASK);
+        assert_eq!(husk.num_unprocessed(), 2 * TEST_TASKS);
+    }
+
+    #[test]
+    fn test_shutdown_drain_within() {
+        let hive = Builder::new()
+            .num_threads(TEST_TASKS)
+            .build_with_default::<MyRefWorker>()
+            .unwrap();
+        hive.swarm_store(0..(2 * TEST_TASKS) as u8);
+        let husk = hive
+            .shutdown(ShutdownMode::DrainWithin(Duration::from_secs(4)))
+            .unwrap();
+        // the first batch of tasks completes, the second batch is cancelled
+        assert_eq!(husk.num_successes(), TEST_TASKS);
+        assert_eq!(husk.num_unprocessed(), TEST_TASKS);
+    }
+
+    #[test]
+    fn test_shutdown_cloned() {
+        let hive = thunk_hive::<u8>(TEST_TASKS);
+        let clone = hive.clone();
+        assert!(hive.shutdown(ShutdownMode::Abort).is_none());
+        assert!(clone.shutdown(ShutdownMode::Abort).is_some());
+    }
+
     #[test]
     fn test_clone() {
         let hive = Builder::new()
@@ -1473,7 +2409,8 @@ mod affinity_tests {
 mod retry_tests {
     use crate::bee::stock::RetryCaller;
     use crate::bee::{ApplyError, Context};
-    use crate::hive::{Builder, Outcome, OutcomeIteratorExt};
+    use crate::hive::{Builder, ManualClock, Outcome, OutcomeIteratorExt};
+    use std::thread;
     use std::time::{Duration, SystemTime};
 
     fn echo_time(i: usize, ctx: &Context) -> Result<String, ApplyError<usize, String>> {
@@ -1541,6 +2478,68 @@ mod retry_tests {
         assert_eq!(not_retried, 3);
     }
 
+    #[test]
+    fn test_deterministic_retries() {
+        let hive = Builder::new()
+            .deterministic()
+            .max_retries(3)
+            .retry_factor(Duration::from_millis(10))
+            .build_with(RetryCaller::of(echo_time))
+            .unwrap();
+        let outcomes = hive.swarm(0..3).collect::<Vec<_>>();
+        assert!(outcomes.iter().all(Outcome::is_success));
+        assert_eq!(hive.num_tasks(), (0, 0));
+    }
+
+    #[test]
+    fn test_retries_manual_clock() {
+        fn fail_once(i: usize, ctx: &Context) -> Result<usize, ApplyError<usize, String>> {
+            if ctx.attempt() == 0 {
+                Err(ApplyError::Retryable {
+                    input: i,
+                    error: "Retryable".into(),
+                })
+            } else {
+                Ok(i)
+            }
+        }
+
+        let clock = ManualClock::new();
+        let hive = Builder::new()
+            .num_threads(2)
+            .max_retries(1)
+            .retry_factor(Duration::from_secs(60))
+            .clock(clock.clone())
+            .build_with(RetryCaller::of(fail_once))
+            .unwrap();
+        let index = hive.apply_store(0);
+        // the retry is not available until the clock has advanced by the retry factor; the
+        // timeout of `wait_for` is also measured by the clock, so it expires immediately
+        thread::sleep(Duration::from_millis(500));
+        assert!(!hive.wait_for(&[index], Duration::ZERO));
+        assert_eq!(hive.num_tasks(), (1, 0));
+        clock.advance(Duration::from_secs(59));
+        thread::sleep(Duration::from_millis(500));
+        assert!(!hive.wait_for(&[index], Duration::ZERO));
+        clock.advance(Duration::from_secs(1));
+        assert!(hive.wait_for(&[index], Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_deterministic_backoff() {
+        let clock = ManualClock::new();
+        let hive = Builder::new()
+            .deterministic()
+            .max_retries(3)
+            .retry_factor(Duration::from_secs(1))
+            .clock(clock.clone())
+            .build_with(RetryCaller::of(echo_time))
+            .unwrap();
+        assert!(hive.apply(0).is_success());
+        // retries are delayed by 1, 2, and 4 seconds
+        assert_eq!(clock.elapsed(), Duration::from_secs(7));
+    }
+
     #[test]
     fn test_disable_retries() {
         let hive = Builder::new()
@@ -1549,6 +2548,6 @@ mod retry_tests {
             .build_with(RetryCaller::of(echo_time))
             .unwrap();
         let v: Result<Vec<_>, _> = hive.swarm(0..10).into_results().collect();
-        assert!(matches!(v, Err(_)));
+        assert!(v.is_err());
     }
 }
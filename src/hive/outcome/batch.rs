@@ -10,6 +10,38 @@ impl<W: Worker> OutcomeBatch<W> {
     pub(crate) fn new(outcomes: HashMap<usize, Outcome<W>>) -> Self {
         Self(outcomes)
     }
+
+    /// Consumes this batch and returns a new batch in which `f` has been applied to the value of
+    /// each `Outcome::Success`. All other outcomes are unchanged.
+    pub fn map_outputs<F>(self, mut f: F) -> Self
+    where
+        F: FnMut(W::Output) -> W::Output,
+    {
+        Self(
+            self.0
+                .into_iter()
+                .map(|(index, outcome)| match outcome {
+                    Outcome::Success { value, index } => (
+                        index,
+                        Outcome::Success {
+                            value: f(value),
+                            index,
+                        },
+                    ),
+                    outcome => (index, outcome),
+                })
+                .collect(),
+        )
+    }
+
+    /// Moves all the outcomes from `other` into this batch. If both batches contain an outcome
+    /// with the same index, the outcome from `other` replaces the one in this batch.
+    ///
+    /// Note that only batches of outcomes produced by the same `Hive` should be merged, since
+    /// task indices are only unique within a `Hive`.
+    pub fn merge(&mut self, other: OutcomeBatch<W>) {
+        self.0.extend(other.0);
+    }
 }
 
 impl<W: Worker, I: IntoIterator<Item = Outcome<W>>> From<I> for OutcomeBatch<W> {
@@ -55,3 +87,45 @@ impl<W: Worker> OutcomeBatch<W> {
         self.0.insert(*outcome.index(), outcome);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::OutcomeBatch;
+    use crate::bee::stock::EchoWorker;
+    use crate::hive::{Outcome, OutcomeStore};
+
+    fn make_batch(indices: impl IntoIterator<Item = usize>) -> OutcomeBatch<EchoWorker<usize>> {
+        indices
+            .into_iter()
+            .map(|index| match index % 2 {
+                0 => Outcome::Success {
+                    value: index,
+                    index,
+                },
+                _ => Outcome::Unprocessed {
+                    input: index,
+                    index,
+                },
+            })
+            .into()
+    }
+
+    #[test]
+    fn test_map_outputs() {
+        let (successes, _, unprocessed) = make_batch(0..4).map_outputs(|i| i * 10).partition();
+        assert_eq!(successes, vec![(0, 0), (2, 20)]);
+        assert_eq!(unprocessed, vec![(1, 1), (3, 3)]);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut batch = make_batch(0..3);
+        batch.merge(make_batch(2..6));
+        let indices: Vec<_> = batch
+            .into_sorted_vec()
+            .iter()
+            .map(|outcome| *outcome.index())
+            .collect();
+        assert_eq!(indices, (0..6).collect::<Vec<_>>());
+    }
+}
@@ -3,10 +3,12 @@ mod iter;
 #[allow(clippy::module_inception)]
 mod outcome;
 mod store;
+mod summary;
 
 pub use batch::OutcomeBatch;
 pub use iter::OutcomeIteratorExt;
 pub use outcome::Outcome;
 pub use store::OutcomeStore;
+pub use summary::OutcomeSummary;
 
 pub(super) use store::sealed::{DerefOutcomes, OwnedOutcomes};
@@ -108,6 +108,16 @@ impl<W: Worker> Outcome<W> {
         }
     }
 
+    /// Returns the `Panic` if this is a `Panic` outcome, otherwise `None`. The `Panic` provides
+    /// access to the panic payload as well as the backtrace (if captured), the index and name of
+    /// the worker thread, and the retry attempt on which the panic occurred.
+    pub fn as_panic(&self) -> Option<&Panic<String>> {
+        match self {
+            Self::Panic { payload, .. } => Some(payload),
+            _ => None,
+        }
+    }
+
     /// Consumes this `Outcome` and returns the value of this `Success` outcome. Panics if this is
     /// not a `Success` outcome.
     pub fn unwrap(self) -> W::Output {
@@ -1,4 +1,4 @@
-use super::Outcome;
+use super::{Outcome, OutcomeSummary};
 use crate::bee::Worker;
 
 /// Traits with methods that should only be accessed internally by public traits.
@@ -207,9 +207,65 @@ pub trait OutcomeStore<W: Worker>: sealed::DerefOutcomes<W> {
             .collect()
     }
 
+    /// Retains only the outcomes for which `f` returns `true`, and removes all other outcomes.
+    fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&Outcome<W>) -> bool,
+    {
+        self.outcomes_deref_mut().retain(|_, outcome| f(outcome));
+    }
+
+    /// Returns an `OutcomeSummary` with the number of stored outcomes of each variant and the
+    /// number of failures with each distinct error.
+    fn summary(&self) -> OutcomeSummary {
+        self.outcomes_deref().values().collect()
+    }
+
     // The following methods are available for structs that store *and* have ownership of
     // `Outcome`s (`Husk` and `OutcomeBatch`).
 
+    /// Consumes this store and returns a `Vec` of the outcomes sorted by index.
+    fn into_sorted_vec(self) -> Vec<Outcome<W>>
+    where
+        Self: sealed::OwnedOutcomes<W>,
+    {
+        let mut outcomes: Vec<_> = self.outcomes().into_values().collect();
+        outcomes.sort();
+        outcomes
+    }
+
+    /// Consumes this store and partitions the outcomes into a tuple of
+    /// `(successes, failures, unprocessed)`, where:
+    /// * `successes` is a `Vec` of `(index, output)` tuples for the `Outcome::Success` outcomes
+    /// * `unprocessed` is a `Vec` of `(index, input)` tuples for the `Outcome::Unprocessed`
+    ///   outcomes
+    /// * `failures` is a `Vec` of all the other outcomes
+    ///
+    /// Each `Vec` is sorted by index.
+    #[allow(clippy::type_complexity)]
+    fn partition(
+        self,
+    ) -> (
+        Vec<(usize, W::Output)>,
+        Vec<Outcome<W>>,
+        Vec<(usize, W::Input)>,
+    )
+    where
+        Self: sealed::OwnedOutcomes<W>,
+    {
+        let mut successes = Vec::new();
+        let mut failures = Vec::new();
+        let mut unprocessed = Vec::new();
+        for outcome in self.into_sorted_vec() {
+            match outcome {
+                Outcome::Success { value, index } => successes.push((index, value)),
+                Outcome::Unprocessed { input, index } => unprocessed.push((index, input)),
+                outcome => failures.push(outcome),
+            }
+        }
+        (successes, failures, unprocessed)
+    }
+
     /// Consumes this store and returns an iterator over the outcomes in index order.
     fn into_iter(self) -> impl Iterator<Item = Outcome<W>>
     where
@@ -334,6 +390,7 @@ pub trait OutcomeStore<W: Worker>: sealed::DerefOutcomes<W> {
 
 #[cfg(test)]
 mod tests {
+    use super::sealed::DerefOutcomes;
     use super::OutcomeStore;
     use crate::bee::{Context, Worker, WorkerResult};
     use crate::hive::{Outcome, OutcomeBatch};
@@ -454,6 +511,46 @@ mod tests {
         assert_eq!(failure_indices, vec![2, 3]);
     }
 
+    #[test]
+    fn test_into_sorted_vec() {
+        let indices: Vec<_> = make_batch()
+            .into_sorted_vec()
+            .iter()
+            .map(|outcome| *outcome.index())
+            .collect();
+        assert_eq!(indices, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_partition() {
+        let (successes, failures, unprocessed) = make_batch().partition();
+        assert_eq!(successes, vec![(0, 1)]);
+        assert_eq!(unprocessed, vec![(1, 2)]);
+        assert_eq!(failures.len(), 2);
+        assert!(matches!(failures[0], Outcome::Failure { index: 2, .. }));
+        assert!(matches!(failures[1], Outcome::Panic { index: 3, .. }));
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut store = make_batch();
+        store.retain(|outcome| !outcome.is_failure());
+        let mut indices: Vec<_> = store.outcomes_deref().keys().copied().collect();
+        indices.sort();
+        assert_eq!(indices, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_summary() {
+        let summary = make_batch().summary();
+        assert_eq!(summary.total(), 4);
+        assert_eq!(summary.successes, 1);
+        assert_eq!(summary.unprocessed, 1);
+        assert_eq!(summary.failures, 1);
+        assert_eq!(summary.panics, 1);
+        assert_eq!(summary.errors["()"], 1);
+    }
+
     #[test]
     fn test_remove() {
         let mut store = make_batch();
@@ -544,7 +641,7 @@ mod retry_tests {
 
         assert!(store.has_unprocessed());
         assert!(store.get(1).unwrap().is_unprocessed());
-        for index in vec![0, 2, 3, 4] {
+        for index in [0, 2, 3, 4] {
             assert!(!store.get(index).unwrap().is_unprocessed());
         }
         assert_eq!(store.unprocessed_indices(), vec![1]);
@@ -553,7 +650,7 @@ mod retry_tests {
         for index in 2..=4 {
             assert!(store.get(index).unwrap().is_failure())
         }
-        for index in vec![0, 1] {
+        for index in [0, 1] {
             assert!(!store.get(index).unwrap().is_failure());
         }
         let mut failure_indices = store.failure_indices();
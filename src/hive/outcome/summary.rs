@@ -0,0 +1,153 @@
+use super::Outcome;
+use crate::bee::Worker;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A summary of a collection of `Outcome`s, with the number of outcomes of each variant and the
+/// number of failures with each distinct error.
+///
+/// The `Display` implementation of `OutcomeSummary` produces a human-readable report.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OutcomeSummary {
+    /// The number of `Outcome::Success` outcomes.
+    pub successes: usize,
+    /// The number of `Outcome::Failure` outcomes.
+    pub failures: usize,
+    /// The number of `Outcome::Unprocessed` outcomes.
+    pub unprocessed: usize,
+    /// The number of `Outcome::Missing` outcomes.
+    pub missing: usize,
+    /// The number of `Outcome::Panic` outcomes.
+    pub panics: usize,
+    /// The number of `Outcome::MaxRetriesAttempted` outcomes.
+    #[cfg(feature = "retry")]
+    pub max_retries_attempted: usize,
+    /// The number of outcomes with each distinct error (i.e., `Failure` and `MaxRetriesAttempted`
+    /// outcomes), keyed by the `Debug` representation of the error.
+    pub errors: BTreeMap<String, usize>,
+}
+
+impl OutcomeSummary {
+    /// Returns the total number of outcomes.
+    pub fn total(&self) -> usize {
+        let total = self.successes + self.failures + self.unprocessed + self.missing + self.panics;
+        #[cfg(feature = "retry")]
+        let total = total + self.max_retries_attempted;
+        total
+    }
+
+    /// Returns `true` if all of the outcomes are successes.
+    pub fn is_all_success(&self) -> bool {
+        self.successes == self.total()
+    }
+
+    /// Adds `outcome` to this summary.
+    pub fn add<W: Worker>(&mut self, outcome: &Outcome<W>) {
+        match outcome {
+            Outcome::Success { .. } => self.successes += 1,
+            Outcome::Failure { error, .. } => {
+                self.failures += 1;
+                self.add_error(error);
+            }
+            Outcome::Unprocessed { .. } => self.unprocessed += 1,
+            Outcome::Missing { .. } => self.missing += 1,
+            Outcome::Panic { .. } => self.panics += 1,
+            #[cfg(feature = "retry")]
+            Outcome::MaxRetriesAttempted { error, .. } => {
+                self.max_retries_attempted += 1;
+                self.add_error(error);
+            }
+        }
+    }
+
+    fn add_error<E: fmt::Debug>(&mut self, error: &E) {
+        *self.errors.entry(format!("{error:?}")).or_default() += 1;
+    }
+}
+
+impl<'a, W: Worker> FromIterator<&'a Outcome<W>> for OutcomeSummary {
+    fn from_iter<T: IntoIterator<Item = &'a Outcome<W>>>(iter: T) -> Self {
+        let mut summary = Self::default();
+        iter.into_iter().for_each(|outcome| summary.add(outcome));
+        summary
+    }
+}
+
+impl fmt::Display for OutcomeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} outcomes: {} successes, {} failures, {} unprocessed, {} missing, {} panics",
+            self.total(),
+            self.successes,
+            self.failures,
+            self.unprocessed,
+            self.missing,
+            self.panics
+        )?;
+        #[cfg(feature = "retry")]
+        write!(f, ", {} max retries attempted", self.max_retries_attempted)?;
+        for (error, count) in &self.errors {
+            write!(f, "\n  {count} x {error}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OutcomeSummary;
+    use crate::bee::stock::EchoWorker;
+    use crate::bee::{Context, Worker, WorkerResult};
+    use crate::hive::Outcome;
+
+    #[derive(Debug)]
+    struct TestWorker;
+
+    impl Worker for TestWorker {
+        type Input = u8;
+        type Output = u8;
+        type Error = String;
+
+        fn apply(&mut self, i: Self::Input, _: &Context) -> WorkerResult<Self> {
+            Ok(i)
+        }
+    }
+
+    #[test]
+    fn test_summary() {
+        let failure = |error: &str, index| Outcome::<TestWorker>::Failure {
+            input: None,
+            error: error.into(),
+            index,
+        };
+        let outcomes = [
+            Outcome::Success { value: 1, index: 0 },
+            failure("bad", 1),
+            failure("worse", 2),
+            failure("bad", 3),
+            Outcome::Unprocessed { input: 5, index: 4 },
+            Outcome::Missing { index: 5 },
+        ];
+        let summary: OutcomeSummary = outcomes.iter().collect();
+        assert_eq!(summary.total(), 6);
+        assert_eq!(summary.successes, 1);
+        assert_eq!(summary.failures, 3);
+        assert_eq!(summary.unprocessed, 1);
+        assert_eq!(summary.missing, 1);
+        assert_eq!(summary.errors.len(), 2);
+        assert_eq!(summary.errors["\"bad\""], 2);
+        assert_eq!(summary.errors["\"worse\""], 1);
+        assert!(!summary.is_all_success());
+        let report = summary.to_string();
+        assert!(report.starts_with("6 outcomes: 1 successes, 3 failures, 1 unprocessed"));
+        assert!(report.ends_with("\n  2 x \"bad\"\n  1 x \"worse\""));
+    }
+
+    #[test]
+    fn test_summary_empty() {
+        let summary: OutcomeSummary = Vec::<Outcome<EchoWorker<u8>>>::new().iter().collect();
+        assert_eq!(summary, OutcomeSummary::default());
+        assert!(summary.is_all_success());
+    }
+}
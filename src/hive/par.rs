@@ -0,0 +1,325 @@
+//! Extension trait for executing iterator operations in parallel using a `Hive`.
+use super::{outcome_channel, Builder, Hive, Outcome};
+use crate::bee::stock::{PunkWorker, Thunk};
+use crate::bee::{DefaultQueen, Queen};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+
+/// The type of `Hive` used to execute the operations of a `ParIter`. The tasks are closures that
+/// send their results back to the `ParIter`, so a single `Hive` of this type can execute
+/// operations with any input and output types.
+pub type ParHive<Q = DefaultQueen<PunkWorker<()>>> = Hive<PunkWorker<()>, Q>;
+
+/// Either a borrowed `Hive` or a temporary `Hive` owned by a `ParIter`.
+enum HiveRef<'a, Q: Queen<Kind = PunkWorker<()>>> {
+    Borrowed(&'a ParHive<Q>),
+    Owned(ParHive<Q>),
+}
+
+impl<Q: Queen<Kind = PunkWorker<()>>> HiveRef<'_, Q> {
+    fn get(&self) -> &ParHive<Q> {
+        match self {
+            Self::Borrowed(hive) => hive,
+            Self::Owned(hive) => hive,
+        }
+    }
+}
+
+/// An iterator whose items are processed in parallel by the worker threads of a `Hive`. A
+/// `ParIter` is created by calling [`HiveIteratorExt::into_hive`] or
+/// [`HiveIteratorExt::into_temp_hive`] on any `Iterator`.
+///
+/// All of the items are submitted to the `Hive` as soon as one of the operations (e.g., `map`) is
+/// called, and the operation blocks until all the tasks are complete. If any task panics, the
+/// panic is resumed on the calling thread.
+pub struct ParIter<'a, I, Q: Queen<Kind = PunkWorker<()>>> {
+    iter: I,
+    hive: HiveRef<'a, Q>,
+    ordered: bool,
+}
+
+impl<I, Q> ParIter<'_, I, Q>
+where
+    I: Iterator,
+    I::Item: Send + 'static,
+    Q: Queen<Kind = PunkWorker<()>>,
+{
+    /// Returns this `ParIter` configured to return the results of `map` and `filter_map` in the
+    /// order they are completed rather than in the same order as the inputs, which avoids
+    /// sorting the results.
+    pub fn unordered(mut self) -> Self {
+        self.ordered = false;
+        self
+    }
+
+    /// Applies `f` to each item in parallel and returns a `Vec` of the results.
+    pub fn map<O, F>(self, f: F) -> Vec<O>
+    where
+        O: Send + 'static,
+        F: Fn(I::Item) -> O + Send + Sync + 'static,
+    {
+        self.filter_map(move |item| Some(f(item)))
+    }
+
+    /// Applies `f` to each item in parallel and returns a `Vec` of the results that are `Some`.
+    pub fn filter_map<O, F>(self, f: F) -> Vec<O>
+    where
+        O: Send + 'static,
+        F: Fn(I::Item) -> Option<O> + Send + Sync + 'static,
+    {
+        let ordered = self.ordered;
+        let mut results = self.execute(f);
+        if ordered {
+            results.sort_unstable_by_key(|(index, _)| *index);
+        }
+        results
+            .into_iter()
+            .filter_map(|(_, output)| output)
+            .collect()
+    }
+
+    /// Calls `f` on each item in parallel.
+    pub fn for_each<F>(self, f: F)
+    where
+        F: Fn(I::Item) + Send + Sync + 'static,
+    {
+        self.execute(f);
+    }
+
+    /// Calls `f` on each item in parallel. Once `f` returns an error, `f` is no longer called on
+    /// any item that has not yet been started. If there are any errors, the error for the item
+    /// that comes first in the iteration order is returned.
+    pub fn try_for_each<E, F>(self, f: F) -> Result<(), E>
+    where
+        E: Send + 'static,
+        F: Fn(I::Item) -> Result<(), E> + Send + Sync + 'static,
+    {
+        let failed = Arc::new(AtomicBool::new(false));
+        let failed_clone = Arc::clone(&failed);
+        self.execute(move |item| {
+            if failed_clone.load(Ordering::Acquire) {
+                return Ok(());
+            }
+            let result = f(item);
+            if result.is_err() {
+                failed_clone.store(true, Ordering::Release);
+            }
+            result
+        })
+        .into_iter()
+        .filter_map(|(index, result)| result.err().map(|error| (index, error)))
+        .min_by_key(|(index, _)| *index)
+        .map_or(Ok(()), |(_, error)| Err(error))
+    }
+
+    /// Submits one task per item that calls `f` on the item, waits for all the tasks to complete,
+    /// and returns the results (along with the position of each item in the iteration order) in
+    /// the order they were completed.
+    ///
+    /// Resumes the panic if any task panicked, and panics if any task was not processed.
+    fn execute<O, F>(self, f: F) -> Vec<(usize, O)>
+    where
+        O: Send + 'static,
+        F: Fn(I::Item) -> O + Send + Sync + 'static,
+    {
+        let hive = self.hive.get();
+        let f = Arc::new(f);
+        let (tx, rx) = mpsc::channel();
+        let (outcome_tx, outcome_rx) = outcome_channel();
+        for (index, item) in self.iter.enumerate() {
+            let f = Arc::clone(&f);
+            let tx = tx.clone();
+            let thunk = Thunk::of(move || {
+                let _ = tx.send((index, f(item)));
+            });
+            hive.apply_send(thunk, outcome_tx.clone());
+        }
+        drop(tx);
+        drop(outcome_tx);
+        // the channel is disconnected once all the tasks have completed
+        for outcome in outcome_rx {
+            match outcome {
+                Outcome::Success { .. } => (),
+                Outcome::Panic { payload, .. } => payload.resume(),
+                outcome => panic!("task {} was not processed", outcome.index()),
+            }
+        }
+        rx.try_iter().collect()
+    }
+}
+
+/// Extension trait that enables the items of any `Iterator` to be processed in parallel using a
+/// `Hive`.
+///
+/// # Examples
+///
+/// ```
+/// use beekeeper::hive::{Builder, HiveIteratorExt};
+/// use beekeeper::bee::stock::PunkWorker;
+///
+/// # fn main() {
+/// // use a temporary hive with four threads
+/// let squares = (0..10u64).par_map(4, |i| i * i);
+/// assert_eq!(squares, vec![0, 1, 4, 9, 16, 25, 36, 49, 64, 81]);
+///
+/// // use an existing hive
+/// let hive = Builder::new()
+///     .num_threads(4)
+///     .build_with_default::<PunkWorker<()>>()
+///     .unwrap();
+/// let evens = (0..10u64)
+///     .into_hive(&hive)
+///     .filter_map(|i| (i % 2 == 0).then_some(i));
+/// assert_eq!(evens, vec![0, 2, 4, 6, 8]);
+/// # }
+/// ```
+pub trait HiveIteratorExt: Iterator + Sized {
+    /// Returns a `ParIter` that processes the items of this iterator using the worker threads of
+    /// `hive`. The same `hive` may be used for any number of `ParIter` operations with any input
+    /// and output types.
+    fn into_hive<Q>(self, hive: &ParHive<Q>) -> ParIter<'_, Self, Q>
+    where
+        Q: Queen<Kind = PunkWorker<()>>,
+    {
+        ParIter {
+            iter: self,
+            hive: HiveRef::Borrowed(hive),
+            ordered: true,
+        }
+    }
+
+    /// Returns a `ParIter` that processes the items of this iterator using a temporary `Hive`
+    /// with `num_threads` worker threads. The `Hive` is dropped after the operation completes.
+    fn into_temp_hive(
+        self,
+        num_threads: usize,
+    ) -> ParIter<'static, Self, DefaultQueen<PunkWorker<()>>> {
+        let hive = Builder::default()
+            .num_threads(num_threads)
+            .build_with_default()
+            .unwrap();
+        ParIter {
+            iter: self,
+            hive: HiveRef::Owned(hive),
+            ordered: true,
+        }
+    }
+
+    /// Applies `f` to each item in parallel using a temporary `Hive` with `num_threads` worker
+    /// threads, and returns a `Vec` of the results in the same order as the items.
+    fn par_map<O, F>(self, num_threads: usize, f: F) -> Vec<O>
+    where
+        Self::Item: Send + 'static,
+        O: Send + 'static,
+        F: Fn(Self::Item) -> O + Send + Sync + 'static,
+    {
+        self.into_temp_hive(num_threads).map(f)
+    }
+
+    /// Applies `f` to each item in parallel using a temporary `Hive` with `num_threads` worker
+    /// threads, and returns a `Vec` of the results that are `Some`, in the same order as the
+    /// items.
+    fn par_filter_map<O, F>(self, num_threads: usize, f: F) -> Vec<O>
+    where
+        Self::Item: Send + 'static,
+        O: Send + 'static,
+        F: Fn(Self::Item) -> Option<O> + Send + Sync + 'static,
+    {
+        self.into_temp_hive(num_threads).filter_map(f)
+    }
+
+    /// Calls `f` on each item in parallel using a temporary `Hive` with `num_threads` worker
+    /// threads.
+    fn par_for_each<F>(self, num_threads: usize, f: F)
+    where
+        Self::Item: Send + 'static,
+        F: Fn(Self::Item) + Send + Sync + 'static,
+    {
+        self.into_temp_hive(num_threads).for_each(f)
+    }
+
+    /// Calls `f` on each item in parallel using a temporary `Hive` with `num_threads` worker
+    /// threads. See [`ParIter::try_for_each`] for details.
+    fn par_try_for_each<E, F>(self, num_threads: usize, f: F) -> Result<(), E>
+    where
+        Self::Item: Send + 'static,
+        E: Send + 'static,
+        F: Fn(Self::Item) -> Result<(), E> + Send + Sync + 'static,
+    {
+        self.into_temp_hive(num_threads).try_for_each(f)
+    }
+}
+
+impl<I: Iterator> HiveIteratorExt for I {}
+
+#[cfg(test)]
+mod tests {
+    use super::HiveIteratorExt;
+    use crate::bee::stock::PunkWorker;
+    use crate::hive::Builder;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_par_map() {
+        let outputs = (0..100usize).par_map(4, |i| i * 2);
+        assert_eq!(outputs, (0..100).map(|i| i * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_par_map_unordered() {
+        let mut outputs = (0..20u64).into_temp_hive(4).unordered().map(|i| {
+            thread::sleep(Duration::from_millis(10 * (i % 3)));
+            i
+        });
+        outputs.sort();
+        assert_eq!(outputs, (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_par_filter_map() {
+        let outputs = ["1", "two", "3", "four"]
+            .into_iter()
+            .par_filter_map(2, |s| s.parse::<u8>().ok());
+        assert_eq!(outputs, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_par_for_each() {
+        let sum = Arc::new(AtomicUsize::new(0));
+        let sum_clone = Arc::clone(&sum);
+        (1..=10usize).par_for_each(4, move |i| {
+            sum_clone.fetch_add(i, Ordering::SeqCst);
+        });
+        assert_eq!(sum.load(Ordering::SeqCst), 55);
+    }
+
+    #[test]
+    fn test_par_try_for_each() {
+        assert_eq!((0..10u8).par_try_for_each(4, |_| Ok::<_, u8>(())), Ok(()));
+        let result = (0..10u8).par_try_for_each(1, |i| if i >= 3 { Err(i) } else { Ok(()) });
+        assert_eq!(result, Err(3));
+    }
+
+    #[test]
+    fn test_into_hive() {
+        let hive = Builder::new()
+            .num_threads(4)
+            .build_with_default::<PunkWorker<()>>()
+            .unwrap();
+        let strings = (0..5u8).into_hive(&hive).map(|i| i.to_string());
+        assert_eq!(strings, vec!["0", "1", "2", "3", "4"]);
+        let lengths = strings.into_iter().into_hive(&hive).map(|s| s.len());
+        assert_eq!(lengths, vec![1; 5]);
+        // no outcomes are stored in the hive
+        assert!(hive.take_stored().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "oh no!")]
+    fn test_panic() {
+        (0..10u8).par_for_each(2, |i| assert!(i != 5, "oh no!"));
+    }
+}
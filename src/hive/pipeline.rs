@@ -0,0 +1,747 @@
+//! A `Pipeline` chains `Hive`s together, such that the `Output` of each stage is the `Input` of
+//! the next stage.
+use super::gate::Gate;
+use super::{outcome_channel, Hive, Husk, Outcome, OutcomeReceiver};
+use crate::atomic::{Atomic, AtomicBool, AtomicInt, AtomicUsize};
+use crate::bee::{Queen, Worker};
+use crate::channel::{Message, Receiver, ReceiverExt, Sender};
+use parking_lot::{Condvar, Mutex};
+use std::any::Any;
+use std::fmt::Debug;
+use std::iter;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// A failed task from one of the stages of a `Pipeline`. Any `Outcome` other than
+/// `Outcome::Success` is considered a failure, including `Outcome::Unprocessed` outcomes of tasks
+/// that were still queued when the `Pipeline` was shut down, and `Outcome::Missing` outcomes of
+/// tasks that were lost because a `Worker` panicked.
+///
+/// Since each stage may have a different `Worker` type, the `Outcome` is type-erased; use
+/// [`downcast`](PipelineFailure::downcast) to recover it.
+#[derive(Debug)]
+pub struct PipelineFailure {
+    stage: usize,
+    index: usize,
+    outcome: Box<dyn Any + Send>,
+}
+
+impl PipelineFailure {
+    fn new<W: Worker>(stage: usize, outcome: Outcome<W>) -> Self {
+        Self {
+            stage,
+            index: *outcome.index(),
+            outcome: Box::new(outcome),
+        }
+    }
+
+    /// Returns the index of the stage (starting from `0`) at which the task failed.
+    pub fn stage(&self) -> usize {
+        self.stage
+    }
+
+    /// Returns the index of the failed task within the `Hive` of its stage.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Consumes this `PipelineFailure` and returns the `Outcome`, if `W` is the `Worker` type of
+    /// the stage at which the task failed, otherwise returns `Err(self)`.
+    pub fn downcast<W: Worker>(self) -> Result<Outcome<W>, Self> {
+        match self.outcome.downcast::<Outcome<W>>() {
+            Ok(outcome) => Ok(*outcome),
+            Err(outcome) => Err(Self {
+                stage: self.stage,
+                index: self.index,
+                outcome,
+            }),
+        }
+    }
+}
+
+/// Limits the number of items in-flight within a stage. Unlike `Gate`, permits are counted, and
+/// an attempt to acquire a permit blocks while all the permits are in use.
+#[derive(Debug)]
+struct Permits {
+    in_use: Mutex<usize>,
+    condvar: Condvar,
+    capacity: usize,
+    closed: AtomicBool,
+}
+
+impl Permits {
+    fn new(capacity: usize) -> Arc<Self> {
+        Arc::new(Self {
+            in_use: Mutex::new(0),
+            condvar: Condvar::new(),
+            capacity: capacity.max(1),
+            closed: Default::default(),
+        })
+    }
+
+    /// Blocks until a permit is available, unless these permits have been closed.
+    fn acquire(&self) {
+        let mut in_use = self.in_use.lock();
+        while *in_use >= self.capacity && !self.closed.get() {
+            self.condvar.wait(&mut in_use);
+        }
+        *in_use += 1;
+    }
+
+    /// Releases a permit and wakes up one thread waiting to acquire a permit.
+    fn release(&self) {
+        let mut in_use = self.in_use.lock();
+        *in_use = in_use.saturating_sub(1);
+        self.condvar.notify_one();
+    }
+
+    /// Closes these permits so that `acquire` never blocks.
+    fn close(&self) {
+        let _in_use = self.in_use.lock();
+        self.closed.set(true);
+        self.condvar.notify_all();
+    }
+}
+
+/// Tracks the number of inputs that are still moving through a `Pipeline` and the number of
+/// outputs that are waiting to be received.
+#[derive(Debug, Default)]
+struct Progress {
+    in_flight: AtomicUsize,
+    num_outputs: AtomicUsize,
+    suspended: AtomicBool,
+    gate: Gate,
+}
+
+impl Progress {
+    fn finish(&self) {
+        self.in_flight.sub(1);
+        self.gate.notify_all();
+    }
+}
+
+/// The stages of a `Pipeline`: either a single `Hive`, or a pair of (previous stages, `Hive`).
+///
+/// This trait is implemented for all `Hive`s, and the methods are only used internally.
+pub trait Stages: Send + 'static {
+    /// The `Husk`s of the stages, nested in the same way as the stages themselves.
+    type Husks;
+
+    #[doc(hidden)]
+    fn set_suspended(&self, suspended: bool);
+
+    #[doc(hidden)]
+    fn join(&self);
+
+    /// Converts each stage into a `Husk`. After each stage is converted, the thread forwarding
+    /// its outcomes is joined - this drops the forwarding thread's reference to the next stage.
+    #[doc(hidden)]
+    fn try_into_husks<F: Iterator<Item = JoinHandle<()>>>(
+        self,
+        forwarders: &mut F,
+    ) -> Option<Self::Husks>;
+}
+
+impl<W: Worker, Q: Queen<Kind = W>> Stages for Hive<W, Q> {
+    type Husks = Husk<W, Q>;
+
+    fn set_suspended(&self, suspended: bool) {
+        if suspended {
+            self.suspend()
+        } else {
+            self.resume()
+        }
+    }
+
+    fn join(&self) {
+        Hive::join(self)
+    }
+
+    fn try_into_husks<F: Iterator<Item = JoinHandle<()>>>(
+        self,
+        forwarders: &mut F,
+    ) -> Option<Self::Husks> {
+        let husk = self.try_into_husk();
+        if let Some(forwarder) = forwarders.next() {
+            let _ = forwarder.join();
+        }
+        husk
+    }
+}
+
+impl<S: Stages, W: Worker, Q: Queen<Kind = W>> Stages for (S, Hive<W, Q>) {
+    type Husks = (S::Husks, Husk<W, Q>);
+
+    fn set_suspended(&self, suspended: bool) {
+        self.0.set_suspended(suspended);
+        self.1.set_suspended(suspended);
+    }
+
+    fn join(&self) {
+        self.0.join();
+        self.1.join();
+    }
+
+    fn try_into_husks<F: Iterator<Item = JoinHandle<()>>>(
+        self,
+        forwarders: &mut F,
+    ) -> Option<Self::Husks> {
+        let prev = self.0.try_into_husks(forwarders);
+        let husk = self.1.try_into_husks(forwarders);
+        prev.zip(husk)
+    }
+}
+
+/// Spawns a thread that receives `Outcome`s from a stage. Each `Success` value is passed to
+/// `deliver`, and every other outcome is sent to the failure sink. A permit is released for each
+/// outcome that is received.
+fn forward<P, F>(
+    stage: usize,
+    outcome_rx: OutcomeReceiver<P>,
+    permits: Arc<Permits>,
+    failure_tx: Sender<PipelineFailure>,
+    progress: Arc<Progress>,
+    mut deliver: F,
+) -> JoinHandle<()>
+where
+    P: Worker,
+    F: FnMut(P::Output) + Send + 'static,
+{
+    thread::spawn(move || {
+        for outcome in outcome_rx {
+            match outcome {
+                Outcome::Success { value, .. } => deliver(value),
+                outcome => {
+                    let _ = failure_tx.send(PipelineFailure::new(stage, outcome));
+                    progress.finish();
+                }
+            }
+            permits.release();
+        }
+    })
+}
+
+/// A builder for a [`Pipeline`]. Stages are added in order by calling `then`.
+///
+/// Each stage has a `capacity`, which is the maximum number of tasks that may be in-flight within
+/// the stage (queued, active, or waiting to be forwarded to the next stage). Once a stage is at
+/// capacity, the previous stage (or the caller submitting to the first stage) blocks until one of
+/// its tasks completes, which applies backpressure through the entire `Pipeline`.
+pub struct PipelineBuilder<W: Worker, Q: Queen<Kind = W>, L: Worker, S: Stages> {
+    head: Hive<W, Q>,
+    head_tx: super::OutcomeSender<W>,
+    head_permits: Arc<Permits>,
+    last_rx: OutcomeReceiver<L>,
+    last_permits: Arc<Permits>,
+    failure_tx: Sender<PipelineFailure>,
+    failure_rx: Receiver<PipelineFailure>,
+    permits: Vec<Arc<Permits>>,
+    forwarders: Vec<JoinHandle<()>>,
+    progress: Arc<Progress>,
+    stages: S,
+}
+
+impl<W: Worker, Q: Queen<Kind = W>> PipelineBuilder<W, Q, W, Hive<W, Q>> {
+    /// Creates a new `PipelineBuilder` whose first stage is `hive`, which may have at most
+    /// `capacity` in-flight tasks.
+    pub fn new(hive: Hive<W, Q>, capacity: usize) -> Self {
+        let (head_tx, last_rx) = outcome_channel();
+        let (failure_tx, failure_rx) = crate::channel::channel();
+        let permits = Permits::new(capacity);
+        Self {
+            head: hive.clone(),
+            head_tx,
+            head_permits: Arc::clone(&permits),
+            last_rx,
+            last_permits: Arc::clone(&permits),
+            failure_tx,
+            failure_rx,
+            permits: vec![permits],
+            forwarders: Vec::new(),
+            progress: Default::default(),
+            stages: hive,
+        }
+    }
+}
+
+impl<W: Worker, Q: Queen<Kind = W>, L: Worker, S: Stages> PipelineBuilder<W, Q, L, S> {
+    /// Adds `hive` as the next stage of the pipeline, which may have at most `capacity` in-flight
+    /// tasks. Each successful output of the current last stage is submitted to `hive`.
+    pub fn then<N, NQ>(
+        mut self,
+        hive: Hive<N, NQ>,
+        capacity: usize,
+    ) -> PipelineBuilder<W, Q, N, (S, Hive<N, NQ>)>
+    where
+        N: Worker<Input = L::Output>,
+        NQ: Queen<Kind = N>,
+    {
+        let (next_tx, next_rx) = outcome_channel();
+        let next_permits = Permits::new(capacity);
+        let forwarder = {
+            let hive = hive.clone();
+            let permits = Arc::clone(&next_permits);
+            forward(
+                self.permits.len() - 1,
+                self.last_rx,
+                self.last_permits,
+                self.failure_tx.clone(),
+                Arc::clone(&self.progress),
+                move |value| {
+                    permits.acquire();
+                    hive.apply_send(value, next_tx.clone());
+                },
+            )
+        };
+        self.forwarders.push(forwarder);
+        self.permits.push(Arc::clone(&next_permits));
+        PipelineBuilder {
+            head: self.head,
+            head_tx: self.head_tx,
+            head_permits: self.head_permits,
+            last_rx: next_rx,
+            last_permits: next_permits,
+            failure_tx: self.failure_tx,
+            failure_rx: self.failure_rx,
+            permits: self.permits,
+            forwarders: self.forwarders,
+            progress: self.progress,
+            stages: (self.stages, hive),
+        }
+    }
+
+    /// Consumes this builder and returns a `Pipeline`. At most `capacity` outputs of the last
+    /// stage are buffered waiting to be received from the `Pipeline`.
+    pub fn build(mut self, capacity: usize) -> Pipeline<W, Q, L, S> {
+        let (output_tx, output_rx) = crate::channel::channel();
+        let output_permits = Permits::new(capacity);
+        let forwarder = {
+            let permits = Arc::clone(&output_permits);
+            let progress = Arc::clone(&self.progress);
+            forward(
+                self.permits.len() - 1,
+                self.last_rx,
+                self.last_permits,
+                self.failure_tx,
+                Arc::clone(&self.progress),
+                move |value| {
+                    permits.acquire();
+                    let _ = output_tx.send(value);
+                    progress.num_outputs.add(1);
+                    progress.finish();
+                },
+            )
+        };
+        self.forwarders.push(forwarder);
+        self.permits.push(Arc::clone(&output_permits));
+        Pipeline(Some(PipelineInner {
+            head: self.head,
+            head_tx: self.head_tx,
+            head_permits: self.head_permits,
+            output_rx: Mutex::new(output_rx),
+            output_permits,
+            failure_rx: Mutex::new(self.failure_rx),
+            permits: self.permits,
+            forwarders: self.forwarders,
+            progress: self.progress,
+            stages: self.stages,
+        }))
+    }
+}
+
+/// A chain of `Hive`s, where each successful output of one stage is submitted as the input to the
+/// next stage. Inputs are submitted to the first stage, and outputs are received from the last
+/// stage. A `Pipeline` is created using a [`PipelineBuilder`].
+///
+/// Any task that does not succeed (at any stage) short-circuits the remainder of the pipeline and
+/// is sent to a shared failure sink, from which it may be retrieved as a [`PipelineFailure`].
+///
+/// # Examples
+///
+/// ```
+/// use beekeeper::bee::stock::{Caller, OnceCaller};
+/// use beekeeper::hive::prelude::*;
+/// use beekeeper::hive::PipelineBuilder;
+///
+/// # fn main() {
+/// let parse = Builder::new()
+///     .num_threads(2)
+///     .build_with(OnceCaller::of(|s: String| s.parse::<u32>()))
+///     .unwrap();
+/// let square = Builder::new()
+///     .num_threads(4)
+///     .build_with(Caller::of(|i: u32| i * i))
+///     .unwrap();
+/// let pipeline = PipelineBuilder::new(parse, 8).then(square, 8).build(8);
+/// pipeline.submit_all(["1", "2", "three", "4"].map(String::from));
+/// let mut outputs: Vec<_> = pipeline.outputs().collect();
+/// outputs.sort();
+/// assert_eq!(outputs, vec![1, 4, 16]);
+/// let failures: Vec<_> = pipeline.failures().collect();
+/// assert_eq!(failures.len(), 1);
+/// assert_eq!(failures[0].stage(), 0);
+/// # }
+/// ```
+pub struct Pipeline<W: Worker, Q: Queen<Kind = W>, L: Worker, S: Stages>(
+    Option<PipelineInner<W, Q, L, S>>,
+);
+
+struct PipelineInner<W: Worker, Q: Queen<Kind = W>, L: Worker, S: Stages> {
+    head: Hive<W, Q>,
+    head_tx: super::OutcomeSender<W>,
+    head_permits: Arc<Permits>,
+    output_rx: Mutex<Receiver<L::Output>>,
+    output_permits: Arc<Permits>,
+    failure_rx: Mutex<Receiver<PipelineFailure>>,
+    permits: Vec<Arc<Permits>>,
+    forwarders: Vec<JoinHandle<()>>,
+    progress: Arc<Progress>,
+    stages: S,
+}
+
+impl<W: Worker, Q: Queen<Kind = W>, L: Worker, S: Stages> Pipeline<W, Q, L, S> {
+    #[inline]
+    fn inner(&self) -> &PipelineInner<W, Q, L, S> {
+        self.0.as_ref().unwrap()
+    }
+
+    /// Submits one `input` to the first stage of the pipeline and returns its index within the
+    /// first stage's `Hive`. Blocks while the first stage is at capacity.
+    pub fn submit(&self, input: W::Input) -> usize {
+        let inner = self.inner();
+        inner.progress.in_flight.add(1);
+        inner.head_permits.acquire();
+        inner.head.apply_send(input, inner.head_tx.clone())
+    }
+
+    /// Submits each of `inputs` to the first stage of the pipeline and returns their indices
+    /// within the first stage's `Hive`. Blocks while the first stage is at capacity.
+    pub fn submit_all(&self, inputs: impl IntoIterator<Item = W::Input>) -> Vec<usize> {
+        inputs.into_iter().map(|input| self.submit(input)).collect()
+    }
+
+    /// Returns the number of inputs that have been submitted but have neither produced an output
+    /// (that is available to be received) nor failed.
+    pub fn num_in_flight(&self) -> usize {
+        self.inner().progress.in_flight.get()
+    }
+
+    /// Receives the next output of the last stage, blocking until one is available. Returns
+    /// `None` if there are no more inputs in-flight.
+    pub fn recv(&self) -> Option<L::Output> {
+        let inner = self.inner();
+        let progress = &inner.progress;
+        loop {
+            progress
+                .gate
+                .wait_while(|| progress.num_outputs.get() == 0 && progress.in_flight.get() > 0);
+            match inner.output_rx.lock().try_recv_msg() {
+                Message::Received(output) => {
+                    progress.num_outputs.sub(1);
+                    inner.output_permits.release();
+                    return Some(output);
+                }
+                Message::ChannelDisconnected => return None,
+                Message::ChannelEmpty if progress.in_flight.get() == 0 => return None,
+                Message::ChannelEmpty => continue,
+            }
+        }
+    }
+
+    /// Returns an iterator that receives outputs of the last stage until there are no more inputs
+    /// in-flight.
+    pub fn outputs(&self) -> impl Iterator<Item = L::Output> + '_ {
+        iter::from_fn(|| self.recv())
+    }
+
+    /// Returns an iterator over the failures that are currently available in the failure sink.
+    /// The iterator does not block waiting for more failures.
+    pub fn failures(&self) -> impl Iterator<Item = PipelineFailure> + '_ {
+        iter::from_fn(|| match self.inner().failure_rx.lock().try_recv_msg() {
+            Message::Received(failure) => Some(failure),
+            _ => None,
+        })
+    }
+
+    /// Suspends all the stages of this pipeline. See [`Hive::suspend`].
+    pub fn suspend(&self) {
+        let inner = self.inner();
+        inner.progress.suspended.set(true);
+        inner.stages.set_suspended(true);
+        inner.progress.gate.notify_all();
+    }
+
+    /// Resumes all the stages of this pipeline. See [`Hive::resume`].
+    pub fn resume(&self) {
+        let inner = self.inner();
+        inner.progress.suspended.set(false);
+        inner.stages.set_suspended(false);
+    }
+
+    /// Returns `true` if this pipeline is suspended.
+    pub fn is_suspended(&self) -> bool {
+        self.inner().progress.suspended.get()
+    }
+
+    /// Blocks until every input that has been submitted has either produced an output or failed.
+    /// If the pipeline is suspended, instead blocks only until the active tasks in each stage have
+    /// completed.
+    ///
+    /// Note that outputs must be received (e.g., using `recv`) in order for more than `capacity`
+    /// outputs to be produced by the last stage, otherwise this method may block indefinitely.
+    pub fn join(&self) {
+        let progress = &self.inner().progress;
+        progress
+            .gate
+            .wait_while(|| progress.in_flight.get() > 0 && !progress.suspended.get());
+        if progress.suspended.get() {
+            self.inner().stages.join();
+        }
+    }
+
+    /// Consumes this `Pipeline` and converts each of its stages into a `Husk`. Returns a tuple of
+    /// `(husks, outputs, failures)`, where `husks` are nested in the same way as the stages (e.g.,
+    /// `((Husk<A>, Husk<B>), Husk<C>)` for a three-stage pipeline), `outputs` are any outputs
+    /// that had not yet been received, and `failures` are any failures that had not yet been
+    /// retrieved from the failure sink.
+    ///
+    /// Each stage is converted in order, after waiting for its tasks to complete (see
+    /// [`Hive::try_into_husk`]). The capacity limits of the stages are lifted, and if the pipeline
+    /// is suspended it is first resumed, so that every in-flight input either produces an output
+    /// or fails.
+    ///
+    /// Returns `None` for the husks if any of the stages' `Hive`s could not be converted because
+    /// it was cloned before it was added to the pipeline.
+    #[allow(clippy::type_complexity)]
+    pub fn into_husks(mut self) -> (Option<S::Husks>, Vec<L::Output>, Vec<PipelineFailure>) {
+        let inner = self.0.take().unwrap();
+        // the pipeline is shutting down, so nothing should block on capacity
+        inner.permits.iter().for_each(|permits| permits.close());
+        // suspended worker threads would never terminate
+        if inner.progress.suspended.get() {
+            inner.stages.set_suspended(false);
+        }
+        drop(inner.head);
+        drop(inner.head_tx);
+        let husks = inner
+            .stages
+            .try_into_husks(&mut inner.forwarders.into_iter());
+        let outputs = iter::from_fn(|| match inner.output_rx.lock().try_recv_msg() {
+            Message::Received(output) => Some(output),
+            _ => None,
+        })
+        .collect();
+        let failure_rx = inner.failure_rx.into_inner();
+        let failures = iter::from_fn(|| match failure_rx.try_recv_msg() {
+            Message::Received(failure) => Some(failure),
+            _ => None,
+        })
+        .collect();
+        (husks, outputs, failures)
+    }
+}
+
+impl<W: Worker, Q: Queen<Kind = W>, L: Worker, S: Stages> Debug for Pipeline<W, Q, L, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(inner) = self.0.as_ref() {
+            f.debug_struct("Pipeline")
+                .field("num_stages", &(inner.permits.len() - 1))
+                .field("progress", &inner.progress)
+                .finish()
+        } else {
+            f.write_str("Pipeline {}")
+        }
+    }
+}
+
+impl<W: Worker, Q: Queen<Kind = W>, L: Worker, S: Stages> Drop for Pipeline<W, Q, L, S> {
+    fn drop(&mut self) {
+        // if this Pipeline has already been turned into Husks, its inner value will be `None`
+        if let Some(inner) = self.0.as_ref() {
+            // unblock any forwarding threads - they will terminate once their stages have been
+            // dropped
+            inner.permits.iter().for_each(|permits| permits.close());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PipelineBuilder;
+    use crate::bee::stock::{Caller, OnceCaller, Thunk, ThunkWorker};
+    use crate::hive::{Builder, Outcome, OutcomeStore};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_pipeline() {
+        let stage1 = Builder::new()
+            .num_threads(2)
+            .build_with(Caller::of(|i: usize| i + 1))
+            .unwrap();
+        let stage2 = Builder::new()
+            .num_threads(4)
+            .build_with(Caller::of(|i: usize| i.to_string()))
+            .unwrap();
+        let stage3 = Builder::new()
+            .num_threads(2)
+            .build_with(Caller::of(|s: String| s.len()))
+            .unwrap();
+        let pipeline = PipelineBuilder::new(stage1, 4)
+            .then(stage2, 4)
+            .then(stage3, 4)
+            .build(100);
+        pipeline.submit_all(0..100);
+        pipeline.join();
+        assert_eq!(pipeline.num_in_flight(), 0);
+        let total: usize = pipeline.outputs().sum();
+        assert_eq!(total, 9 + 90 * 2 + 3);
+        let ((husk1, husk2), husk3) = pipeline.into_husks().0.unwrap();
+        assert!(husk1.is_empty());
+        assert!(husk2.is_empty());
+        assert!(husk3.is_empty());
+    }
+
+    type Stage2Fn = fn(usize) -> Result<usize, usize>;
+
+    fn stage2_fn(i: usize) -> Result<usize, usize> {
+        if i % 10 == 5 {
+            Err(i)
+        } else {
+            Ok(i)
+        }
+    }
+
+    #[test]
+    fn test_failures() {
+        let stage1 = Builder::new()
+            .num_threads(2)
+            .build_with(OnceCaller::of(|i: usize| {
+                if i % 10 == 0 {
+                    Err("tens")
+                } else {
+                    Ok(i)
+                }
+            }))
+            .unwrap();
+        let stage2 = Builder::new()
+            .num_threads(2)
+            .build_with(OnceCaller::of(stage2_fn as Stage2Fn))
+            .unwrap();
+        let pipeline = PipelineBuilder::new(stage1, 4).then(stage2, 4).build(100);
+        pipeline.submit_all(0..100);
+        assert_eq!(pipeline.outputs().count(), 80);
+        let failures: Vec<_> = pipeline.failures().collect();
+        assert_eq!(failures.len(), 20);
+        let (stage1_failures, stage2_failures): (Vec<_>, Vec<_>) = failures
+            .into_iter()
+            .partition(|failure| failure.stage() == 0);
+        assert_eq!(stage1_failures.len(), 10);
+        assert_eq!(stage2_failures.len(), 10);
+        let failure = stage2_failures.into_iter().next().unwrap();
+        // downcasting to the wrong type fails
+        let failure = failure
+            .downcast::<ThunkWorker<usize>>()
+            .expect_err("wrong worker type");
+        assert!(matches!(
+            failure.downcast::<OnceCaller<usize, usize, usize, Stage2Fn>>(),
+            Ok(Outcome::Failure { error, .. }) if error % 10 == 5
+        ));
+    }
+
+    #[test]
+    fn test_panic() {
+        let stage1 = Builder::new()
+            .num_threads(2)
+            .build_with_default::<ThunkWorker<usize>>()
+            .unwrap();
+        let stage2 = Builder::new()
+            .num_threads(2)
+            .build_with(Caller::of(|i: usize| i))
+            .unwrap();
+        // the capacity is less than the number of lost tasks, so their permits must be released
+        let pipeline = PipelineBuilder::new(stage1, 2).then(stage2, 2).build(20);
+        pipeline.submit_all((0..20).map(|i| {
+            Thunk::of(move || {
+                // `ThunkWorker` does not catch panics, so the outcome is lost
+                assert!(i % 3 != 0, "oh no!");
+                i
+            })
+        }));
+        assert_eq!(pipeline.outputs().count(), 13);
+        assert_eq!(pipeline.num_in_flight(), 0);
+        let failures: Vec<_> = pipeline.failures().collect();
+        assert_eq!(failures.len(), 7);
+        assert!(failures.into_iter().all(|failure| failure.stage() == 0
+            && matches!(
+                failure.downcast::<ThunkWorker<usize>>(),
+                Ok(Outcome::Missing { .. })
+            )));
+    }
+
+    #[test]
+    fn test_backpressure() {
+        let num_started = Arc::new(AtomicUsize::new(0));
+        let stage1 = Builder::new()
+            .num_threads(4)
+            .build_with_default::<ThunkWorker<usize>>()
+            .unwrap();
+        let stage2 = Builder::new()
+            .num_threads(1)
+            .build_with(Caller::of(|i: usize| {
+                thread::sleep(Duration::from_millis(100));
+                i
+            }))
+            .unwrap();
+        let pipeline = Arc::new(PipelineBuilder::new(stage1, 2).then(stage2, 2).build(2));
+        let submitter = {
+            let pipeline = Arc::clone(&pipeline);
+            let num_started = Arc::clone(&num_started);
+            thread::spawn(move || {
+                pipeline.submit_all((0..20).map(|i| {
+                    let num_started = Arc::clone(&num_started);
+                    Thunk::of(move || {
+                        num_started.fetch_add(1, Ordering::SeqCst);
+                        i
+                    })
+                }))
+            })
+        };
+        thread::sleep(Duration::from_secs(1));
+        // no outputs have been received, so at most 2 + 2 + 2 (+ 1 in the hand of each forwarding
+        // thread) tasks could have been started
+        assert!(num_started.load(Ordering::SeqCst) <= 8);
+        let outputs: Vec<_> = pipeline.outputs().take(20).collect();
+        assert_eq!(outputs.len(), 20);
+        submitter.join().unwrap();
+        assert_eq!(num_started.load(Ordering::SeqCst), 20);
+    }
+
+    #[test]
+    fn test_suspend() {
+        let stage1 = Builder::new()
+            .num_threads(1)
+            .build_with(Caller::of(|i: usize| i))
+            .unwrap();
+        let stage2 = Builder::new()
+            .num_threads(1)
+            .build_with(Caller::of(|i: usize| i))
+            .unwrap();
+        let pipeline = PipelineBuilder::new(stage1, 10).then(stage2, 10).build(10);
+        pipeline.suspend();
+        assert!(pipeline.is_suspended());
+        pipeline.submit_all(0..5);
+        pipeline.join();
+        assert_eq!(pipeline.num_in_flight(), 5);
+        assert_eq!(pipeline.failures().count(), 0);
+        let (husks, mut outputs, failures) = pipeline.into_husks();
+        let (husk1, husk2) = husks.unwrap();
+        assert!(husk1.is_empty());
+        assert!(husk2.is_empty());
+        outputs.sort();
+        assert_eq!(outputs, vec![0, 1, 2, 3, 4]);
+        assert!(failures.is_empty());
+    }
+}
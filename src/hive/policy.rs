@@ -0,0 +1,123 @@
+//! Policies that determine how a `Hive` responds to worker panics.
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Determines how a `Hive` responds when a `Worker` panics.
+///
+/// Both uncaught panics (which terminate the worker thread) and caught panics (which result in an
+/// `Outcome::Panic`, e.g. from the blanket `Worker` implementation for `RefWorker`) are subject to
+/// the policy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// A worker thread that terminates due to a panic is always replaced with a new thread. This
+    /// is the default policy.
+    #[default]
+    Respawn,
+    /// Like `Respawn`, but the `Hive` is poisoned once more than the given number of panics have
+    /// occurred.
+    RespawnMax(usize),
+    /// Like `Respawn`, but the `Hive` is poisoned once more than `max_panics` panics have occurred
+    /// within a rolling time `window`.
+    RespawnWithin {
+        /// The maximum number of panics allowed within `window`.
+        max_panics: usize,
+        /// The length of the rolling time window in which panics are counted.
+        window: Duration,
+    },
+    /// A worker thread that panics is stopped and is not replaced. Note that this reduces the
+    /// number of threads available to the `Hive` - threads can be added using `Hive::grow`. In a
+    /// deterministic `Hive`, this is treated the same as `Respawn`.
+    Stop,
+    /// Panics are caught and returned as `Outcome::Panic`, and the panic is re-raised on the
+    /// thread that submitted the task if that thread receives the outcome directly (i.e., from
+    /// `apply`, `apply_in_group`, `map`, `swarm`, `map_streaming`, and their `_unordered`
+    /// variants, or `map_reduce`). The worker thread survives.
+    Propagate,
+}
+
+/// Applies a `PanicPolicy` by keeping track of when panics occur.
+#[derive(Debug, Default)]
+pub struct PanicMonitor {
+    policy: PanicPolicy,
+    // the times of recent panics - only as many as are needed to apply the policy are retained
+    times: Mutex<VecDeque<Instant>>,
+}
+
+impl PanicMonitor {
+    pub fn new(policy: PanicPolicy) -> Self {
+        Self {
+            policy,
+            times: Default::default(),
+        }
+    }
+
+    /// Returns the policy.
+    #[inline]
+    pub fn policy(&self) -> PanicPolicy {
+        self.policy
+    }
+
+    /// Records a panic that occurred at time `now`. Returns `true` if the number of panics exceeds
+    /// the maximum allowed by the policy, i.e. the hive should be poisoned.
+    pub fn record(&self, now: Instant) -> bool {
+        let (max_panics, window) = match self.policy {
+            PanicPolicy::RespawnMax(max_panics) => (max_panics, None),
+            PanicPolicy::RespawnWithin { max_panics, window } => (max_panics, Some(window)),
+            _ => return false,
+        };
+        let mut times = self.times.lock();
+        if let Some(window) = window {
+            while times
+                .front()
+                .is_some_and(|time| now.duration_since(*time) > window)
+            {
+                times.pop_front();
+            }
+        }
+        times.push_back(now);
+        if times.len() > max_panics {
+            // there is no need to keep track of more panics than the limit
+            times.pop_front();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PanicMonitor, PanicPolicy};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_respawn() {
+        let monitor = PanicMonitor::new(PanicPolicy::Respawn);
+        let now = Instant::now();
+        assert!((0..100).all(|_| !monitor.record(now)));
+    }
+
+    #[test]
+    fn test_respawn_max() {
+        let monitor = PanicMonitor::new(PanicPolicy::RespawnMax(2));
+        let now = Instant::now();
+        assert!(!monitor.record(now));
+        assert!(!monitor.record(now));
+        assert!(monitor.record(now));
+        assert!(monitor.record(now + Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn test_respawn_within() {
+        let monitor = PanicMonitor::new(PanicPolicy::RespawnWithin {
+            max_panics: 1,
+            window: Duration::from_millis(200),
+        });
+        let now = Instant::now();
+        assert!(!monitor.record(now));
+        let later = now + Duration::from_millis(300);
+        assert!(!monitor.record(later));
+        assert!(monitor.record(later));
+    }
+}
@@ -0,0 +1,138 @@
+//! Tracking of the progress reported by active tasks.
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A snapshot of the progress of an active task.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskProgress {
+    /// The fraction of the task that is complete (between `0.0` and `1.0`), as most recently
+    /// reported by the `Worker`, or `None` if the task has not reported any progress.
+    pub fraction: Option<f64>,
+    /// The message most recently reported by the `Worker`, if any.
+    pub message: Option<String>,
+    /// The index of the worker thread on which the task is running.
+    pub thread_index: usize,
+    /// The name of the worker thread on which the task is running, if it has one.
+    pub thread_name: Option<String>,
+    /// How long the task has been running (on its current attempt), according to the `Hive`'s
+    /// clock.
+    pub elapsed: Duration,
+}
+
+#[derive(Debug)]
+struct ActiveTask {
+    fraction: Option<f64>,
+    message: Option<String>,
+    thread_index: usize,
+    thread_name: Option<String>,
+    started: Instant,
+}
+
+/// Keeps track of the progress of each active task in a `Hive`, keyed by task index.
+#[derive(Debug, Default)]
+pub struct ProgressTracker(Mutex<HashMap<usize, ActiveTask>>);
+
+impl ProgressTracker {
+    /// Records that the task with the given `index` has started at time `now` on the current
+    /// thread, which is the worker thread with the given `thread_index`.
+    pub fn start(&self, index: usize, thread_index: usize, now: Instant) {
+        let task = ActiveTask {
+            fraction: None,
+            message: None,
+            thread_index,
+            thread_name: thread::current().name().map(String::from),
+            started: now,
+        };
+        self.0.lock().insert(index, task);
+    }
+
+    /// Updates the progress of the task with the given `index`. Does nothing if the task is not
+    /// active.
+    pub fn report(&self, index: usize, fraction: f64, message: String) {
+        if let Some(task) = self.0.lock().get_mut(&index) {
+            task.fraction = Some(fraction.clamp(0.0, 1.0));
+            task.message = Some(message);
+        }
+    }
+
+    /// Records that the task with the given `index` is no longer active.
+    pub fn finish(&self, index: usize) {
+        self.0.lock().remove(&index);
+    }
+
+    /// Returns a snapshot of the progress of all active tasks, where the elapsed time of each task
+    /// is measured up to `now`.
+    pub fn snapshot(&self, now: Instant) -> HashMap<usize, TaskProgress> {
+        self.0
+            .lock()
+            .iter()
+            .map(|(index, task)| {
+                let progress = TaskProgress {
+                    fraction: task.fraction,
+                    message: task.message.clone(),
+                    thread_index: task.thread_index,
+                    thread_name: task.thread_name.clone(),
+                    elapsed: now.saturating_duration_since(task.started),
+                };
+                (*index, progress)
+            })
+            .collect()
+    }
+}
+
+/// Removes a task from its `ProgressTracker` when dropped, including if the worker panics.
+pub struct ProgressGuard<'a> {
+    tracker: &'a ProgressTracker,
+    index: usize,
+}
+
+impl<'a> ProgressGuard<'a> {
+    pub fn new(
+        tracker: &'a ProgressTracker,
+        index: usize,
+        thread_index: usize,
+        now: Instant,
+    ) -> Self {
+        tracker.start(index, thread_index, now);
+        Self { tracker, index }
+    }
+}
+
+impl Drop for ProgressGuard<'_> {
+    fn drop(&mut self) {
+        self.tracker.finish(self.index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProgressGuard, ProgressTracker};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_progress() {
+        let tracker = ProgressTracker::default();
+        let start = Instant::now();
+        let guard = ProgressGuard::new(&tracker, 5, 1, start);
+        tracker.start(6, 2, start + Duration::from_secs(1));
+        tracker.report(5, 1.5, "done".into());
+        // ignored because task 7 is not active
+        tracker.report(7, 0.5, "nope".into());
+        let snapshot = tracker.snapshot(start + Duration::from_secs(3));
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[&5].fraction, Some(1.0));
+        assert_eq!(snapshot[&5].message.as_deref(), Some("done"));
+        assert_eq!(snapshot[&5].thread_index, 1);
+        assert_eq!(snapshot[&6].fraction, None);
+        assert_eq!(snapshot[&6].thread_index, 2);
+        // elapsed time is measured from the given start time
+        assert_eq!(snapshot[&5].elapsed, Duration::from_secs(3));
+        assert_eq!(snapshot[&6].elapsed, Duration::from_secs(2));
+        drop(guard);
+        tracker.finish(6);
+        assert!(tracker.snapshot(Instant::now()).is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+//! Limits on the number of outcomes that are stored in a `Hive`.
+use super::Outcome;
+use crate::bee::Worker;
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Determines what a `Hive` does when a worker thread needs to store an outcome but the maximum
+/// number of stored outcomes (set with `Builder::max_stored_outcomes`) has been reached.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StoreLimitPolicy {
+    /// The outcome that has been stored for the longest time is dropped to make room for the new
+    /// outcome. This is the default policy.
+    #[default]
+    DropOldest,
+    /// The `Outcome::Success` that has been stored for the longest time is dropped to make room
+    /// for the new outcome. If there are no stored successes, the oldest outcome is dropped.
+    DropSuccessesFirst,
+    /// The worker thread blocks until there is room for the new outcome, i.e., until stored
+    /// outcomes are removed (e.g., using `take_stored`). Note that this means no further tasks are
+    /// processed by that thread until outcomes are removed. Waiting threads are released when the
+    /// `Hive` is poisoned, and the limit is ignored. A deterministic `Hive` drops the oldest
+    /// outcome instead of blocking.
+    ///
+    /// A task whose worker thread is blocked is still active, so `Hive::join` does not return
+    /// until its outcome has been stored. Calling `join` and then `take_stored` blocks forever if
+    /// more outcomes are stored than the limit allows; instead, remove outcomes while the tasks
+    /// are running, e.g., using `Hive::stored_iter` or `Hive::drain_stored_blocking`.
+    Block,
+    /// The oldest outcome is removed and sent to the channel set with `Hive::set_store_overflow`.
+    /// If there is no overflow channel, or the send fails, the outcome is dropped.
+    Overflow,
+}
+
+/// Enforces a limit on the number of stored outcomes by keeping track of the order in which they
+/// were stored.
+#[derive(Debug)]
+pub struct StoreLimiter {
+    max: usize,
+    policy: StoreLimitPolicy,
+    // indices of stored outcomes in the order they were stored; may contain the indices of
+    // outcomes that have since been removed from the store, which are skipped during eviction
+    order: Mutex<VecDeque<usize>>,
+    // whether the store was full the last time an outcome was stored
+    full: AtomicBool,
+    // number of outcomes that have been dropped
+    num_dropped: AtomicUsize,
+}
+
+impl StoreLimiter {
+    /// Returns a new `StoreLimiter` if `max` is `Some`.
+    pub fn new(max: Option<usize>, policy: StoreLimitPolicy) -> Option<Self> {
+        max.map(|max| Self {
+            max,
+            policy,
+            order: Default::default(),
+            full: Default::default(),
+            num_dropped: Default::default(),
+        })
+    }
+
+    /// Returns the maximum number of stored outcomes.
+    #[inline]
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Returns the policy.
+    #[inline]
+    pub fn policy(&self) -> StoreLimitPolicy {
+        self.policy
+    }
+
+    /// Returns `true` if there is no room in `outcomes` for an outcome with the given `index`.
+    pub fn is_full<W: Worker>(&self, outcomes: &HashMap<usize, Outcome<W>>, index: usize) -> bool {
+        outcomes.len() >= self.max && !outcomes.contains_key(&index)
+    }
+
+    /// Records whether the store is `full`. Returns `true` if the store was not full the last time
+    /// this method was called, i.e., the limit has just been reached.
+    pub fn set_full(&self, full: bool) -> bool {
+        let was_full = self.full.swap(full, Ordering::Relaxed);
+        full && !was_full
+    }
+
+    /// Records that an outcome was dropped.
+    pub fn add_dropped(&self) {
+        self.num_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the number of outcomes that have been dropped.
+    pub fn num_dropped(&self) -> usize {
+        self.num_dropped.load(Ordering::Relaxed)
+    }
+
+    /// Inserts `outcome` into `outcomes`, first removing an outcome according to the policy if
+    /// there is no room for it. Returns the removed outcome, if any.
+    pub fn insert<W: Worker>(
+        &self,
+        outcomes: &mut HashMap<usize, Outcome<W>>,
+        outcome: Outcome<W>,
+    ) -> Option<Outcome<W>> {
+        let index = *outcome.index();
+        let mut order = self.order.lock();
+        let evicted = if self.is_full(outcomes, index) {
+            self.evict(outcomes, &mut order)
+        } else {
+            None
+        };
+        if outcomes.insert(index, outcome).is_none() {
+            order.push_back(index);
+        }
+        // discard the indices of outcomes that have been removed so `order` doesn't grow unbounded
+        if order.len() > 2 * self.max {
+            order.retain(|index| outcomes.contains_key(index));
+        }
+        evicted
+    }
+
+    fn evict<W: Worker>(
+        &self,
+        outcomes: &mut HashMap<usize, Outcome<W>>,
+        order: &mut VecDeque<usize>,
+    ) -> Option<Outcome<W>> {
+        if self.policy == StoreLimitPolicy::DropSuccessesFirst {
+            let pos = order
+                .iter()
+                .position(|index| matches!(outcomes.get(index), Some(Outcome::Success { .. })));
+            if let Some(index) = pos.and_then(|pos| order.remove(pos)) {
+                return outcomes.remove(&index);
+            }
+        }
+        while let Some(index) = order.pop_front() {
+            if let Some(outcome) = outcomes.remove(&index) {
+                return Some(outcome);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StoreLimitPolicy, StoreLimiter};
+    use crate::bee::stock::EchoWorker;
+    use crate::hive::Outcome;
+    use std::collections::HashMap;
+
+    type TestOutcome = Outcome<EchoWorker<usize>>;
+
+    fn success(index: usize) -> TestOutcome {
+        Outcome::Success {
+            value: index,
+            index,
+        }
+    }
+
+    fn unprocessed(index: usize) -> TestOutcome {
+        Outcome::Unprocessed {
+            input: index,
+            index,
+        }
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        let limiter = StoreLimiter::new(Some(2), StoreLimitPolicy::DropOldest).unwrap();
+        let mut outcomes = HashMap::new();
+        // outcomes are evicted in the order they were stored, regardless of index
+        assert!(limiter.insert(&mut outcomes, success(5)).is_none());
+        assert!(limiter.insert(&mut outcomes, success(1)).is_none());
+        assert!(limiter.is_full(&outcomes, 3));
+        assert!(!limiter.is_full(&outcomes, 1));
+        let evicted = limiter.insert(&mut outcomes, success(3)).unwrap();
+        assert_eq!(*evicted.index(), 5);
+        // an outcome that was removed from the store is skipped
+        outcomes.remove(&1);
+        assert!(limiter.insert(&mut outcomes, success(4)).is_none());
+        let evicted = limiter.insert(&mut outcomes, success(6)).unwrap();
+        assert_eq!(*evicted.index(), 3);
+        let mut indices: Vec<_> = outcomes.keys().copied().collect();
+        indices.sort();
+        assert_eq!(indices, vec![4, 6]);
+    }
+
+    #[test]
+    fn test_drop_successes_first() {
+        let limiter = StoreLimiter::new(Some(3), StoreLimitPolicy::DropSuccessesFirst).unwrap();
+        let mut outcomes = HashMap::new();
+        limiter.insert(&mut outcomes, unprocessed(0));
+        limiter.insert(&mut outcomes, success(1));
+        limiter.insert(&mut outcomes, unprocessed(2));
+        let evicted = limiter.insert(&mut outcomes, unprocessed(3)).unwrap();
+        assert_eq!(*evicted.index(), 1);
+        // there are no more successes, so the oldest outcome is evicted
+        let evicted = limiter.insert(&mut outcomes, success(4)).unwrap();
+        assert_eq!(*evicted.index(), 0);
+    }
+
+    #[test]
+    fn test_set_full() {
+        let limiter = StoreLimiter::new(Some(1), StoreLimitPolicy::default()).unwrap();
+        assert!(!limiter.set_full(false));
+        assert!(limiter.set_full(true));
+        assert!(!limiter.set_full(true));
+        assert!(!limiter.set_full(false));
+        assert!(limiter.set_full(true));
+    }
+}
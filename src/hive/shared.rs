@@ -1,34 +1,77 @@
+use super::apiary::ApiaryLink;
 use super::counter::{self, DualCounter};
-use super::{Config, Outcome, OutcomeSender, Shared, Task, TaskReceiver};
+use super::group::{TaskGroupStats, TaskGroups};
+use super::limit::RateLimiter;
+use super::policy::{PanicMonitor, PanicPolicy};
+use super::progress::ProgressGuard;
+use super::retention::{StoreLimitPolicy, StoreLimiter};
+use super::{
+    Config, Gate, Outcome, OutcomeSender, RateLimitKeyFn, Shared, StoreLimitFn, Task, TaskProgress,
+    TaskReceiver,
+};
 use crate::atomic::{Atomic, AtomicInt, AtomicUsize};
 use crate::bee::{Context, Queen, Worker};
 use crate::channel::SenderExt;
-use parking_lot::Mutex;
+use parking_lot::{Mutex, MutexGuard};
 use std::collections::HashMap;
-use std::ops::DerefMut;
+use std::ops::{Deref, DerefMut};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
 use std::thread::Builder;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{fmt, iter, mem};
 
 impl<W: Worker, Q: Queen<Kind = W>> Shared<W, Q> {
     pub fn new(config: Config, queen: Q, task_rx: TaskReceiver<W>) -> Self {
+        let rate_limit = config.rate_limit.get().map(|limit| {
+            config
+                .rate_limit_burst
+                .get()
+                .map(|burst| limit.with_burst(burst))
+                .unwrap_or(limit)
+        });
+        let clock = config.clock.get().unwrap_or_default();
+        let rate_limiter = RateLimiter::new(rate_limit, config.key_rate_limit.get(), clock.now());
+        let groups = TaskGroups::new(config.group_limits.get().unwrap_or_default());
+        let panic_monitor = PanicMonitor::new(config.panic_policy.get().unwrap_or_default());
+        let store_limiter = StoreLimiter::new(
+            config.max_stored_outcomes.get(),
+            config.store_limit_policy.get().unwrap_or_default(),
+        );
+        let next_task_index = AtomicUsize::new(config.next_task_index.get_or_default());
         Shared {
             config,
             queen: Mutex::new(queen),
             task_rx: Mutex::new(task_rx),
             num_tasks: DualCounter::default(),
-            next_task_index: Default::default(),
+            next_task_index,
             num_panics: Default::default(),
             num_referrers: AtomicUsize::new(1),
             poisoned: Default::default(),
+            closed: Default::default(),
             suspended: Default::default(),
             resume_gate: Default::default(),
             join_gate: Default::default(),
             outcomes: Default::default(),
+            stored_gate: Default::default(),
+            store_limiter,
+            store_overflow: Default::default(),
+            store_limit_handler: Default::default(),
+            apiary: Default::default(),
+            rate_limiter,
+            rate_limit_key: Default::default(),
+            rate_deferred: Default::default(),
+            deferred_gate: Default::default(),
+            groups: Mutex::new(groups),
+            progress: Default::default(),
+            panic_monitor,
+            inline_lock: Default::default(),
+            inline_pending: Default::default(),
             #[cfg(feature = "retry")]
             retry_queue: Default::default(),
             #[cfg(feature = "retry")]
             next_retry: Default::default(),
+            clock,
         }
     }
 
@@ -71,6 +114,21 @@ impl<W: Worker, Q: Queen<Kind = W>> Shared<W, Q> {
         Task::new(input, ctx, outcome_tx)
     }
 
+    /// Like `prepare_task`, but the task is given the specified `index` rather than the next
+    /// index in the sequence. This is used to resubmit a task under its original index.
+    pub fn prepare_task_with_index(
+        &self,
+        input: W::Input,
+        index: usize,
+        outcome_tx: Option<OutcomeSender<W>>,
+    ) -> Task<W> {
+        self.num_tasks
+            .increment_left(1)
+            .expect("overflowed queued task counter");
+        let ctx = Context::new(index, self.suspended.clone());
+        Task::new(input, ctx, outcome_tx)
+    }
+
     /// Increments the number of queued tasks by the number of provided inputs. Returns an iterator
     /// over `Task`s created from the provided inputs, `outcome_tx`s, and sequential indices.
     pub fn prepare_batch<'a, T: Iterator<Item = W::Input> + 'a>(
@@ -126,7 +184,7 @@ impl<W: Worker, Q: Queen<Kind = W>> Shared<W, Q> {
     {
         // don't unlock outcomes unless we have to
         let mut outcomes = Option::None;
-        tasks
+        let indices = tasks
             .map(|task| {
                 let index = task.index();
                 if let Some(outcome) = task.into_unprocessed_try_send() {
@@ -136,7 +194,211 @@ impl<W: Worker, Q: Queen<Kind = W>> Shared<W, Q> {
                 }
                 index
             })
-            .collect()
+            .collect();
+        if outcomes.take().is_some() {
+            self.stored_gate.notify_all();
+        }
+        indices
+    }
+
+    /// Sets the function used to determine the rate-limit key of each task.
+    pub fn set_rate_limit_key(&self, key_fn: RateLimitKeyFn<W>) {
+        self.rate_limit_key.write().replace(key_fn);
+    }
+
+    /// Returns the rate-limit key of `task`, if there is a key function.
+    fn rate_limit_key(&self, task: &Task<W>) -> Option<u64> {
+        self.rate_limit_key
+            .read()
+            .as_ref()
+            .and_then(|key_fn| key_fn(&task.input))
+    }
+
+    /// Called by a worker thread before starting a task. Returns the task if it is permitted to
+    /// start by the rate limiter (or if there is no rate limiter). Otherwise, the task is deferred
+    /// until it may be permitted to start and `None` is returned, so that the worker thread does
+    /// not hold on to the task while waiting. The task is still counted as queued while deferred.
+    fn try_start_rate_limited(&self, task: Task<W>) -> Option<Task<W>> {
+        let Some(rate_limiter) = self.rate_limiter.as_ref() else {
+            return Some(task);
+        };
+        let now = self.clock.now();
+        match rate_limiter.try_acquire(self.rate_limit_key(&task), now) {
+            Ok(_) => Some(task),
+            Err(wait) => {
+                self.rate_deferred.lock().push(task, now + wait);
+                None
+            }
+        }
+    }
+
+    /// Returns a task that was deferred by the rate limiter and that is now permitted to start,
+    /// if any.
+    fn next_rate_deferred_task(&self) -> Option<Task<W>> {
+        let rate_limiter = self.rate_limiter.as_ref()?;
+        let mut rate_deferred = self.rate_deferred.lock();
+        if rate_deferred.is_empty() {
+            return None;
+        }
+        let now = self.clock.now();
+        rate_deferred.pop_ready(now, |task| {
+            rate_limiter.try_acquire(self.rate_limit_key(task), now)
+        })
+    }
+
+    /// Returns the earliest time (according to the clock) at which a task that was deferred by
+    /// the rate limiter may be permitted to start, or at which a task that is waiting to be retried
+    /// becomes available, if there are any such tasks.
+    fn next_deferred_at(&self) -> Option<Instant> {
+        let next_at = self
+            .rate_limiter
+            .as_ref()
+            .and_then(|_| self.rate_deferred.lock().next_ready());
+        #[cfg(feature = "retry")]
+        let next_at = match (next_at, *self.next_retry.read()) {
+            (Some(ready_at), Some(retry_at)) => Some(ready_at.min(retry_at)),
+            (ready_at, retry_at) => ready_at.or(retry_at),
+        };
+        next_at
+    }
+
+    /// Returns the (real) time to wait for a new task: at most `timeout`, but no longer than until
+    /// the clock needs to be checked for the next deferred task (see `next_deferred_at`).
+    pub fn deferred_timeout(&self, timeout: Duration) -> Duration {
+        self.next_deferred_at().map_or(timeout, |next_at| {
+            timeout.min(self.clock.timeout_until(next_at))
+        })
+    }
+
+    /// Receives the next task from the task channel, waiting for at most `timeout`, but no longer
+    /// than until the next deferred task may become available. This includes the time spent
+    /// waiting for another thread that is receiving a task.
+    fn recv_task_timeout(&self, timeout: Duration) -> Result<Task<W>, RecvTimeoutError> {
+        let deadline = Instant::now() + self.deferred_timeout(timeout);
+        let task_rx = self
+            .task_rx
+            .try_lock_until(deadline)
+            .ok_or(RecvTimeoutError::Timeout)?;
+        task_rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Called by a worker thread when there are no new tasks but there are deferred tasks. Waits
+    /// for at most `timeout`, but no longer than until the next deferred task may become
+    /// available, or until a task group with deferred tasks is no longer saturated. Stops waiting
+    /// early if the hive is suspended or poisoned.
+    fn wait_for_deferred(&self, timeout: Duration) {
+        self.deferred_gate.wait_while_until(
+            || {
+                !self.is_suspended()
+                    && !self.is_poisoned()
+                    && !self.groups.lock().has_startable_deferred()
+            },
+            Instant::now() + self.deferred_timeout(timeout),
+        );
+    }
+
+    /// Returns the next queued `Task` without blocking, except to wait (according to the clock)
+    /// for a task that is waiting to be retried or that was deferred by the rate limiter if there
+    /// are no other tasks. Returns `None` if there are no tasks available, or if the hive is
+    /// suspended or poisoned. Used when the hive is deterministic.
+    pub fn try_next_task(&self) -> Option<Task<W>> {
+        loop {
+            if let Some(task) = self.next_task_until(self.clock.now()) {
+                return Some(task);
+            }
+            if self.is_suspended() || self.is_poisoned() {
+                return None;
+            }
+            let next_at = self.next_deferred_at()?;
+            self.clock
+                .sleep(next_at.saturating_duration_since(self.clock.now()));
+        }
+    }
+
+    /// Returns the current time according to the hive's clock.
+    #[inline]
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    /// Returns the shared name of the task `group`, creating it (without a concurrency limit) if
+    /// it does not already exist.
+    pub fn intern_group(&self, group: &str) -> Arc<str> {
+        self.groups.lock().intern(group)
+    }
+
+    /// Sets the maximum number of tasks in `group` that may be active at the same time.
+    pub fn set_group_limit(&self, group: &str, max_concurrency: Option<usize>) {
+        self.groups.lock().set_limit(group, max_concurrency);
+    }
+
+    /// Returns a snapshot of the state of each task group.
+    pub fn group_stats(&self) -> HashMap<String, TaskGroupStats> {
+        self.groups.lock().stats()
+    }
+
+    /// Called by a worker thread after receiving a task that is permitted to start by the rate
+    /// limiter. If the task belongs to a group that is saturated, the task is deferred and `None`
+    /// is returned, otherwise the task is returned.
+    fn try_start_in_group(&self, task: Task<W>) -> Option<Task<W>> {
+        match task.ctx.group_name().cloned() {
+            Some(group) => self.groups.lock().try_start(&group, task),
+            None => Some(task),
+        }
+    }
+
+    /// Called by a worker thread after receiving a task. Returns the task if it may be started,
+    /// i.e., if it is permitted to start by the rate limiter and its group is not saturated.
+    /// Otherwise, the task is deferred and `None` is returned.
+    ///
+    /// The rate limit is checked first, so a task that is deferred by the rate limiter does not
+    /// hold a slot in its group while it waits.
+    fn try_start(&self, task: Task<W>) -> Option<Task<W>> {
+        self.try_start_rate_limited(task)
+            .and_then(|task| self.try_start_in_group(task))
+    }
+
+    /// Returns a previously deferred task that may now be started, if any. A task whose group is
+    /// no longer saturated is returned first (it has already been permitted to start by the rate
+    /// limiter), otherwise a task deferred by the rate limiter is returned if it is now permitted
+    /// to start and its group is not saturated.
+    fn next_deferred_task(&self) -> Option<Task<W>> {
+        if let Some(task) = self.groups.lock().next_deferred() {
+            return Some(task);
+        }
+        loop {
+            let task = self.next_rate_deferred_task()?;
+            if let Some(task) = self.try_start_in_group(task) {
+                return Some(task);
+            }
+        }
+    }
+
+    /// Returns `true` if there are tasks that were deferred because their group was saturated or
+    /// because they were not yet permitted to start by the rate limiter.
+    fn has_deferred_tasks(&self) -> bool {
+        self.groups.lock().has_deferred() || !self.rate_deferred.lock().is_empty()
+    }
+
+    /// Returns a guard that marks the task as finished within its group (if any) when dropped,
+    /// which happens even if the worker panics while executing the task.
+    pub fn group_guard(&self, task: &Task<W>) -> Option<GroupGuard<'_, W, Q>> {
+        task.ctx.group_name().map(|group| GroupGuard {
+            shared: self,
+            group: Arc::clone(group),
+        })
+    }
+
+    /// Called by a worker thread before executing `task` to enable the task to report its
+    /// progress. The task is tracked until the returned guard is dropped.
+    pub fn track_progress(&self, task: &mut Task<W>, thread_index: usize) -> ProgressGuard<'_> {
+        task.ctx.set_progress(Arc::clone(&self.progress));
+        ProgressGuard::new(&self.progress, task.index(), thread_index, self.now())
+    }
+
+    /// Returns a snapshot of the progress of each active task.
+    pub fn progress(&self) -> HashMap<usize, TaskProgress> {
+        self.progress.snapshot(self.now())
     }
 
     /// Called by a worker thread after completing a task. Notifies any thread that has `join`ed
@@ -147,10 +409,99 @@ impl<W: Worker, Q: Queen<Kind = W>> Shared<W, Q> {
             .expect("active task counter was smaller than expected");
         if panicking {
             self.num_panics.add(1);
+            self.record_panic();
         }
         self.no_work_notify_all();
     }
 
+    /// Returns the panic policy.
+    #[inline]
+    pub fn panic_policy(&self) -> PanicPolicy {
+        self.panic_monitor.policy()
+    }
+
+    /// Called when a worker panics (whether or not the panic was caught). Poisons the hive if the
+    /// number of panics exceeds the maximum allowed by the panic policy.
+    pub fn record_panic(&self) {
+        if self.panic_monitor.record(self.clock.now()) && !self.is_poisoned() {
+            self.poison();
+        }
+    }
+
+    /// Returns the maximum number of tasks a worker thread executes as a single batch.
+    #[inline]
+    pub fn batch_size(&self) -> usize {
+        self.config.batch_size.get().unwrap_or(1)
+    }
+
+    /// Returns a batch of tasks that starts with `task` and contains at most `batch_size` tasks.
+    /// Additional tasks are added to the batch as they become available, until the batch is full
+    /// or the batch timeout has elapsed.
+    pub fn next_batch(&self, task: Task<W>) -> Vec<Task<W>> {
+        let batch_size = self.batch_size();
+        let timeout = self.config.batch_timeout.get().unwrap_or_default();
+        let deadline = self.clock.now() + timeout;
+        let mut batch = Vec::with_capacity(batch_size);
+        batch.push(task);
+        while batch.len() < batch_size {
+            match self.next_task_until(deadline) {
+                Some(task) => batch.push(task),
+                None => break,
+            }
+        }
+        batch
+    }
+
+    /// Sets the link to the `Apiary` with which the hive is registered, or removes it if `link` is
+    /// `None`. Returns `false` if the hive is already registered with an `Apiary`.
+    pub fn set_apiary(&self, link: Option<ApiaryLink>) -> bool {
+        let mut apiary = self.apiary.write();
+        if link.is_some() && apiary.is_some() {
+            return false;
+        }
+        *apiary = link;
+        true
+    }
+
+    /// Removes and returns the link to the `Apiary` with which the hive is registered, if any.
+    pub fn take_apiary(&self) -> Option<ApiaryLink> {
+        self.apiary.write().take()
+    }
+
+    /// Returns `true` if the hive is registered with an `Apiary`.
+    pub fn has_apiary(&self) -> bool {
+        self.apiary.read().is_some()
+    }
+
+    /// Notifies the `Apiary` with which the hive is registered (if any) that tasks are available.
+    pub fn wake_apiary(&self) {
+        if let Some(link) = self.apiary.read().as_ref() {
+            link.wake();
+        }
+    }
+
+    /// Returns `true` if tasks are executed on the submitting thread rather than by worker threads.
+    #[inline]
+    pub fn is_deterministic(&self) -> bool {
+        self.config.deterministic.get_or_default()
+    }
+
+    /// Tries to acquire the lock that allows the current thread to execute tasks when the hive is
+    /// deterministic. Returns `None` if another thread is currently executing tasks, in which case
+    /// that thread will also execute any tasks submitted by this thread.
+    pub fn try_lock_inline(&self) -> Option<MutexGuard<'_, ()>> {
+        self.inline_pending.set(true);
+        let lock = self.inline_lock.try_lock()?;
+        self.inline_pending.set(false);
+        Some(lock)
+    }
+
+    /// Returns `true` if a thread submitted tasks while another thread held the inline lock, i.e.,
+    /// those tasks may not yet have been executed.
+    pub fn has_inline_pending(&self) -> bool {
+        self.inline_pending.get()
+    }
+
     /// Returns a tuple with the number of (queued, active) tasks.
     #[inline]
     pub fn num_tasks(&self) -> (u64, u64) {
@@ -173,10 +524,28 @@ impl<W: Worker, Q: Queen<Kind = W>> Shared<W, Q> {
         self.join_gate.wait_while(|| self.has_work());
     }
 
+    /// Blocks the current thread until all tasks have been processed (as with `wait_on_done`) or
+    /// until `deadline` (according to the clock). Returns `true` if there is no more work to do.
+    pub fn wait_on_done_until(&self, deadline: Instant) -> bool {
+        while self.has_work() {
+            if self.clock.now() >= deadline {
+                return false;
+            }
+            self.join_gate.wait_while_until(
+                || self.has_work(),
+                Instant::now() + self.clock.timeout_until(deadline),
+            );
+        }
+        true
+    }
+
     /// Notify all observers joining this hive when there is no more work to do.
     pub fn no_work_notify_all(&self) {
         if !self.has_work() {
             self.join_gate.notify_all();
+            // threads waiting for outcomes to be stored also need to know when there is no more
+            // work, since no more outcomes will be stored
+            self.stored_gate.notify_all();
         }
     }
 
@@ -200,6 +569,17 @@ impl<W: Worker, Q: Queen<Kind = W>> Shared<W, Q> {
     pub fn poison(&self) {
         self.poisoned.set(true);
         self.drain_tasks_into_unprocessed();
+        // wake up any worker threads waiting for the hive to be resumed (or for deferred tasks)
+        // so they can terminate
+        self.resume_gate.notify_all();
+        self.deferred_gate.notify_all();
+    }
+
+    /// Sets the `suspended` flag to signal cancellation to active tasks, then poisons the hive so
+    /// that no queued tasks will be started and all worker threads terminate.
+    pub fn abort(&self) {
+        self.suspended.set(true);
+        self.poison();
     }
 
     /// Returns `true` if the hive has been poisoned. A poisoned have may accept new tasks but will
@@ -210,13 +590,47 @@ impl<W: Worker, Q: Queen<Kind = W>> Shared<W, Q> {
         self.poisoned.get()
     }
 
+    /// Sets the `closed` flag to `true`. Returns `true` if the value was changed.
+    pub fn close(&self) -> bool {
+        !self.closed.set(true)
+    }
+
+    /// Returns `true` if the hive has been closed, i.e., it no longer accepts new tasks.
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.closed.get()
+    }
+
+    /// Converts each `Task` in the iterator into `Outcome::Unprocessed` (as with
+    /// `send_or_store_as_unprocessed`) and removes the tasks from the queued task count. This is
+    /// used for tasks that are prepared but never queued because the hive is closed.
+    pub fn reject_tasks<I>(&self, tasks: I) -> Vec<usize>
+    where
+        I: Iterator<Item = Task<W>>,
+    {
+        let indices = self.send_or_store_as_unprocessed(tasks);
+        self.num_tasks
+            .decrement_left(indices.len() as u64)
+            .expect("queued task counter was smaller than expected");
+        self.no_work_notify_all();
+        indices
+    }
+
+    /// Returns `true` if new tasks should be queued, i.e. the hive is neither poisoned nor closed.
+    #[inline]
+    pub fn is_accepting(&self) -> bool {
+        !self.is_poisoned() && !self.is_closed()
+    }
+
     /// Sets the `cancelled` flag. Worker threads may terminate early. No new worker threads will
     /// be spawned. Returns `true` if the value was changed.
     pub fn set_suspended(&self, suspended: bool) -> bool {
         if self.suspended.set(suspended) == suspended {
             false
         } else {
-            if !suspended {
+            if suspended {
+                self.deferred_gate.notify_all();
+            } else {
                 self.resume_gate.notify_all();
             }
             true
@@ -231,24 +645,174 @@ impl<W: Worker, Q: Queen<Kind = W>> Shared<W, Q> {
 
     /// Returns a mutable reference to the retained task outcomes.
     pub fn outcomes(&self) -> impl DerefMut<Target = HashMap<usize, Outcome<W>>> + '_ {
-        self.outcomes.lock()
+        OutcomesGuard::new(self.outcomes.lock(), &self.stored_gate)
     }
 
-    /// Adds a new outcome to the retained task outcomes.
+    /// Adds a new outcome to the retained task outcomes. If there is a limit on the number of
+    /// stored outcomes and it has been reached, the limit policy is applied.
     pub fn add_outcome(&self, outcome: Outcome<W>) {
-        let mut lock = self.outcomes.lock();
-        lock.insert(*outcome.index(), outcome);
+        match self.store_limiter.as_ref() {
+            Some(limiter) => self.add_outcome_limited(outcome, limiter),
+            None => {
+                self.outcomes.lock().insert(*outcome.index(), outcome);
+            }
+        }
+        self.stored_gate.notify_all();
+    }
+
+    fn add_outcome_limited(&self, outcome: Outcome<W>, limiter: &StoreLimiter) {
+        let index = *outcome.index();
+        let mut outcomes = self.outcomes.lock();
+        if limiter.policy() == StoreLimitPolicy::Block && !self.is_deterministic() {
+            while limiter.is_full(&outcomes, index) && !self.is_poisoned() {
+                drop(outcomes);
+                if limiter.set_full(true) {
+                    self.on_store_limit(limiter.max());
+                }
+                self.stored_gate.wait_while(|| {
+                    limiter.is_full(&self.outcomes.lock(), index) && !self.is_poisoned()
+                });
+                outcomes = self.outcomes.lock();
+            }
+        }
+        let evicted = limiter.insert(&mut outcomes, outcome);
+        let limit_reached = limiter.set_full(outcomes.len() >= limiter.max());
+        drop(outcomes);
+        if let Some(evicted) = evicted {
+            let dropped = match limiter.policy() {
+                StoreLimitPolicy::Overflow => match self.store_overflow.lock().as_ref() {
+                    Some(tx) => tx.try_send_msg(evicted),
+                    None => Some(evicted),
+                },
+                _ => Some(evicted),
+            };
+            if dropped.is_some() {
+                limiter.add_dropped();
+            }
+        }
+        if limit_reached {
+            self.on_store_limit(limiter.max());
+        }
+    }
+
+    /// Calls the store limit handler, if there is one.
+    fn on_store_limit(&self, max: usize) {
+        if let Some(handler) = self.store_limit_handler.read().as_ref() {
+            handler(max);
+        }
+    }
+
+    /// Sets the channel to which outcomes are sent when they are evicted from the store.
+    pub fn set_store_overflow(&self, tx: OutcomeSender<W>) {
+        self.store_overflow.lock().replace(tx);
+    }
+
+    /// Sets the function that is called when the maximum number of stored outcomes is reached.
+    pub fn set_store_limit_handler(&self, handler: StoreLimitFn) {
+        self.store_limit_handler.write().replace(handler);
+    }
+
+    /// Returns the number of outcomes that have been dropped because the maximum number of stored
+    /// outcomes was reached.
+    pub fn num_dropped_outcomes(&self) -> usize {
+        self.store_limiter
+            .as_ref()
+            .map(StoreLimiter::num_dropped)
+            .unwrap_or_default()
+    }
+
+    /// Returns `true` if an outcome is stored for every one of `indices`.
+    fn has_stored_all(&self, indices: &[usize]) -> bool {
+        let outcomes = self.outcomes.lock();
+        indices.iter().all(|index| outcomes.contains_key(index))
+    }
+
+    /// Returns the first of `indices` for which an outcome is stored, if any.
+    fn find_stored(&self, indices: &[usize]) -> Option<usize> {
+        let outcomes = self.outcomes.lock();
+        indices
+            .iter()
+            .find(|index| outcomes.contains_key(index))
+            .copied()
+    }
+
+    /// Blocks the current thread until outcomes for all of `indices` have been stored, or until
+    /// `deadline`. Returns `true` if all the outcomes are stored.
+    pub fn wait_for_stored(&self, indices: &[usize], deadline: Instant) -> bool {
+        while !self.has_stored_all(indices) {
+            if self.clock.now() >= deadline {
+                return false;
+            }
+            self.stored_gate.wait_while_until(
+                || !self.has_stored_all(indices),
+                Instant::now() + self.clock.timeout_until(deadline),
+            );
+        }
+        true
+    }
+
+    /// Blocks the current thread until an outcome for any of `indices` has been stored, and
+    /// returns its index. Returns `None` immediately if `indices` is empty, or once there is no
+    /// more work to be done (see `has_work`) and none of the outcomes has been stored.
+    pub fn wait_for_any_stored(&self, indices: &[usize]) -> Option<usize> {
+        if indices.is_empty() {
+            return None;
+        }
+        self.stored_gate
+            .wait_while(|| self.find_stored(indices).is_none() && self.has_work());
+        self.find_stored(indices)
+    }
+
+    /// Returns `true` if an outcome is stored for any of `indices`, or for any index if `indices`
+    /// is `None`.
+    fn has_stored_any(&self, indices: Option<&[usize]>) -> bool {
+        match indices {
+            Some(indices) => self.find_stored(indices).is_some(),
+            None => !self.outcomes.lock().is_empty(),
+        }
+    }
+
+    /// Removes and returns the stored outcome for any of `indices`, or for any index if `indices`
+    /// is `None`.
+    fn try_take_stored(&self, indices: Option<&[usize]>) -> Option<Outcome<W>> {
+        let mut outcomes = self.outcomes();
+        let index = match indices {
+            Some(indices) => indices
+                .iter()
+                .find(|index| outcomes.contains_key(index))
+                .copied(),
+            None => outcomes.keys().next().copied(),
+        }?;
+        outcomes.remove(&index)
+    }
+
+    /// Blocks the current thread until an outcome has been stored for any of `indices` (or for
+    /// any index if `indices` is `None`), then removes and returns it. Returns `None` if there is
+    /// no such outcome and there is no more work to do.
+    pub fn wait_take_stored(&self, indices: Option<&[usize]>) -> Option<Outcome<W>> {
+        loop {
+            if let Some(outcome) = self.try_take_stored(indices) {
+                return Some(outcome);
+            }
+            if !self.has_work() {
+                // an outcome may have been stored after the last check but before the task was
+                // finished
+                return self.try_take_stored(indices);
+            }
+            self.stored_gate
+                .wait_while(|| !self.has_stored_any(indices) && self.has_work());
+        }
     }
 
     /// Removes and returns all retained task outcomes.
     pub fn take_outcomes(&self) -> HashMap<usize, Outcome<W>> {
-        let mut lock = self.outcomes.lock();
+        let mut lock = self.outcomes();
         mem::take(&mut *lock)
     }
 
     /// Removes and returns all retained `Unprocessed` outcomes.
     pub fn take_unprocessed(&self) -> Vec<Outcome<W>> {
-        let mut outcomes = self.outcomes.lock();
+        let mut outcomes = self.outcomes();
         let unprocessed_indices: Vec<_> = outcomes
             .keys()
             .cloned()
@@ -261,6 +825,49 @@ impl<W: Worker, Q: Queen<Kind = W>> Shared<W, Q> {
     }
 }
 
+/// Guard for the stored outcomes that notifies threads waiting on the stored gate if any outcomes
+/// were removed while the lock was held, so that worker threads blocked by the
+/// `StoreLimitPolicy::Block` policy can continue.
+struct OutcomesGuard<'a, W: Worker> {
+    lock: Option<MutexGuard<'a, HashMap<usize, Outcome<W>>>>,
+    len: usize,
+    gate: &'a Gate,
+}
+
+impl<'a, W: Worker> OutcomesGuard<'a, W> {
+    fn new(lock: MutexGuard<'a, HashMap<usize, Outcome<W>>>, gate: &'a Gate) -> Self {
+        let len = lock.len();
+        Self {
+            lock: Some(lock),
+            len,
+            gate,
+        }
+    }
+}
+
+impl<W: Worker> Deref for OutcomesGuard<'_, W> {
+    type Target = HashMap<usize, Outcome<W>>;
+
+    fn deref(&self) -> &Self::Target {
+        self.lock.as_ref().unwrap()
+    }
+}
+
+impl<W: Worker> DerefMut for OutcomesGuard<'_, W> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.lock.as_mut().unwrap()
+    }
+}
+
+impl<W: Worker> Drop for OutcomesGuard<'_, W> {
+    fn drop(&mut self) {
+        let len = self.lock.take().map(|lock| lock.len()).unwrap_or_default();
+        if len < self.len {
+            self.gate.notify_all();
+        }
+    }
+}
+
 impl<W: Worker, Q: Queen<Kind = W>> fmt::Debug for Shared<W, Q> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let (queued, active) = self.num_tasks();
@@ -301,6 +908,20 @@ mod affinity {
 // time to wait in between polling the retry queue and then the task receiver
 const RECV_TIMEOUT: Duration = Duration::from_secs(1);
 
+/// Marks a task as finished within its group when dropped.
+pub struct GroupGuard<'a, W: Worker, Q: Queen<Kind = W>> {
+    shared: &'a Shared<W, Q>,
+    group: Arc<str>,
+}
+
+impl<W: Worker, Q: Queen<Kind = W>> Drop for GroupGuard<'_, W, Q> {
+    fn drop(&mut self) {
+        self.shared.groups.lock().finish(&self.group);
+        // wake up any worker threads waiting for a deferred task in this group to be startable
+        self.shared.deferred_gate.notify_all();
+    }
+}
+
 // TODO: if `outcomes` were `DerefMut` then the argument could either be a mutable referece or
 // a Lazy<Mutex> that aquires the lock on first access. Unfortunately, rust's Lazy does not support
 // mutable access, so we'd need something like OnceCell or OnceMutex.
@@ -332,6 +953,7 @@ mod no_retry {
     use crate::bee::{Queen, Worker};
     use crate::hive::{Husk, Shared, Task};
     use std::sync::mpsc::RecvTimeoutError;
+    use std::time::Instant;
 
     impl<W: Worker, Q: Queen<Kind = W>> Shared<W, Q> {
         /// Returns the next queued `Task`. The thread blocks until a new task becomes available, and
@@ -340,24 +962,74 @@ mod no_retry {
         /// are no tasks queued. Also returns `None` if the cancelled flag has been set.
         pub fn next_task(&self) -> Result<Task<W>, NextTaskError> {
             loop {
-                self.resume_gate.wait_while(|| self.is_suspended());
+                self.resume_gate
+                    .wait_while(|| self.is_suspended() && !self.is_poisoned());
 
                 if self.is_poisoned() {
                     return Err(NextTaskError::Poisoned);
                 }
 
-                match self.task_rx.lock().recv_timeout(super::RECV_TIMEOUT) {
-                    Ok(task) => break Ok(task),
+                if let Some(task) = self.next_deferred_task() {
+                    break Ok(task);
+                }
+
+                let task = match self.recv_task_timeout(super::RECV_TIMEOUT) {
+                    Ok(task) => task,
+                    Err(RecvTimeoutError::Disconnected) if self.has_deferred_tasks() => {
+                        self.wait_for_deferred(super::RECV_TIMEOUT);
+                        continue;
+                    }
                     Err(RecvTimeoutError::Disconnected) => break Err(NextTaskError::Disconnected),
                     Err(RecvTimeoutError::Timeout) => continue,
+                };
+                if let Some(task) = self.try_start(task) {
+                    break Ok(task);
+                }
+            }
+            .and_then(|task| {
+                match self.num_tasks.transfer(1) {
+                    Ok(_) => Ok(task),
+                    Err(e) => {
+                        // poison the hive so it can't be used anymore
+                        self.poison();
+                        Err(NextTaskError::InvalidCounter(e))
+                    }
+                }
+            })
+        }
+
+        /// Returns the next queued `Task`, waiting until `deadline` for one to become available.
+        /// Returns `None` if no task becomes available before the deadline, or if the hive is
+        /// suspended or poisoned.
+        pub fn next_task_until(&self, deadline: Instant) -> Option<Task<W>> {
+            loop {
+                if self.is_suspended() || self.is_poisoned() {
+                    return None;
+                }
+
+                if let Some(task) = self.next_deferred_task() {
+                    break Some(task);
+                }
+
+                let task = match self.recv_task_timeout(self.clock.timeout_until(deadline)) {
+                    Ok(task) => task,
+                    Err(_) if self.clock.now() >= deadline => return None,
+                    Err(RecvTimeoutError::Disconnected) if self.has_deferred_tasks() => {
+                        self.wait_for_deferred(self.clock.timeout_until(deadline));
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => return None,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                };
+                if let Some(task) = self.try_start(task) {
+                    break Some(task);
                 }
             }
             .and_then(|task| match self.num_tasks.transfer(1) {
-                Ok(_) => Ok(task),
-                Err(e) => {
-                    // poison the hive so it can't be used anymore
+                Ok(_) => Some(task),
+                Err(_) => {
                     self.poison();
-                    Err(NextTaskError::InvalidCounter(e))
+                    None
                 }
             })
         }
@@ -366,9 +1038,16 @@ mod no_retry {
         /// to send them or (if the task does not have a sender, or if the send fails) stores them
         /// in the `outcomes` map.
         pub fn drain_tasks_into_unprocessed(&self) {
-            let task_rx = self.task_rx.lock();
-            let mut outcomes = self.outcomes.lock();
-            send_or_store(task_rx.try_iter(), &mut outcomes);
+            {
+                let task_rx = self.task_rx.lock();
+                let mut outcomes = self.outcomes.lock();
+                send_or_store(task_rx.try_iter(), &mut outcomes);
+                // deferred tasks do not hold slots in their groups (see `try_start`), so there
+                // are no group slots to release
+                send_or_store(self.groups.lock().drain_deferred(), &mut outcomes);
+                send_or_store(self.rate_deferred.lock().drain(), &mut outcomes);
+            }
+            self.stored_gate.notify_all();
         }
 
         /// Consumes this `Shared` and returns a `Husk` containing the `Queen`, panic count, stored
@@ -379,11 +1058,18 @@ mod no_retry {
             let task_rx = self.task_rx.into_inner();
             let mut outcomes = self.outcomes.into_inner();
             send_or_store(task_rx.try_iter(), &mut outcomes);
+            send_or_store(self.groups.into_inner().drain_deferred(), &mut outcomes);
+            send_or_store(self.rate_deferred.into_inner().drain(), &mut outcomes);
+            let mut config = self.config.into_unsync();
+            config
+                .next_task_index
+                .set(Some(self.next_task_index.into_inner()));
             Husk::new(
-                self.config.into_unsync(),
+                config,
                 self.queen.into_inner(),
                 self.num_panics.into_inner(),
                 outcomes,
+                self.rate_limit_key.into_inner(),
             )
         }
     }
@@ -445,7 +1131,7 @@ mod retry {
             self.num_tasks
                 .increment_left(1)
                 .expect("overflowed queued task counter");
-            let available_at = queue.push(task, delay);
+            let available_at = queue.push(task, delay, self.clock.now());
             self.update_next_retry(Some(available_at));
         }
 
@@ -455,28 +1141,32 @@ mod retry {
         /// are no tasks queued for retry.
         pub fn next_task(&self) -> Result<Task<W>, NextTaskError> {
             loop {
-                self.resume_gate.wait_while(|| self.is_suspended());
+                self.resume_gate
+                    .wait_while(|| self.is_suspended() && !self.is_poisoned());
 
                 if self.is_poisoned() {
                     return Err(NextTaskError::Poisoned);
                 }
 
-                let has_retry = {
-                    let next_retry = self.next_retry.read();
-                    next_retry.is_some_and(|next_retry| next_retry <= Instant::now())
-                };
-                if has_retry {
-                    let mut queue = self.retry_queue.lock();
-                    if let Some(task) = queue.try_pop() {
-                        self.update_next_retry(queue.next_available());
-                        break Ok(task);
-                    }
+                if let Some(task) = self.next_deferred_task() {
+                    break Ok(task);
                 }
 
-                match self.task_rx.lock().recv_timeout(super::RECV_TIMEOUT) {
-                    Ok(task) => break Ok(task),
+                if let Some(task) = self.try_pop_retry().and_then(|task| self.try_start(task)) {
+                    break Ok(task);
+                }
+
+                let task = match self.recv_task_timeout(super::RECV_TIMEOUT) {
+                    Ok(task) => task,
+                    Err(RecvTimeoutError::Disconnected) if self.has_deferred_tasks() => {
+                        self.wait_for_deferred(super::RECV_TIMEOUT);
+                        continue;
+                    }
                     Err(RecvTimeoutError::Disconnected) => break Err(NextTaskError::Disconnected),
                     Err(RecvTimeoutError::Timeout) => continue,
+                };
+                if let Some(task) = self.try_start(task) {
+                    break Ok(task);
                 }
             }
             .and_then(|task| match self.num_tasks.transfer(1) {
@@ -485,15 +1175,78 @@ mod retry {
             })
         }
 
+        /// Returns the next queued `Task`, waiting until `deadline` for one to become available.
+        /// Returns `None` if no task becomes available before the deadline, or if the hive is
+        /// suspended or poisoned.
+        pub fn next_task_until(&self, deadline: Instant) -> Option<Task<W>> {
+            loop {
+                if self.is_suspended() || self.is_poisoned() {
+                    return None;
+                }
+
+                if let Some(task) = self.next_deferred_task() {
+                    break Some(task);
+                }
+
+                if let Some(task) = self.try_pop_retry().and_then(|task| self.try_start(task)) {
+                    break Some(task);
+                }
+
+                let task = match self.recv_task_timeout(self.clock.timeout_until(deadline)) {
+                    Ok(task) => task,
+                    Err(_) if self.clock.now() >= deadline => return None,
+                    Err(RecvTimeoutError::Disconnected) if self.has_deferred_tasks() => {
+                        self.wait_for_deferred(self.clock.timeout_until(deadline));
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => return None,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                };
+                if let Some(task) = self.try_start(task) {
+                    break Some(task);
+                }
+            }
+            .and_then(|task| match self.num_tasks.transfer(1) {
+                Ok(_) => Some(task),
+                Err(_) => {
+                    self.poison();
+                    None
+                }
+            })
+        }
+
+        /// Removes and returns the task at the head of the retry queue if it is available.
+        fn try_pop_retry(&self) -> Option<Task<W>> {
+            let has_retry = {
+                let next_retry = self.next_retry.read();
+                next_retry.is_some_and(|next_retry| next_retry <= self.clock.now())
+            };
+            if has_retry {
+                let mut queue = self.retry_queue.lock();
+                if let Some(task) = queue.try_pop(self.clock.now()) {
+                    self.update_next_retry(queue.next_available());
+                    return Some(task);
+                }
+            }
+            None
+        }
+
         /// Drains all queued tasks, converts them into `Outcome::Unprocessed` outcomes, and tries
         /// to send them or (if the task does not have a sender, or if the send fails) stores them
         /// in the `outcomes` map.
         pub fn drain_tasks_into_unprocessed(&self) {
-            let mut outcomes = self.outcomes.lock();
-            let task_rx = self.task_rx.lock();
-            super::send_or_store(task_rx.try_iter(), &mut outcomes);
-            let mut retry_queue = self.retry_queue.lock();
-            super::send_or_store(retry_queue.drain(), &mut outcomes);
+            {
+                let mut outcomes = self.outcomes.lock();
+                let task_rx = self.task_rx.lock();
+                super::send_or_store(task_rx.try_iter(), &mut outcomes);
+                let mut retry_queue = self.retry_queue.lock();
+                super::send_or_store(retry_queue.drain(), &mut outcomes);
+                // deferred tasks do not hold slots in their groups (see `try_start`), so there
+                // are no group slots to release
+                super::send_or_store(self.groups.lock().drain_deferred(), &mut outcomes);
+                super::send_or_store(self.rate_deferred.lock().drain(), &mut outcomes);
+            }
+            self.stored_gate.notify_all();
         }
 
         /// Consumes this `Shared` and returns a `Husk` containing the `Queen`, panic count, stored
@@ -506,11 +1259,18 @@ mod retry {
             super::send_or_store(task_rx.try_iter(), &mut outcomes);
             let mut retry_queue = self.retry_queue.into_inner();
             super::send_or_store(retry_queue.drain(), &mut outcomes);
+            super::send_or_store(self.groups.into_inner().drain_deferred(), &mut outcomes);
+            super::send_or_store(self.rate_deferred.into_inner().drain(), &mut outcomes);
+            let mut config = self.config.into_unsync();
+            config
+                .next_task_index
+                .set(Some(self.next_task_index.into_inner()));
             Husk::new(
-                self.config.into_unsync(),
+                config,
                 self.queen.into_inner(),
                 self.num_panics.into_inner(),
                 outcomes,
+                self.rate_limit_key.into_inner(),
             )
         }
     }
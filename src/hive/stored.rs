@@ -0,0 +1,129 @@
+//! Iteration over outcomes as they are stored in a `Hive`.
+use super::{Outcome, Shared};
+use crate::bee::{Queen, Worker};
+
+/// An iterator that removes and yields the outcomes stored in a `Hive` as soon as they become
+/// available. If `indices` is `Some`, only the outcomes of those tasks are yielded, otherwise all
+/// stored outcomes are yielded.
+///
+/// The iterator ends once all of the `indices` have been yielded, or once there are no more
+/// stored outcomes and the `Hive` has no more work to do (as determined by `join`). If there are
+/// `indices` whose outcomes will never be stored (e.g., because the `Worker` panicked), an
+/// `Outcome::Missing` is yielded for each of them.
+pub struct StoredIter<'a, W: Worker, Q: Queen<Kind = W>> {
+    shared: &'a Shared<W, Q>,
+    // indices of the outcomes that have not yet been yielded, if only specific outcomes are wanted
+    indices: Option<Vec<usize>>,
+}
+
+impl<'a, W: Worker, Q: Queen<Kind = W>> StoredIter<'a, W, Q> {
+    pub fn new(shared: &'a Shared<W, Q>, indices: Option<Vec<usize>>) -> Self {
+        Self { shared, indices }
+    }
+}
+
+impl<W: Worker, Q: Queen<Kind = W>> Iterator for StoredIter<'_, W, Q> {
+    type Item = Outcome<W>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.indices.as_mut() {
+            None => self.shared.wait_take_stored(None),
+            Some(indices) if indices.is_empty() => None,
+            Some(indices) => {
+                let outcome = self
+                    .shared
+                    .wait_take_stored(Some(indices))
+                    .unwrap_or(Outcome::Missing { index: indices[0] });
+                if let Some(pos) = indices.iter().position(|index| index == outcome.index()) {
+                    indices.remove(pos);
+                }
+                Some(outcome)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bee::stock::{Caller, Thunk, ThunkWorker};
+    use crate::hive::{Builder, Outcome, OutcomeStore, StoreLimitPolicy};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_stored_iter() {
+        let hive = Builder::new()
+            .num_threads(2)
+            .build_with_default::<ThunkWorker<u64>>()
+            .unwrap();
+        // the last task does not complete until it is released
+        let release = Arc::new(AtomicBool::new(false));
+        hive.map_store((0..4).map(|i| {
+            let release = Arc::clone(&release);
+            Thunk::of(move || {
+                while i == 3 && !release.load(Ordering::Acquire) {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                i
+            })
+        }));
+        // outcomes are yielded (in any order) before the last task has completed
+        let mut stored = hive.stored_iter();
+        let mut yielded: Vec<_> = stored.by_ref().take(3).map(Outcome::unwrap).collect();
+        yielded.sort();
+        assert_eq!(yielded, vec![0, 1, 2]);
+        release.store(true, Ordering::Release);
+        assert_eq!(stored.next().unwrap().unwrap(), 3);
+        assert!(stored.next().is_none());
+        assert!(hive.take_stored().is_empty());
+    }
+
+    #[test]
+    fn test_stored_iter_for() {
+        let hive = Builder::new()
+            .num_threads(2)
+            .build_with(Caller::of(|i: usize| i * 2))
+            .unwrap();
+        let indices = hive.map_store(0..10);
+        let mut outputs: Vec<_> = hive
+            .stored_iter_for(indices[5..].to_vec())
+            .map(Outcome::unwrap)
+            .collect();
+        outputs.sort();
+        assert_eq!(outputs, vec![10, 12, 14, 16, 18]);
+        // the other outcomes remain in the hive
+        hive.join();
+        assert_eq!(hive.num_successes(), 5);
+    }
+
+    #[test]
+    fn test_stored_iter_for_missing() {
+        let hive = Builder::new()
+            .num_threads(1)
+            .build_with(Caller::of(|i: usize| i))
+            .unwrap();
+        let index = hive.apply_store(1);
+        // this index will never be stored
+        let mut outcomes: Vec<_> = hive.stored_iter_for(vec![index + 1, index]).collect();
+        outcomes.sort_by_key(|outcome| *outcome.index());
+        assert!(outcomes[0].is_success());
+        assert!(matches!(outcomes[1], Outcome::Missing { .. }));
+    }
+
+    #[test]
+    fn test_drain_stored_blocking() {
+        let hive = Builder::new()
+            .num_threads(4)
+            .max_stored_outcomes(2)
+            .store_limit_policy(StoreLimitPolicy::Block)
+            .build_with(Caller::of(|i: usize| i))
+            .unwrap();
+        hive.map_store(0..20);
+        // `join` would block forever since outcomes would not be removed
+        let outcomes = hive.drain_stored_blocking();
+        assert_eq!(outcomes.num_successes(), 20);
+        assert_eq!(hive.num_tasks(), (0, 0));
+    }
+}
@@ -0,0 +1,247 @@
+//! Lazy submission of tasks from an iterator with a bounded number of tasks in flight.
+use super::{outcome_channel, Hive, Outcome, OutcomeReceiver, OutcomeSender};
+use crate::bee::{Queen, Worker};
+use std::collections::{HashMap, VecDeque};
+
+/// An iterator that pulls inputs lazily from an iterator, submits them to a `Hive`, and yields
+/// their `Outcome`s. At most `window` tasks are submitted whose outcomes have not yet been
+/// yielded.
+///
+/// If `ordered` is `true`, outcomes are yielded in the same order as the inputs, otherwise they
+/// are yielded in the order they become available.
+///
+/// All tasks are submitted with the same outcome channel. If the outcome of a task is lost (e.g.,
+/// because a worker panicked), the `Hive` sends `Outcome::Missing` in its place, so the iterator
+/// blocks on the channel without having to check the state of the `Hive`.
+pub struct Streaming<'a, W: Worker, Q: Queen<Kind = W>, I> {
+    hive: &'a Hive<W, Q>,
+    // `None` once the inputs have been exhausted
+    inputs: Option<I>,
+    window: usize,
+    ordered: bool,
+    tx: OutcomeSender<W>,
+    rx: OutcomeReceiver<W>,
+    // indices of the tasks whose outcomes have not yet been yielded, in submission order
+    pending: VecDeque<usize>,
+    // outcomes that were received before the outcomes of earlier tasks (only if `ordered`)
+    received: HashMap<usize, Outcome<W>>,
+}
+
+impl<'a, W, Q, I> Streaming<'a, W, Q, I>
+where
+    W: Worker,
+    Q: Queen<Kind = W>,
+    I: Iterator<Item = W::Input>,
+{
+    /// Creates a new `Streaming` iterator.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is `0`.
+    pub fn new(hive: &'a Hive<W, Q>, inputs: I, window: usize, ordered: bool) -> Self {
+        assert!(window > 0, "window must be greater than 0");
+        let (tx, rx) = outcome_channel();
+        Self {
+            hive,
+            inputs: Some(inputs),
+            window,
+            ordered,
+            tx,
+            rx,
+            pending: VecDeque::with_capacity(window),
+            received: HashMap::new(),
+        }
+    }
+
+    /// Submits inputs until the window is full or there are no more inputs.
+    fn fill(&mut self) {
+        while self.pending.len() < self.window {
+            match self.inputs.as_mut().and_then(Iterator::next) {
+                Some(input) => {
+                    let index = self.hive.apply_send(input, self.tx.clone());
+                    self.pending.push_back(index);
+                }
+                None => {
+                    self.inputs = None;
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Blocks until the next outcome is received.
+    fn recv(&self) -> Outcome<W> {
+        // this iterator holds a sender, so the channel never disconnects
+        self.rx
+            .recv()
+            .expect("streaming outcome channel disconnected")
+    }
+
+    /// Returns the next outcome in submission order.
+    fn next_ordered(&mut self) -> Option<Outcome<W>> {
+        let index = self.pending.pop_front()?;
+        if let Some(outcome) = self.received.remove(&index) {
+            return Some(outcome);
+        }
+        loop {
+            let outcome = self.recv();
+            if *outcome.index() == index {
+                return Some(outcome);
+            }
+            self.received.insert(*outcome.index(), outcome);
+        }
+    }
+
+    /// Returns the next outcome to become available.
+    fn next_unordered(&mut self) -> Option<Outcome<W>> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let outcome = self.recv();
+        if let Some(pos) = self
+            .pending
+            .iter()
+            .position(|index| index == outcome.index())
+        {
+            self.pending.remove(pos);
+        }
+        Some(outcome)
+    }
+}
+
+impl<W, Q, I> Iterator for Streaming<'_, W, Q, I>
+where
+    W: Worker,
+    Q: Queen<Kind = W>,
+    I: Iterator<Item = W::Input>,
+{
+    type Item = Outcome<W>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.fill();
+        if self.ordered {
+            self.next_ordered()
+        } else {
+            self.next_unordered()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bee::stock::{Caller, EchoWorker, Thunk, ThunkWorker};
+    use crate::hive::{Builder, Outcome};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_map_streaming() {
+        let hive = Builder::new()
+            .num_threads(4)
+            .build_with_default::<EchoWorker<usize>>()
+            .unwrap();
+        let outputs: Vec<_> = hive.map_streaming(0..100, 8).map(Outcome::unwrap).collect();
+        assert_eq!(outputs, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_map_streaming_unordered() {
+        let hive = Builder::new()
+            .num_threads(4)
+            .build_with(Caller::of(|i: u64| {
+                thread::sleep(Duration::from_millis(10 * (i % 3)));
+                i
+            }))
+            .unwrap();
+        let mut outputs: Vec<_> = hive
+            .map_streaming_unordered(0..50, 8)
+            .map(Outcome::unwrap)
+            .collect();
+        outputs.sort();
+        assert_eq!(outputs, (0..50).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_map_streaming_window() {
+        let hive = Builder::new()
+            .num_threads(4)
+            .build_with_default::<EchoWorker<usize>>()
+            .unwrap();
+        let submitted = AtomicUsize::new(0);
+        // an infinite iterator that counts how many inputs have been pulled
+        let inputs = (0..).inspect(|_| {
+            submitted.fetch_add(1, Ordering::SeqCst);
+        });
+        let mut outcomes = hive.map_streaming(inputs, 5);
+        for i in 0..10 {
+            assert_eq!(outcomes.next().unwrap().unwrap(), i);
+            assert!(submitted.load(Ordering::SeqCst) <= i + 5);
+        }
+    }
+
+    #[test]
+    fn test_map_streaming_lost_outcome() {
+        let hive = Builder::new()
+            .num_threads(2)
+            .build_with_default::<ThunkWorker<usize>>()
+            .unwrap();
+        let count = Arc::new(AtomicUsize::new(0));
+        let inputs = (0..4).map(|i| {
+            let count = Arc::clone(&count);
+            Thunk::of(move || {
+                count.fetch_add(1, Ordering::SeqCst);
+                // `ThunkWorker` does not catch panics, so the outcome is lost
+                assert!(i != 2, "oh no!");
+                i
+            })
+        });
+        let outcomes: Vec<_> = hive.map_streaming(inputs, 2).collect();
+        assert_eq!(outcomes.len(), 4);
+        assert!(matches!(outcomes[2], Outcome::Missing { .. }));
+        assert_eq!(count.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_map_streaming_lost_outcome_busy_hive() {
+        let hive = Builder::new()
+            .num_threads(2)
+            .build_with_default::<ThunkWorker<usize>>()
+            .unwrap();
+        // another task keeps the hive busy while the outcomes are streamed
+        let done = Arc::new(AtomicBool::new(false));
+        {
+            let done = Arc::clone(&done);
+            hive.apply_store(Thunk::of(move || {
+                while !done.load(Ordering::Acquire) {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                0
+            }));
+        }
+        for ordered in [true, false] {
+            let inputs = (0..4).map(|i| {
+                Thunk::of(move || {
+                    assert!(i != 2, "oh no!");
+                    i
+                })
+            });
+            let outcomes: Vec<_> = if ordered {
+                hive.map_streaming(inputs, 2).collect()
+            } else {
+                hive.map_streaming_unordered(inputs, 2).collect()
+            };
+            assert_eq!(outcomes.len(), 4);
+            assert_eq!(
+                outcomes
+                    .iter()
+                    .filter(|outcome| matches!(outcome, Outcome::Missing { .. }))
+                    .count(),
+                1
+            );
+        }
+        done.store(true, Ordering::Release);
+        hive.join();
+    }
+}
@@ -61,6 +61,9 @@
 //!             * Create a custom worker fatory that implements the `Queen` trait
 //! 2. A `Hive` to execute your tasks. Your options are:
 //!     * Use one of the convenience methods in the [util](crate::util) module (see Example 1 below)
+//!     * Call one of the `par_*` methods (e.g., `par_map`) on any `Iterator`, which are provided
+//!       by the [`HiveIteratorExt`](crate::hive::HiveIteratorExt) extension trait. These use a
+//!       temporary `Hive`, or an existing `Hive` may be used by calling `into_hive`.
 //!     * Create a `Hive` manually using [`Builder`](crate::hive::builder::Builder) (see Examples 2
 //!       and 3 below)
 //!         * [`Builder::new()`](crate::hive::builder::Builder::new) creates an empty `Builder`
@@ -115,6 +118,9 @@
 //! After submitting tasks, you may use the [`Hive::join()`](crate::hive::Hive#join) method to wait
 //! for all tasks to complete. Using `join` is strongly recommended when using one of the `_store`
 //! methods, otherwise you'll need to continually poll the `Hive` to check for completed tasks.
+//! Stored outcomes are retained until they are removed, so a `Hive` that is used for a long time
+//! should either remove them regularly or limit how many are retained using
+//! [`Builder::max_stored_outcomes`](crate::hive::Builder::max_stored_outcomes).
 //!
 //! When you are finished with a `Hive`, you may simply drop it (either explicitly, or by letting
 //! it go out of scope) - the worker threads will be terminated automatically. If you used the
@@ -123,6 +129,10 @@
 //! parameters, you can use the [`Hive::try_into_husk()`](crate::hive::Hive#try_into_husk) method to extract
 //! the relevant data from the `Hive` into a [`Husk`](crate::hive::husk::Husk) object.
 //!
+//! If you need several `Hive`s with different `Worker` types, you can avoid oversubscribing the
+//! CPU by building them without worker threads and registering them with a shared
+//! [`Apiary`](crate::hive::Apiary), whose threads execute the tasks of all its member `Hive`s.
+//!
 //! ## Examples
 //!
 //! ### 1. Parallelize an existing function
@@ -1,30 +1,132 @@
 use super::boxed::BoxedFnOnce;
 use std::any::Any;
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::cell::{Cell, RefCell};
 use std::fmt::Debug;
 use std::panic::AssertUnwindSafe;
+use std::sync::Once;
+use std::thread;
 
 pub type PanicPayload = Box<dyn Any + Send + 'static>;
 
-/// Wraps a payload from a caught `panic` with an optional `detail`.
+thread_local! {
+    // backtrace captured by the panic hook for the most recent panic on this thread
+    static BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+    // whether this thread is currently executing a function via `Panic::try_call`
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+    // index of the `Hive` worker thread, if this is one
+    static THREAD_INDEX: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+static INSTALL_HOOK: Once = Once::new();
+
+/// Installs a panic hook that captures a backtrace of each panic that is caught by a `Panic` (i.e.,
+/// a panic in a task executed by a `Hive`), so that it is available from [`Panic::backtrace`].
+/// The hook calls the previously installed hook after capturing the backtrace. Backtraces are
+/// only captured if enabled via the `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE` environment
+/// variables.
+///
+/// This function replaces the process-wide panic hook, so it is never called implicitly. It
+/// should be called once, before any tasks are submitted. Calling it more than once has no
+/// effect. If another panic hook is installed afterward (without calling the hook it replaces),
+/// backtraces are no longer captured.
+pub fn capture_backtraces() {
+    INSTALL_HOOK.call_once(|| {
+        let prev_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if CATCHING.with(Cell::get) {
+                let backtrace = Backtrace::capture();
+                if backtrace.status() == BacktraceStatus::Captured {
+                    BACKTRACE.with(|cell| *cell.borrow_mut() = Some(backtrace));
+                }
+            }
+            prev_hook(info);
+        }));
+    });
+}
+
+/// Calls `f` and catches any panic, with the `CATCHING` flag set so that the panic hook (if
+/// installed) captures a backtrace.
+fn catch_unwind<O, F: FnOnce() -> O>(f: F) -> Result<O, PanicPayload> {
+    BACKTRACE.with(|cell| cell.borrow_mut().take());
+    let catching = CATCHING.with(|cell| cell.replace(true));
+    let result = std::panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|cell| cell.set(catching));
+    result
+}
+
+/// Records that the current thread is the `Hive` worker thread with the given `index`.
+pub(crate) fn set_thread_index(index: usize) {
+    THREAD_INDEX.with(|cell| cell.set(Some(index)));
+}
+
+/// Wraps a payload from a caught `panic` with an optional `detail`, along with information about
+/// where the panic occurred.
 #[derive(Debug)]
 pub struct Panic<T: Send + Debug + Eq> {
     payload: PanicPayload,
     detail: Option<T>,
+    // boxed to keep `Panic` (and thus `ApplyError`) small
+    origin: Box<Origin>,
+}
+
+/// Information about where a panic occurred.
+#[derive(Debug)]
+struct Origin {
+    backtrace: Option<Backtrace>,
+    thread_index: Option<usize>,
+    thread_name: Option<String>,
+    attempt: u32,
 }
 
 impl<T: Send + Debug + Eq> Panic<T> {
+    fn from_payload(payload: PanicPayload, detail: Option<T>) -> Self {
+        Self {
+            payload,
+            detail,
+            origin: Box::new(Origin {
+                backtrace: BACKTRACE.with(|cell| cell.borrow_mut().take()),
+                thread_index: THREAD_INDEX.with(|cell| cell.get()),
+                thread_name: thread::current().name().map(String::from),
+                attempt: 0,
+            }),
+        }
+    }
+
     /// Attempts to call the provided function `f` and catches any panic. Returns either the return
     /// value of the function or a `Panic` created from the panic payload and the provided `detail`.
     pub fn try_call<O, F: FnOnce() -> O>(detail: Option<T>, f: F) -> Result<O, Self> {
-        std::panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| Self { payload, detail })
+        catch_unwind(f).map_err(|payload| Self::from_payload(payload, detail))
     }
 
     pub(crate) fn try_call_boxed<O, F: BoxedFnOnce<Output = O> + ?Sized>(
         detail: Option<T>,
         f: Box<F>,
     ) -> Result<O, Self> {
-        std::panic::catch_unwind(AssertUnwindSafe(|| f.call_box()))
-            .map_err(|payload| Self { payload, detail })
+        catch_unwind(|| f.call_box()).map_err(|payload| Self::from_payload(payload, detail))
+    }
+
+    /// Returns a new `Panic` with the same origin (but without a backtrace or detail), whose payload
+    /// is a copy of this panic's message if the payload is a `&str` or `String`. Used to give a
+    /// panic to every task in a batch, since the payload itself cannot be cloned.
+    pub(crate) fn duplicate(&self) -> Self {
+        let message = if let Some(message) = self.payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = self.payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "Box<dyn Any>".into()
+        };
+        Self {
+            payload: Box::new(message),
+            detail: None,
+            origin: Box::new(Origin {
+                backtrace: None,
+                thread_index: self.origin.thread_index,
+                thread_name: self.origin.thread_name.clone(),
+                attempt: self.origin.attempt,
+            }),
+        }
     }
 
     /// Returns the payload of the panic.
@@ -37,6 +139,35 @@ impl<T: Send + Debug + Eq> Panic<T> {
         self.detail.as_ref()
     }
 
+    /// Returns the backtrace of the panic, if one was captured. Backtraces are only captured if
+    /// the hook has been installed by calling [`capture_backtraces`], and if enabled by the
+    /// `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE` environment variables.
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        self.origin.backtrace.as_ref()
+    }
+
+    /// Returns the index of the `Hive` worker thread on which the panic occurred, or `None` if it
+    /// did not occur on a worker thread.
+    pub fn thread_index(&self) -> Option<usize> {
+        self.origin.thread_index
+    }
+
+    /// Returns the name of the thread on which the panic occurred, if the thread has a name.
+    pub fn thread_name(&self) -> Option<&str> {
+        self.origin.thread_name.as_deref()
+    }
+
+    /// Returns the retry attempt of the task that panicked (`0` for the first attempt).
+    pub fn attempt(&self) -> u32 {
+        self.origin.attempt
+    }
+
+    /// Sets the retry attempt of the task that panicked.
+    #[cfg(feature = "retry")]
+    pub(crate) fn set_attempt(&mut self, attempt: u32) {
+        self.origin.attempt = attempt;
+    }
+
     /// Consumes this `Panic` and resumes unwinding the thread.
     pub fn resume(self) -> ! {
         std::panic::resume_unwind(self.payload)
@@ -54,6 +185,7 @@ impl<T: Send + Debug + Eq> Eq for Panic<T> {}
 #[cfg(test)]
 mod tests {
     use super::Panic;
+    use std::backtrace::BacktraceStatus;
     use std::fmt::Debug;
 
     impl<T: Send + Debug + Eq> Panic<T> {
@@ -62,7 +194,7 @@ mod tests {
             let payload = std::panic::catch_unwind(|| panic!("{}", msg))
                 .err()
                 .unwrap();
-            Self { payload, detail }
+            Self::from_payload(payload, detail)
         }
     }
 
@@ -73,6 +205,36 @@ mod tests {
         assert_eq!(*panic.detail().unwrap(), "test");
     }
 
+    #[test]
+    fn test_panic_location() {
+        let handle = std::thread::Builder::new()
+            .name("panicky".into())
+            .spawn(|| {
+                super::set_thread_index(3);
+                Panic::<String>::try_call(None, || panic!("panic!")).unwrap_err()
+            })
+            .unwrap();
+        let panic = handle.join().unwrap();
+        assert_eq!(panic.thread_index(), Some(3));
+        assert_eq!(panic.thread_name(), Some("panicky"));
+        assert_eq!(panic.attempt(), 0);
+        let panic = Panic::<String>::try_call(None, || panic!("panic!")).unwrap_err();
+        assert_eq!(panic.thread_index(), None);
+    }
+
+    #[test]
+    fn test_capture_backtraces() {
+        super::capture_backtraces();
+        let panic = Panic::<String>::try_call(None, || panic!("panic!")).unwrap_err();
+        // whether a backtrace is captured depends on the environment
+        if std::backtrace::Backtrace::capture().status() == BacktraceStatus::Captured {
+            assert!(panic.backtrace().is_some());
+        }
+        // a panic that is not caught by `try_call` does not leave a backtrace behind
+        let _ = std::panic::catch_unwind(|| panic!("panic!"));
+        assert!(super::BACKTRACE.with(|cell| cell.borrow().is_none()));
+    }
+
     #[test]
     #[should_panic]
     fn test_resume_panic() {
@@ -74,6 +74,54 @@ where
         .into()
 }
 
+/// Convenience function that creates a `Hive` with `num_threads` worker threads that execute
+/// `map_fn` on the provided inputs and reduce the successful outputs to a single value using
+/// `reduce_fn`. Outputs are reduced in the order they become available, so the result should not
+/// depend on the order in which values are reduced.
+///
+/// Returns the reduced value (or `None` if there were no successful outputs) and an
+/// `OutcomeBatch` of the tasks that failed.
+///
+/// # Examples
+///
+/// ```
+/// # use beekeeper::hive::OutcomeStore;
+/// # fn main() {
+/// let (total, failures) = beekeeper::util::map_reduce(
+///     4,
+///     ["1", "2", "three", "4"],
+///     |s| s.parse::<u32>(),
+///     |a, b| a + b,
+/// );
+/// assert_eq!(total, Some(7));
+/// assert_eq!(failures.num_failures(), 1);
+/// # }
+/// ```
+#[allow(clippy::type_complexity)]
+pub fn map_reduce<I, O, E, Inputs, F, R>(
+    num_threads: usize,
+    inputs: Inputs,
+    map_fn: F,
+    reduce_fn: R,
+) -> (Option<O>, OutcomeBatch<OnceCaller<I, O, E, F>>)
+where
+    I: Send + Sync + 'static,
+    O: Send + Sync + 'static,
+    E: Send + Sync + Debug + 'static,
+    Inputs: IntoIterator<Item = I>,
+    F: FnMut(I) -> Result<O, E> + Send + Sync + Clone + 'static,
+    R: Fn(O, O) -> O,
+{
+    Builder::default()
+        .num_threads(num_threads)
+        .build_with(OnceCaller::of(map_fn))
+        .unwrap()
+        .fold_outcomes(inputs, None, |acc, value| match acc {
+            Some(acc) => Some(reduce_fn(acc, value)),
+            None => Some(value),
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use crate::hive::{Outcome, OutcomeStore};
@@ -106,6 +154,25 @@ mod tests {
         assert_eq!(99, result.num_successes());
         assert!(result.ok_or_unwrap_errors(true).is_err());
     }
+
+    #[test]
+    fn test_map_reduce() {
+        let (sum, failures) = super::map_reduce(
+            4,
+            0..100u64,
+            |i| if i % 10 == 0 { Err(i) } else { Ok(i) },
+            |a, b| a + b,
+        );
+        assert_eq!(sum, Some(4950 - 450));
+        assert_eq!(failures.num_failures(), 10);
+    }
+
+    #[test]
+    fn test_map_reduce_empty() {
+        let (sum, failures) = super::map_reduce(4, Vec::<u64>::new(), Ok::<_, ()>, |a, b| a + b);
+        assert_eq!(sum, None);
+        assert!(failures.is_empty());
+    }
 }
 
 #[cfg(feature = "retry")]